@@ -1,4 +1,7 @@
 use crate::error::ErrorCode;
+use crate::states::operator_approval::OperatorApprovalState;
+use crate::states::pool::PoolState;
+use crate::states::position_lock::position_lock_address;
 use anchor_lang::prelude::*;
 use anchor_spl::token::TokenAccount;
 
@@ -16,23 +19,132 @@ pub fn check_deadline(deadline: i64) -> Result<()> {
     Ok(())
 }
 
-/// Ensures that the signer is the owner or a delgated authority for the position NFT
+/// Checks whether the pool price is within the bounds specified by a user
+///
+/// # Arguments
+///
+/// * `pool_state` - The pool whose price is checked
+/// * `sqrt_price_min_x32` - The minimum Q32.32 sqrt price. Zero for no lower bound
+/// * `sqrt_price_max_x32` - The maximum Q32.32 sqrt price. Zero for no upper bound
+///
+pub fn check_sqrt_price_bounds(
+    pool_state: &AccountInfo,
+    sqrt_price_min_x32: u64,
+    sqrt_price_max_x32: u64,
+) -> Result<()> {
+    let sqrt_price_x32 = AccountLoader::<PoolState>::try_from(pool_state)?
+        .load()?
+        .sqrt_price_x32;
+    require!(
+        sqrt_price_x32 >= sqrt_price_min_x32
+            && (sqrt_price_max_x32 == 0 || sqrt_price_x32 <= sqrt_price_max_x32),
+        ErrorCode::PriceSlippageCheck
+    );
+    Ok(())
+}
+
+/// Ensures that the signer is the owner, a delgated authority or an approved operator for
+/// the position NFT. The owner of a locked position NFT is not authorized, so that liquidity
+/// cannot be decreased before unlock.
 ///
 /// # Arguments
 ///
 /// * `signer` - The signer address
 /// * `token_account` - The token account holding the position NFT
+/// * `remaining_accounts` - Begin with the operator approval of the token account owner for
+/// the signer, if the signer is not the owner or delegate
 ///
 pub fn is_authorized_for_token<'info>(
     signer: &Signer<'info>,
     token_account: &Box<Account<'info, TokenAccount>>,
+    remaining_accounts: &[AccountInfo<'info>],
+) -> Result<()> {
+    check_authority(signer, token_account, remaining_accounts, false)
+}
+
+/// Ensures that the signer can collect fees of the position NFT. Like `is_authorized_for_token`,
+/// but also authorizes the owner of a locked position NFT.
+///
+/// # Arguments
+///
+/// * `signer` - The signer address
+/// * `token_account` - The token account holding the position NFT
+/// * `remaining_accounts` - Begin with the operator approval of the token account owner for
+/// the signer, if the signer is not the owner or delegate
+///
+pub fn is_authorized_to_collect<'info>(
+    signer: &Signer<'info>,
+    token_account: &Box<Account<'info, TokenAccount>>,
+    remaining_accounts: &[AccountInfo<'info>],
 ) -> Result<()> {
+    check_authority(signer, token_account, remaining_accounts, true)
+}
+
+/// Ensures that the position NFT is not held in a lock escrow
+///
+/// # Arguments
+///
+/// * `token_account` - The token account holding the position NFT
+///
+pub fn is_not_locked(token_account: &TokenAccount) -> Result<()> {
+    require!(token_account.amount == 1, ErrorCode::NotApproved);
     require!(
-        token_account.amount == 1
-            && (token_account.owner == signer.key()
-                || (token_account.delegate.contains(&signer.key())
-                    && token_account.delegated_amount > 0)),
+        token_account.owner != position_lock_address(&token_account.mint),
+        ErrorCode::PositionLocked
+    );
+    Ok(())
+}
+
+/// Returns the remaining accounts after the operator approval, which is only passed if the
+/// signer is not the owner or delegate of the position NFT
+///
+/// # Arguments
+///
+/// * `signer` - The signer address
+/// * `token_account` - The token account holding the position NFT
+/// * `remaining_accounts` - The remaining accounts of the instruction
+///
+pub fn skip_operator_approval<'a, 'info>(
+    signer: &Pubkey,
+    token_account: &TokenAccount,
+    remaining_accounts: &'a [AccountInfo<'info>],
+) -> &'a [AccountInfo<'info>] {
+    if is_owner_or_delegate(signer, token_account) || remaining_accounts.is_empty() {
+        remaining_accounts
+    } else {
+        &remaining_accounts[1..]
+    }
+}
+
+fn is_owner_or_delegate(signer: &Pubkey, token_account: &TokenAccount) -> bool {
+    token_account.owner == *signer
+        || (token_account.delegate.contains(signer) && token_account.delegated_amount > 0)
+}
+
+fn check_authority<'info>(
+    signer: &Signer<'info>,
+    token_account: &Account<'info, TokenAccount>,
+    remaining_accounts: &[AccountInfo<'info>],
+    allow_locked: bool,
+) -> Result<()> {
+    require!(token_account.amount == 1, ErrorCode::NotApproved);
+    if is_owner_or_delegate(&signer.key(), token_account) {
+        return Ok(());
+    }
+
+    // Approvals are only created at their PDA, so the stored owner and operator identify them
+    let operator_approval = remaining_accounts.first().ok_or(ErrorCode::NotApproved)?;
+    let approval_loader = AccountLoader::<OperatorApprovalState>::try_from(operator_approval)
+        .map_err(|_| ErrorCode::NotApproved)?;
+    let approval = approval_loader.load()?;
+    require!(
+        approval.owner == token_account.owner && approval.operator == signer.key(),
         ErrorCode::NotApproved
     );
+
+    // Locks approve their owner as an operator of the escrowed NFT
+    if !allow_locked {
+        is_not_locked(token_account)?;
+    }
     Ok(())
 }
@@ -1,10 +1,19 @@
 use crate::error::ErrorCode;
 use crate::program::CyclosCore;
-use crate::states::factory::FactoryState;
+use crate::states::factory::{FactoryState, COLLECTION_SEED};
 use crate::states::fee::{FeeState, FEE_SEED};
+use crate::states::gauge::{
+    EpochGaugeState, EpochState, GaugeControllerState, GaugeState, GaugeVoteState, GaugeVoterState,
+    EPOCH_GAUGE_SEED, EPOCH_SEED, GAUGE_CONTROLLER_SEED, GAUGE_SEED, GAUGE_VOTER_SEED,
+    GAUGE_VOTE_SEED,
+};
+use crate::states::locked_voter::{Escrow, Locker};
+use crate::states::operator_approval::{OperatorApprovalState, OPERATOR_APPROVAL_SEED};
 use crate::states::oracle::{ObservationState, OBSERVATION_SEED};
+use crate::states::pair::PAIR_SEED;
 use crate::states::pool::{PoolState, POOL_SEED};
 use crate::states::position::{PositionState, POSITION_SEED};
+use crate::states::position_lock::{PositionLockState, POSITION_LOCK_SEED};
 use crate::states::tick::{TickState, TICK_SEED};
 use crate::states::tick_bitmap::{TickBitmapState, BITMAP_SEED};
 use crate::states::tokenized_position::TokenizedPositionState;
@@ -12,6 +21,7 @@ use anchor_lang::prelude::*;
 use anchor_spl::associated_token::get_associated_token_address;
 use anchor_spl::associated_token::AssociatedToken;
 use anchor_spl::token::{Mint, Token, TokenAccount};
+use mpl_token_metadata::pda::find_metadata_account;
 use std::mem::size_of;
 
 #[derive(Accounts)]
@@ -60,6 +70,20 @@ pub struct EnableFeeAmount<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct DisableFeeAmount<'info> {
+    /// Valid protocol owner
+    #[account(address = factory_state.load()?.owner)]
+    pub owner: Signer<'info>,
+
+    /// Factory state stores the protocol owner address
+    pub factory_state: AccountLoader<'info, FactoryState>,
+
+    /// The fee tier to disable
+    #[account(mut)]
+    pub fee_state: AccountLoader<'info, FeeState>,
+}
+
 #[derive(Accounts)]
 pub struct SetOwner<'info> {
     /// Current protocol owner
@@ -75,6 +99,74 @@ pub struct SetOwner<'info> {
     pub factory_state: AccountLoader<'info, FactoryState>,
 }
 
+#[derive(Accounts)]
+pub struct SetMetadataConfig<'info> {
+    /// Valid protocol owner
+    #[account(address = factory_state.load()?.owner)]
+    pub owner: Signer<'info>,
+
+    /// Factory state stores the metadata URI and collection name
+    #[account(mut)]
+    pub factory_state: AccountLoader<'info, FactoryState>,
+}
+
+#[derive(Accounts)]
+pub struct CreatePositionCollection<'info> {
+    /// Valid protocol owner, pays to create the collection NFT
+    #[account(mut, address = factory_state.load()?.owner)]
+    pub owner: Signer<'info>,
+
+    /// Mint and update authority of the collection NFT
+    pub factory_state: AccountLoader<'info, FactoryState>,
+
+    /// Mint of the collection NFT
+    #[account(
+        init,
+        seeds = [COLLECTION_SEED.as_bytes()],
+        bump,
+        mint::decimals = 0,
+        mint::authority = factory_state,
+        payer = owner
+    )]
+    pub collection_mint: Box<Account<'info, Mint>>,
+
+    /// Factory owned token account where the collection NFT is minted
+    #[account(
+        init,
+        associated_token::mint = collection_mint,
+        associated_token::authority = factory_state,
+        payer = owner
+    )]
+    pub collection_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// To store metaplex metadata of the collection
+    /// CHECK: Address validated by the metadata program
+    #[account(mut)]
+    pub collection_metadata: UncheckedAccount<'info>,
+
+    /// To store the master edition of the collection
+    /// CHECK: Address validated by the metadata program
+    #[account(mut)]
+    pub collection_master_edition: UncheckedAccount<'info>,
+
+    /// Sysvar for token mint and metadata account creation
+    pub rent: Sysvar<'info, Rent>,
+
+    /// Program to create NFT metadata
+    /// CHECK: Metadata program address constraint applied
+    #[account(address = mpl_token_metadata::ID)]
+    pub metadata_program: UncheckedAccount<'info>,
+
+    /// Program to create the mint and mint the collection NFT
+    pub token_program: Program<'info, Token>,
+
+    /// Program to create an ATA for the collection NFT
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    /// Program to create the mint and metadata accounts
+    pub system_program: Program<'info, System>,
+}
+
 #[derive(Accounts)]
 pub struct CreateAndInitPool<'info> {
     /// Address paying to create the pool. Can be anyone
@@ -84,12 +176,15 @@ pub struct CreateAndInitPool<'info> {
     /// Desired token pair for the pool
     /// token_0 mint address should be smaller than token_1 address
     #[account(
-        constraint = token_0.key() < token_1.key()
+        constraint = token_0.key() < token_1.key() @ ErrorCode::TokensNotSorted
     )]
     pub token_0: Box<Account<'info, Mint>>,
     pub token_1: Box<Account<'info, Mint>>,
 
     /// Stores the desired fee for the pool
+    #[account(
+        constraint = fee_state.load()?.enabled @ ErrorCode::FeeDisabled
+    )]
     pub fee_state: AccountLoader<'info, FeeState>,
 
     /// Initialize an account to store the pool state
@@ -123,6 +218,19 @@ pub struct CreateAndInitPool<'info> {
     )]
     pub initial_observation_state: AccountLoader<'info, ObservationState>,
 
+    /// Lists the fee tiers of all pools for the token pair. Created with the first pool of the pair
+    /// CHECK: Initialized inside function body if empty
+    #[account(
+        mut,
+        seeds = [
+            PAIR_SEED.as_bytes(),
+            token_0.key().as_ref(),
+            token_1.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub pair_state: UncheckedAccount<'info>,
+
     /// To create a new program account
     pub system_program: Program<'info, System>,
 
@@ -130,6 +238,92 @@ pub struct CreateAndInitPool<'info> {
     pub rent: Sysvar<'info, Rent>,
 }
 
+#[derive(Accounts)]
+pub struct RegisterPoolPair<'info> {
+    /// Pays to create the pair registry. Can be anyone
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// The pool to register
+    pub pool_state: AccountLoader<'info, PoolState>,
+
+    /// Lists the fee tiers of all pools for the token pair. Created if it doesn't exist
+    /// CHECK: Initialized inside function body if empty
+    #[account(
+        mut,
+        seeds = [
+            PAIR_SEED.as_bytes(),
+            pool_state.load()?.token_0.as_ref(),
+            pool_state.load()?.token_1.as_ref(),
+        ],
+        bump,
+    )]
+    pub pair_state: UncheckedAccount<'info>,
+
+    /// To create a new program account
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReinitializePrice<'info> {
+    /// Address reinitializing the price. Can be anyone
+    pub signer: Signer<'info>,
+
+    /// The empty pool whose price is reinitialized
+    #[account(mut)]
+    pub pool_state: AccountLoader<'info, PoolState>,
+
+    /// The program account for the most recent oracle observation, at index = pool.observation_index
+    /// CHECK: Safety check performed inside function body
+    #[account(mut)]
+    pub last_observation_state: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateFeeState<'info> {
+    /// Pays rent for the grown account. Can be anyone
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// The fee tier created with an older layout
+    /// CHECK: Owner and discriminator validated inside function body
+    #[account(mut)]
+    pub fee_state: UncheckedAccount<'info>,
+
+    /// To top up rent of the grown account
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigratePoolState<'info> {
+    /// Pays rent for the grown account. Can be anyone
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// The pool created with an older layout
+    /// CHECK: Owner and discriminator validated inside function body
+    #[account(mut)]
+    pub pool_state: UncheckedAccount<'info>,
+
+    /// To top up rent of the grown account
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateFactoryState<'info> {
+    /// Pays rent for the grown account. Can be anyone
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// The factory created with an older layout
+    /// CHECK: Owner and discriminator validated inside function body
+    #[account(mut)]
+    pub factory_state: UncheckedAccount<'info>,
+
+    /// To top up rent of the grown account
+    pub system_program: Program<'info, System>,
+}
+
 #[derive(Accounts)]
 pub struct IncreaseObservationCardinalityNext<'info> {
     /// Pays to increase storage slots for oracle observations
@@ -314,6 +508,64 @@ pub struct InitPositionAccount<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct TransferPosition<'info> {
+    /// The position owner. Pays to create the position account of the new owner
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    /// The new position owner
+    /// CHECK: This is not dangerous because we don't read or write from this account
+    #[account(constraint = new_owner.key() != owner.key())]
+    pub new_owner: UncheckedAccount<'info>,
+
+    /// The pool of the position
+    pub pool_state: AccountLoader<'info, PoolState>,
+
+    /// The lower tick boundary of the position
+    pub tick_lower_state: AccountLoader<'info, TickState>,
+
+    /// The upper tick boundary of the position
+    pub tick_upper_state: AccountLoader<'info, TickState>,
+
+    /// The position to transfer
+    #[account(
+        mut,
+        seeds = [
+            POSITION_SEED.as_bytes(),
+            pool_state.load()?.token_0.as_ref(),
+            pool_state.load()?.token_1.as_ref(),
+            &pool_state.load()?.fee.to_be_bytes(),
+            owner.key().as_ref(),
+            &tick_lower_state.load()?.tick.to_be_bytes(),
+            &tick_upper_state.load()?.tick.to_be_bytes(),
+        ],
+        bump = position_state.load()?.bump,
+    )]
+    pub position_state: AccountLoader<'info, PositionState>,
+
+    /// The position of the new owner, created if it does not exist
+    #[account(
+        init_if_needed,
+        seeds = [
+            POSITION_SEED.as_bytes(),
+            pool_state.load()?.token_0.as_ref(),
+            pool_state.load()?.token_1.as_ref(),
+            &pool_state.load()?.fee.to_be_bytes(),
+            new_owner.key().as_ref(),
+            &tick_lower_state.load()?.tick.to_be_bytes(),
+            &tick_upper_state.load()?.tick.to_be_bytes(),
+        ],
+        bump,
+        payer = owner,
+        space = 8 + size_of::<PositionState>()
+    )]
+    pub new_position_state: AccountLoader<'info, PositionState>,
+
+    /// Program to create the position account
+    pub system_program: Program<'info, System>,
+}
+
 #[derive(Accounts)]
 pub struct MintContext<'info> {
     /// Pays to mint liquidity
@@ -381,6 +633,79 @@ pub struct MintContext<'info> {
     pub callback_handler: UncheckedAccount<'info>,
 }
 
+#[derive(Accounts)]
+pub struct MintWithInit<'info> {
+    /// Pays to mint liquidity, and rent for accounts created by the mint
+    #[account(mut)]
+    pub minter: Signer<'info>,
+
+    /// The token account spending token_0 to mint the position
+    /// CHECK: Safety check performed inside function body
+    #[account(mut)]
+    pub token_account_0: UncheckedAccount<'info>,
+
+    /// The token account spending token_1 to mint the position
+    /// CHECK: Safety check performed inside function body
+    #[account(mut)]
+    pub token_account_1: UncheckedAccount<'info>,
+
+    /// The address that holds pool tokens for token_0
+    #[account(mut)]
+    pub vault_0: Box<Account<'info, TokenAccount>>,
+
+    /// The address that holds pool tokens for token_1
+    #[account(mut)]
+    pub vault_1: Box<Account<'info, TokenAccount>>,
+
+    /// Liquidity is minted on behalf of recipient
+    /// CHECK: This is not dangerous because we don't read or write from this account
+    pub recipient: UncheckedAccount<'info>,
+
+    /// Mint liquidity for this pool
+    #[account(mut)]
+    pub pool_state: AccountLoader<'info, PoolState>,
+
+    /// The lower tick boundary of the position. Created if it doesn't exist
+    /// CHECK: Safety check performed inside function body
+    #[account(mut)]
+    pub tick_lower_state: UncheckedAccount<'info>,
+
+    /// The upper tick boundary of the position. Created if it doesn't exist
+    /// CHECK: Safety check performed inside function body
+    #[account(mut)]
+    pub tick_upper_state: UncheckedAccount<'info>,
+
+    /// The bitmap storing initialization state of the lower tick. Created if it doesn't exist
+    /// CHECK: Safety check performed inside function body
+    #[account(mut)]
+    pub bitmap_lower_state: UncheckedAccount<'info>,
+
+    /// The bitmap storing initialization state of the upper tick. Created if it doesn't exist
+    /// CHECK: Safety check performed inside function body
+    #[account(mut)]
+    pub bitmap_upper_state: UncheckedAccount<'info>,
+
+    /// The position into which liquidity is minted. Created if it doesn't exist
+    /// CHECK: Safety check performed inside function body
+    #[account(mut)]
+    pub position_state: UncheckedAccount<'info>,
+
+    /// The program account for the most recent oracle observation, at index = pool.observation_index
+    /// CHECK: Safety check performed inside function body
+    #[account(mut)]
+    pub last_observation_state: UncheckedAccount<'info>,
+
+    /// The SPL program to perform token transfers
+    pub token_program: Program<'info, Token>,
+
+    /// Program which receives mint_callback
+    /// CHECK: Allow arbitrary callback handlers
+    pub callback_handler: UncheckedAccount<'info>,
+
+    /// To create missing tick, bitmap and position accounts
+    pub system_program: Program<'info, System>,
+}
+
 #[derive(Accounts)]
 pub struct MintCallback<'info> {
     /// Pays to mint liquidity
@@ -565,29 +890,210 @@ pub struct SwapContext<'info> {
 // Non fungible position manager
 
 #[derive(Accounts)]
-pub struct MintTokenizedPosition<'info> {
-    /// Pays to mint the position
+pub struct ApproveOperator<'info> {
+    /// The owner of the position NFTs. Pays to create the approval account
     #[account(mut)]
-    pub minter: Signer<'info>,
+    pub owner: Signer<'info>,
 
-    /// Receives the position NFT
+    /// The address to approve
     /// CHECK: This is not dangerous because we don't read or write from this account
-    pub recipient: UncheckedAccount<'info>,
-
-    /// The program account acting as the core liquidity custodian for token holder, and as
-    /// mint authority of the position NFT
-    pub factory_state: AccountLoader<'info, FactoryState>,
+    #[account(constraint = operator.key() != owner.key())]
+    pub operator: UncheckedAccount<'info>,
 
-    /// Unique token mint address
+    /// Stores the approval
     #[account(
         init,
-        mint::decimals = 0,
-        mint::authority = factory_state,
-        payer = minter
+        seeds = [
+            OPERATOR_APPROVAL_SEED.as_bytes(),
+            owner.key().as_ref(),
+            operator.key().as_ref(),
+        ],
+        bump,
+        payer = owner,
+        space = 8 + size_of::<OperatorApprovalState>()
     )]
-    pub nft_mint: Box<Account<'info, Mint>>,
+    pub operator_approval: AccountLoader<'info, OperatorApprovalState>,
 
-    /// Token account where position NFT will be minted
+    /// Program to create the approval account
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeOperator<'info> {
+    /// The owner of the position NFTs. Receives the reclaimed lamports
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    /// The address to revoke
+    /// CHECK: This is not dangerous because we don't read or write from this account
+    pub operator: UncheckedAccount<'info>,
+
+    /// The approval to delete
+    #[account(
+        mut,
+        seeds = [
+            OPERATOR_APPROVAL_SEED.as_bytes(),
+            owner.key().as_ref(),
+            operator.key().as_ref(),
+        ],
+        bump = operator_approval.load()?.bump,
+        close = owner
+    )]
+    pub operator_approval: AccountLoader<'info, OperatorApprovalState>,
+}
+
+#[derive(Accounts)]
+pub struct LockPosition<'info> {
+    /// The holder of the position NFT, who becomes the lock owner. Pays to create the lock
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    /// Mint address of the position NFT
+    pub nft_mint: Box<Account<'info, Mint>>,
+
+    /// Only genuine position NFTs can be locked
+    #[account(
+        seeds = [POSITION_SEED.as_bytes(), nft_mint.key().as_ref()],
+        bump = tokenized_position_state.load()?.bump
+    )]
+    pub tokenized_position_state: AccountLoader<'info, TokenizedPositionState>,
+
+    /// The token account holding the position NFT
+    #[account(
+        mut,
+        token::mint = nft_mint,
+        token::authority = owner
+    )]
+    pub nft_account: Box<Account<'info, TokenAccount>>,
+
+    /// Stores the lock, and holds the NFT in escrow
+    #[account(
+        init,
+        seeds = [POSITION_LOCK_SEED.as_bytes(), nft_mint.key().as_ref()],
+        bump,
+        payer = owner,
+        space = 8 + size_of::<PositionLockState>()
+    )]
+    pub position_lock_state: AccountLoader<'info, PositionLockState>,
+
+    /// Escrow token account of the lock
+    #[account(
+        init,
+        associated_token::mint = nft_mint,
+        associated_token::authority = position_lock_state,
+        payer = owner
+    )]
+    pub escrow_nft_account: Box<Account<'info, TokenAccount>>,
+
+    /// Approves the lock owner as an operator of the escrow, to collect fees
+    #[account(
+        init,
+        seeds = [
+            OPERATOR_APPROVAL_SEED.as_bytes(),
+            position_lock_state.key().as_ref(),
+            owner.key().as_ref(),
+        ],
+        bump,
+        payer = owner,
+        space = 8 + size_of::<OperatorApprovalState>()
+    )]
+    pub operator_approval: AccountLoader<'info, OperatorApprovalState>,
+
+    /// Sysvar for ATA creation
+    pub rent: Sysvar<'info, Rent>,
+
+    /// Program to transfer the NFT into escrow
+    pub token_program: Program<'info, Token>,
+
+    /// Program to create the escrow token account
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    /// Program to create the lock and approval accounts
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExtendLock<'info> {
+    /// The lock owner
+    #[account(address = position_lock_state.load()?.owner)]
+    pub owner: Signer<'info>,
+
+    /// The lock to extend
+    #[account(mut)]
+    pub position_lock_state: AccountLoader<'info, PositionLockState>,
+}
+
+#[derive(Accounts)]
+pub struct UnlockPosition<'info> {
+    /// The lock owner. Receives the reclaimed lamports
+    #[account(mut, address = position_lock_state.load()?.owner)]
+    pub owner: Signer<'info>,
+
+    /// Mint address of the locked position NFT
+    pub nft_mint: Box<Account<'info, Mint>>,
+
+    /// The lock to close
+    #[account(
+        mut,
+        seeds = [POSITION_LOCK_SEED.as_bytes(), nft_mint.key().as_ref()],
+        bump = position_lock_state.load()?.bump,
+        close = owner
+    )]
+    pub position_lock_state: AccountLoader<'info, PositionLockState>,
+
+    /// Escrow token account of the lock. Closed after the NFT is withdrawn
+    #[account(
+        mut,
+        associated_token::mint = nft_mint,
+        associated_token::authority = position_lock_state
+    )]
+    pub escrow_nft_account: Box<Account<'info, TokenAccount>>,
+
+    /// Receives the position NFT
+    #[account(mut, token::mint = nft_mint)]
+    pub nft_account: Box<Account<'info, TokenAccount>>,
+
+    /// Operator approval of the lock owner on the escrow, deleted on unlock
+    #[account(
+        mut,
+        seeds = [
+            OPERATOR_APPROVAL_SEED.as_bytes(),
+            position_lock_state.key().as_ref(),
+            owner.key().as_ref(),
+        ],
+        bump = operator_approval.load()?.bump,
+        close = owner
+    )]
+    pub operator_approval: AccountLoader<'info, OperatorApprovalState>,
+
+    /// Program to withdraw the NFT and close the escrow token account
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct MintTokenizedPosition<'info> {
+    /// Pays to mint the position
+    #[account(mut)]
+    pub minter: Signer<'info>,
+
+    /// Receives the position NFT
+    /// CHECK: This is not dangerous because we don't read or write from this account
+    pub recipient: UncheckedAccount<'info>,
+
+    /// The program account acting as the core liquidity custodian for token holder, and as
+    /// mint authority of the position NFT
+    pub factory_state: AccountLoader<'info, FactoryState>,
+
+    /// Unique token mint address
+    #[account(
+        init,
+        mint::decimals = 0,
+        mint::authority = factory_state,
+        payer = minter
+    )]
+    pub nft_mint: Box<Account<'info, Mint>>,
+
+    /// Token account where position NFT will be minted
     #[account(
         init,
         associated_token::mint = nft_mint,
@@ -681,7 +1187,8 @@ pub struct AddMetaplexMetadata<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
 
-    /// Authority of the NFT mint
+    /// Authority of the NFT mint, and collection authority of the position NFT collection
+    #[account(mut)]
     pub factory_state: AccountLoader<'info, FactoryState>,
 
     /// Mint address for the tokenized position
@@ -695,17 +1202,30 @@ pub struct AddMetaplexMetadata<'info> {
     )]
     pub tokenized_position_state: AccountLoader<'info, TokenizedPositionState>,
 
+    /// The pool of the tokenized position, to name the NFT
+    #[account(address = tokenized_position_state.load()?.pool_id)]
+    pub pool_state: AccountLoader<'info, PoolState>,
+
     /// To store metaplex metadata
     /// CHECK: Safety check performed inside function body
     #[account(mut)]
     pub metadata_account: UncheckedAccount<'info>,
 
-    /// Sysvar for metadata account creation
-    pub rent: Sysvar<'info, Rent>,
+    /// Mint of the collection NFT the position is verified into
+    #[account(seeds = [COLLECTION_SEED.as_bytes()], bump)]
+    pub collection_mint: Box<Account<'info, Mint>>,
+
+    /// Metaplex metadata of the collection
+    /// CHECK: Address validated by the metadata program
+    pub collection_metadata: UncheckedAccount<'info>,
+
+    /// Master edition of the collection
+    /// CHECK: Address validated by the metadata program
+    pub collection_master_edition: UncheckedAccount<'info>,
 
     /// Program to create NFT metadata
     /// CHECK: Metadata program address constraint applied
-    #[account(address = metaplex_token_metadata::ID)]
+    #[account(address = mpl_token_metadata::ID)]
     pub metadata_program: UncheckedAccount<'info>,
 
     /// Program to update mint authority
@@ -715,6 +1235,49 @@ pub struct AddMetaplexMetadata<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct VerifyPositionCollection<'info> {
+    /// Pays for collection verification
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// Update authority of the position metadata and the collection
+    #[account(mut)]
+    pub factory_state: AccountLoader<'info, FactoryState>,
+
+    /// Mint address for the tokenized position
+    pub nft_mint: Box<Account<'info, Mint>>,
+
+    /// Position state of the tokenized position. Only genuine positions can be verified
+    #[account(
+        seeds = [POSITION_SEED.as_bytes(), nft_mint.key().as_ref()],
+        bump = tokenized_position_state.load()?.bump
+    )]
+    pub tokenized_position_state: AccountLoader<'info, TokenizedPositionState>,
+
+    /// Metaplex metadata of the position NFT
+    /// CHECK: Metadata PDA of the position NFT mint
+    #[account(mut, address = find_metadata_account(&nft_mint.key()).0)]
+    pub metadata_account: UncheckedAccount<'info>,
+
+    /// Mint of the collection NFT
+    #[account(seeds = [COLLECTION_SEED.as_bytes()], bump)]
+    pub collection_mint: Box<Account<'info, Mint>>,
+
+    /// Metaplex metadata of the collection
+    /// CHECK: Address validated by the metadata program
+    pub collection_metadata: UncheckedAccount<'info>,
+
+    /// Master edition of the collection
+    /// CHECK: Address validated by the metadata program
+    pub collection_master_edition: UncheckedAccount<'info>,
+
+    /// Program to verify the collection
+    /// CHECK: Metadata program address constraint applied
+    #[account(address = mpl_token_metadata::ID)]
+    pub metadata_program: UncheckedAccount<'info>,
+}
+
 #[derive(Accounts)]
 pub struct IncreaseLiquidity<'info> {
     /// Pays to mint the position
@@ -727,6 +1290,12 @@ pub struct IncreaseLiquidity<'info> {
     #[account(mut)]
     pub tokenized_position_state: AccountLoader<'info, TokenizedPositionState>,
 
+    /// The token account holding the position NFT, to check that the position is not locked
+    #[account(
+        constraint = nft_account.mint == tokenized_position_state.load()?.mint
+    )]
+    pub nft_account: Box<Account<'info, TokenAccount>>,
+
     /// Mint liquidity for this pool
     /// CHECK: Safety check performed inside function body
     #[account(mut)]
@@ -925,66 +1494,667 @@ pub struct CollectFromTokenized<'info> {
 }
 
 #[derive(Accounts)]
-pub struct ExactInputSingle<'info> {
-    /// The user performing the swap
-    pub signer: Signer<'info>,
+pub struct CollectFromTokenizedBatch<'info> {
+    /// The position owner or delegated authority of every position in the batch
+    pub owner_or_delegate: Signer<'info>,
 
-    /// The factory state to read protocol fees
-    /// CHECK: Safety check performed inside function body
-    pub factory_state: UncheckedAccount<'info>,
+    /// The program account acting as the core liquidity custodian for token holder
+    pub factory_state: AccountLoader<'info, FactoryState>,
 
-    /// The program account of the pool in which the swap will be performed
+    /// The pool of every position in the batch
     /// CHECK: Safety check performed inside function body
     #[account(mut)]
     pub pool_state: UncheckedAccount<'info>,
 
-    /// The user token account for input token
+    /// The latest observation state
+    /// CHECK: Safety check performed inside function body
+    pub last_observation_state: UncheckedAccount<'info>,
+
+    /// The pool's token account for token_0
+    #[account(mut)]
+    pub vault_0: Box<Account<'info, TokenAccount>>,
+
+    /// The pool's token account for token_1
+    #[account(mut)]
+    pub vault_1: Box<Account<'info, TokenAccount>>,
+
+    /// The destination token account for the collected amount_0
     /// CHECK: Account validation is performed by the token program
     #[account(mut)]
-    pub input_token_account: UncheckedAccount<'info>,
+    pub recipient_wallet_0: UncheckedAccount<'info>,
 
-    /// The user token account for output token
+    /// The destination token account for the collected amount_1
     /// CHECK: Account validation is performed by the token program
     #[account(mut)]
-    pub output_token_account: UncheckedAccount<'info>,
+    pub recipient_wallet_1: UncheckedAccount<'info>,
 
-    /// The vault token account for input token
+    /// SPL program to transfer out tokens
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ZapOut<'info> {
+    /// The position owner or delegated authority. Only the owner can burn the NFT, and
+    /// receives the rent of the position account
     #[account(mut)]
-    pub input_vault: Box<Account<'info, TokenAccount>>,
+    pub owner_or_delegate: Signer<'info>,
 
-    /// The vault token account for output token
+    /// The mint of the position NFT, burned if the position is fully exited
+    #[account(
+        mut,
+        address = tokenized_position_state.load()?.mint
+    )]
+    pub nft_mint: Box<Account<'info, Mint>>,
+
+    /// The token account for the tokenized position
+    #[account(
+        mut,
+        constraint = nft_account.mint == tokenized_position_state.load()?.mint
+    )]
+    pub nft_account: Box<Account<'info, TokenAccount>>,
+
+    /// Withdraw liquidity from this position. Closed if the NFT is burned
     #[account(mut)]
-    pub output_vault: Box<Account<'info, TokenAccount>>,
+    pub tokenized_position_state: AccountLoader<'info, TokenizedPositionState>,
 
-    /// The program account for the most recent oracle observation
+    /// The program account acting as the core liquidity custodian for token holder
+    pub factory_state: AccountLoader<'info, FactoryState>,
+
+    /// Burn liquidity and swap in this pool
     /// CHECK: Safety check performed inside function body
     #[account(mut)]
-    pub last_observation_state: UncheckedAccount<'info>,
+    pub pool_state: UncheckedAccount<'info>,
 
-    /// The core program where swap is performed
-    pub core_program: Program<'info, CyclosCore>,
+    /// Core program account to store position data
+    /// CHECK: Safety check performed inside function body
+    #[account(mut)]
+    pub core_position_state: UncheckedAccount<'info>,
 
-    /// SPL program for token transfers
-    pub token_program: Program<'info, Token>,
-}
+    /// Account to store data for the position's lower tick
+    /// CHECK: Safety check performed inside function body
+    #[account(mut)]
+    pub tick_lower_state: UncheckedAccount<'info>,
 
-#[derive(Accounts)]
-pub struct ExactInput<'info> {
-    /// The user performing the swap
-    pub signer: Signer<'info>,
+    /// Account to store data for the position's upper tick
+    /// CHECK: Safety check performed inside function body
+    #[account(mut)]
+    pub tick_upper_state: UncheckedAccount<'info>,
 
-    /// The factory state to read protocol fees
+    /// Stores init state for the lower tick
     /// CHECK: Safety check performed inside function body
-    pub factory_state: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub bitmap_lower_state: UncheckedAccount<'info>,
 
-    /// The token account that pays input tokens for the swap
-    /// CHECK: Account validation is performed by the token program
+    /// Stores init state for the upper tick
+    /// CHECK: Safety check performed inside function body
     #[account(mut)]
-    pub input_token_account: UncheckedAccount<'info>,
+    pub bitmap_upper_state: UncheckedAccount<'info>,
 
-    /// The core program where swap is performed
-    pub core_program: Program<'info, CyclosCore>,
+    /// The latest observation state
+    /// CHECK: Safety check performed inside function body
+    #[account(mut)]
+    pub last_observation_state: UncheckedAccount<'info>,
 
-    /// SPL program for token transfers
-    pub token_program: Program<'info, Token>,
+    /// The pool's token account for token_0
+    #[account(mut)]
+    pub vault_0: Box<Account<'info, TokenAccount>>,
+
+    /// The pool's token account for token_1
+    #[account(mut)]
+    pub vault_1: Box<Account<'info, TokenAccount>>,
+
+    /// The user token account for token_0
+    /// CHECK: Account validation is performed by the token program
+    #[account(mut)]
+    pub token_account_0: UncheckedAccount<'info>,
+
+    /// The user token account for token_1
+    /// CHECK: Account validation is performed by the token program
+    #[account(mut)]
+    pub token_account_1: UncheckedAccount<'info>,
+
+    /// The core program where liquidity is burned and swapped
+    pub core_program: Program<'info, CyclosCore>,
+
+    /// SPL program to transfer out tokens and burn the position NFT
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct SplitTokenizedPosition<'info> {
+    /// The position owner or delegated authority. Pays to create the new position
+    #[account(mut)]
+    pub owner_or_delegate: Signer<'info>,
+
+    /// The token account for the tokenized position
+    #[account(
+        constraint = nft_account.mint == tokenized_position_state.load()?.mint
+    )]
+    pub nft_account: Box<Account<'info, TokenAccount>>,
+
+    /// The position to split
+    #[account(mut)]
+    pub tokenized_position_state: AccountLoader<'info, TokenizedPositionState>,
+
+    /// The program account acting as the core liquidity custodian for token holder, and as
+    /// mint authority of the position NFT
+    pub factory_state: AccountLoader<'info, FactoryState>,
+
+    /// The pool of the position
+    #[account(address = tokenized_position_state.load()?.pool_id)]
+    pub pool_state: AccountLoader<'info, PoolState>,
+
+    /// The core position holding the liquidity of the tokenized positions
+    #[account(
+        seeds = [
+            POSITION_SEED.as_bytes(),
+            pool_state.load()?.token_0.as_ref(),
+            pool_state.load()?.token_1.as_ref(),
+            &pool_state.load()?.fee.to_be_bytes(),
+            factory_state.key().as_ref(),
+            &tokenized_position_state.load()?.tick_lower.to_be_bytes(),
+            &tokenized_position_state.load()?.tick_upper.to_be_bytes(),
+        ],
+        bump = core_position_state.load()?.bump,
+    )]
+    pub core_position_state: AccountLoader<'info, PositionState>,
+
+    /// Receives the NFT of the new position. Must be the owner of the split NFT
+    /// CHECK: Address is checked against the owner of the split NFT
+    #[account(address = nft_account.owner)]
+    pub recipient: UncheckedAccount<'info>,
+
+    /// Unique token mint address of the new position
+    #[account(
+        init,
+        mint::decimals = 0,
+        mint::authority = factory_state,
+        payer = owner_or_delegate
+    )]
+    pub new_nft_mint: Box<Account<'info, Mint>>,
+
+    /// Token account where the new position NFT will be minted
+    #[account(
+        init,
+        associated_token::mint = new_nft_mint,
+        associated_token::authority = recipient,
+        payer = owner_or_delegate
+    )]
+    pub new_nft_account: Box<Account<'info, TokenAccount>>,
+
+    /// Metadata for the new tokenized position
+    #[account(
+        init,
+        seeds = [POSITION_SEED.as_bytes(), new_nft_mint.key().as_ref()],
+        bump,
+        payer = owner_or_delegate,
+        space = 8 + size_of::<TokenizedPositionState>()
+    )]
+    pub new_tokenized_position_state: AccountLoader<'info, TokenizedPositionState>,
+
+    /// Program to create the position accounts
+    pub system_program: Program<'info, System>,
+
+    /// Sysvar for token mint and ATA creation
+    pub rent: Sysvar<'info, Rent>,
+
+    /// Program to create mint account and mint tokens
+    pub token_program: Program<'info, Token>,
+
+    /// Program to create an ATA for receiving position NFT
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+pub struct MergeTokenizedPositions<'info> {
+    /// The owner or delegated authority of both positions
+    pub owner_or_delegate: Signer<'info>,
+
+    /// The owner of both position NFTs. Receives the rent of the merged position
+    /// CHECK: Address is checked against the owner of the merged NFT
+    #[account(mut, address = source_nft_account.owner)]
+    pub owner: UncheckedAccount<'info>,
+
+    /// The token account for the position receiving the liquidity
+    #[account(
+        constraint = nft_account.mint == tokenized_position_state.load()?.mint
+    )]
+    pub nft_account: Box<Account<'info, TokenAccount>>,
+
+    /// The position receiving the liquidity
+    #[account(mut)]
+    pub tokenized_position_state: AccountLoader<'info, TokenizedPositionState>,
+
+    /// The mint of the merged position NFT, burned
+    #[account(
+        mut,
+        address = source_tokenized_position_state.load()?.mint
+    )]
+    pub source_nft_mint: Box<Account<'info, Mint>>,
+
+    /// The token account for the merged position, held by the same owner
+    #[account(
+        mut,
+        constraint = source_nft_account.mint == source_tokenized_position_state.load()?.mint,
+        constraint = source_nft_account.owner == nft_account.owner
+    )]
+    pub source_nft_account: Box<Account<'info, TokenAccount>>,
+
+    /// The merged position, closed after its liquidity is moved
+    #[account(
+        mut,
+        close = owner,
+        constraint = source_tokenized_position_state.key() != tokenized_position_state.key()
+    )]
+    pub source_tokenized_position_state: AccountLoader<'info, TokenizedPositionState>,
+
+    /// The program account acting as the core liquidity custodian for token holder
+    pub factory_state: AccountLoader<'info, FactoryState>,
+
+    /// The pool of the position
+    #[account(address = tokenized_position_state.load()?.pool_id)]
+    pub pool_state: AccountLoader<'info, PoolState>,
+
+    /// The core position holding the liquidity of the tokenized positions
+    #[account(
+        seeds = [
+            POSITION_SEED.as_bytes(),
+            pool_state.load()?.token_0.as_ref(),
+            pool_state.load()?.token_1.as_ref(),
+            &pool_state.load()?.fee.to_be_bytes(),
+            factory_state.key().as_ref(),
+            &tokenized_position_state.load()?.tick_lower.to_be_bytes(),
+            &tokenized_position_state.load()?.tick_upper.to_be_bytes(),
+        ],
+        bump = core_position_state.load()?.bump,
+    )]
+    pub core_position_state: AccountLoader<'info, PositionState>,
+
+    /// Program to burn the merged position NFT
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ValueTokenizedPosition<'info> {
+    /// The position to value
+    pub tokenized_position_state: AccountLoader<'info, TokenizedPositionState>,
+
+    /// The pool of the position
+    #[account(address = tokenized_position_state.load()?.pool_id)]
+    pub pool_state: AccountLoader<'info, PoolState>,
+
+    /// The lower tick of the position
+    #[account(
+        seeds = [
+            TICK_SEED.as_bytes(),
+            pool_state.load()?.token_0.as_ref(),
+            pool_state.load()?.token_1.as_ref(),
+            &pool_state.load()?.fee.to_be_bytes(),
+            &tokenized_position_state.load()?.tick_lower.to_be_bytes(),
+        ],
+        bump = tick_lower_state.load()?.bump,
+    )]
+    pub tick_lower_state: AccountLoader<'info, TickState>,
+
+    /// The upper tick of the position
+    #[account(
+        seeds = [
+            TICK_SEED.as_bytes(),
+            pool_state.load()?.token_0.as_ref(),
+            pool_state.load()?.token_1.as_ref(),
+            &pool_state.load()?.fee.to_be_bytes(),
+            &tokenized_position_state.load()?.tick_upper.to_be_bytes(),
+        ],
+        bump = tick_upper_state.load()?.bump,
+    )]
+    pub tick_upper_state: AccountLoader<'info, TickState>,
+}
+
+#[derive(Accounts)]
+pub struct ExactInputSingle<'info> {
+    /// The user performing the swap
+    pub signer: Signer<'info>,
+
+    /// The factory state to read protocol fees
+    /// CHECK: Safety check performed inside function body
+    pub factory_state: UncheckedAccount<'info>,
+
+    /// The program account of the pool in which the swap will be performed
+    /// CHECK: Safety check performed inside function body
+    #[account(mut)]
+    pub pool_state: UncheckedAccount<'info>,
+
+    /// The user token account for input token
+    /// CHECK: Account validation is performed by the token program
+    #[account(mut)]
+    pub input_token_account: UncheckedAccount<'info>,
+
+    /// The user token account for output token
+    /// CHECK: Account validation is performed by the token program
+    #[account(mut)]
+    pub output_token_account: UncheckedAccount<'info>,
+
+    /// The vault token account for input token
+    #[account(mut)]
+    pub input_vault: Box<Account<'info, TokenAccount>>,
+
+    /// The vault token account for output token
+    #[account(mut)]
+    pub output_vault: Box<Account<'info, TokenAccount>>,
+
+    /// The program account for the most recent oracle observation
+    /// CHECK: Safety check performed inside function body
+    #[account(mut)]
+    pub last_observation_state: UncheckedAccount<'info>,
+
+    /// The core program where swap is performed
+    pub core_program: Program<'info, CyclosCore>,
+
+    /// SPL program for token transfers
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ExactInput<'info> {
+    /// The user performing the swap
+    pub signer: Signer<'info>,
+
+    /// The factory state to read protocol fees
+    /// CHECK: Safety check performed inside function body
+    pub factory_state: UncheckedAccount<'info>,
+
+    /// The token account that pays input tokens for the swap
+    /// CHECK: Account validation is performed by the token program
+    #[account(mut)]
+    pub input_token_account: UncheckedAccount<'info>,
+
+    /// The core program where swap is performed
+    pub core_program: Program<'info, CyclosCore>,
+
+    /// SPL program for token transfers
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ExactInputSplit<'info> {
+    /// The user performing the swap
+    pub signer: Signer<'info>,
+
+    /// The factory state to read protocol fees
+    /// CHECK: Safety check performed inside function body
+    pub factory_state: UncheckedAccount<'info>,
+
+    /// The user token account for input token
+    /// CHECK: Account validation is performed by the token program
+    #[account(mut)]
+    pub input_token_account: UncheckedAccount<'info>,
+
+    /// The user token account for output token, shared by all legs
+    /// CHECK: Account validation is performed by the token program
+    #[account(mut)]
+    pub output_token_account: UncheckedAccount<'info>,
+
+    /// The core program where swap is performed
+    pub core_program: Program<'info, CyclosCore>,
+
+    /// SPL program for token transfers
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CreateGaugeController<'info> {
+    /// Valid protocol owner, pays to create the gauge controller
+    #[account(mut, address = factory_state.load()?.owner)]
+    pub owner: Signer<'info>,
+
+    /// Factory state stores the protocol owner address
+    pub factory_state: AccountLoader<'info, FactoryState>,
+
+    /// The locker whose escrows vote
+    pub locker: Box<Account<'info, Locker>>,
+
+    /// Initialize the gauge controller
+    #[account(
+        init,
+        seeds = [GAUGE_CONTROLLER_SEED.as_bytes()],
+        bump,
+        payer = owner,
+        space = 8 + size_of::<GaugeControllerState>()
+    )]
+    pub gauge_controller_state: AccountLoader<'info, GaugeControllerState>,
+
+    /// To create a new program account
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetGaugeEmissions<'info> {
+    /// Valid protocol owner
+    #[account(address = factory_state.load()?.owner)]
+    pub owner: Signer<'info>,
+
+    /// Factory state stores the protocol owner address
+    pub factory_state: AccountLoader<'info, FactoryState>,
+
+    /// The gauge controller storing emissions per epoch
+    #[account(
+        mut,
+        seeds = [GAUGE_CONTROLLER_SEED.as_bytes()],
+        bump = gauge_controller_state.load()?.bump
+    )]
+    pub gauge_controller_state: AccountLoader<'info, GaugeControllerState>,
+}
+
+#[derive(Accounts)]
+pub struct CreateGauge<'info> {
+    /// Valid protocol owner, pays to create the gauge
+    #[account(mut, address = factory_state.load()?.owner)]
+    pub owner: Signer<'info>,
+
+    /// Factory state stores the protocol owner address
+    pub factory_state: AccountLoader<'info, FactoryState>,
+
+    /// The pool receiving incentives
+    pub pool_state: AccountLoader<'info, PoolState>,
+
+    /// Initialize the gauge of the pool
+    #[account(
+        init,
+        seeds = [GAUGE_SEED.as_bytes(), pool_state.key().as_ref()],
+        bump,
+        payer = owner,
+        space = 8 + size_of::<GaugeState>()
+    )]
+    pub gauge_state: AccountLoader<'info, GaugeState>,
+
+    /// To create a new program account
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(epoch: u32)]
+pub struct InitEpochAccount<'info> {
+    /// Pays to create the epoch account
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// The epoch account to be initialized
+    #[account(
+        init,
+        seeds = [EPOCH_SEED.as_bytes(), &epoch.to_be_bytes()],
+        bump,
+        payer = payer,
+        space = 8 + size_of::<EpochState>()
+    )]
+    pub epoch_state: AccountLoader<'info, EpochState>,
+
+    /// To create a new program account
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(epoch: u32)]
+pub struct InitEpochGaugeAccount<'info> {
+    /// Pays to create the epoch gauge account
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// The gauge voted for
+    pub gauge_state: AccountLoader<'info, GaugeState>,
+
+    /// The epoch gauge account to be initialized
+    #[account(
+        init,
+        seeds = [
+            EPOCH_GAUGE_SEED.as_bytes(),
+            gauge_state.key().as_ref(),
+            &epoch.to_be_bytes()
+        ],
+        bump,
+        payer = payer,
+        space = 8 + size_of::<EpochGaugeState>()
+    )]
+    pub epoch_gauge_state: AccountLoader<'info, EpochGaugeState>,
+
+    /// To create a new program account
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitGaugeVoterAccount<'info> {
+    /// Pays to create the gauge voter account
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// The gauge controller storing the locker address
+    #[account(
+        seeds = [GAUGE_CONTROLLER_SEED.as_bytes()],
+        bump = gauge_controller_state.load()?.bump
+    )]
+    pub gauge_controller_state: AccountLoader<'info, GaugeControllerState>,
+
+    /// Escrow of the locker
+    #[account(constraint = escrow.locker == gauge_controller_state.load()?.locker)]
+    pub escrow: Box<Account<'info, Escrow>>,
+
+    /// The gauge voter account to be initialized
+    #[account(
+        init,
+        seeds = [GAUGE_VOTER_SEED.as_bytes(), escrow.key().as_ref()],
+        bump,
+        payer = payer,
+        space = 8 + size_of::<GaugeVoterState>()
+    )]
+    pub gauge_voter_state: AccountLoader<'info, GaugeVoterState>,
+
+    /// To create a new program account
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(epoch: u32)]
+pub struct VoteGauge<'info> {
+    /// The escrow owner or its vote delegate. Pays to create the vote account
+    #[account(
+        mut,
+        constraint = voter.key() == escrow.owner || voter.key() == escrow.vote_delegate
+    )]
+    pub voter: Signer<'info>,
+
+    /// The gauge controller storing the locker address and epochs
+    #[account(
+        seeds = [GAUGE_CONTROLLER_SEED.as_bytes()],
+        bump = gauge_controller_state.load()?.bump
+    )]
+    pub gauge_controller_state: AccountLoader<'info, GaugeControllerState>,
+
+    /// The locker storing voting power parameters
+    #[account(address = gauge_controller_state.load()?.locker)]
+    pub locker: Box<Account<'info, Locker>>,
+
+    /// Escrow of the locker casting the vote
+    #[account(constraint = escrow.locker == locker.key())]
+    pub escrow: Box<Account<'info, Escrow>>,
+
+    /// Vote weight allocated by the escrow
+    #[account(
+        mut,
+        seeds = [GAUGE_VOTER_SEED.as_bytes(), escrow.key().as_ref()],
+        bump = gauge_voter_state.load()?.bump
+    )]
+    pub gauge_voter_state: AccountLoader<'info, GaugeVoterState>,
+
+    /// The gauge voted for
+    pub gauge_state: AccountLoader<'info, GaugeState>,
+
+    /// Total votes of the epoch
+    #[account(
+        mut,
+        seeds = [EPOCH_SEED.as_bytes(), &epoch.to_be_bytes()],
+        bump = epoch_state.load()?.bump
+    )]
+    pub epoch_state: AccountLoader<'info, EpochState>,
+
+    /// Votes of the gauge in the epoch
+    #[account(
+        mut,
+        seeds = [
+            EPOCH_GAUGE_SEED.as_bytes(),
+            gauge_state.key().as_ref(),
+            &epoch.to_be_bytes()
+        ],
+        bump = epoch_gauge_state.load()?.bump
+    )]
+    pub epoch_gauge_state: AccountLoader<'info, EpochGaugeState>,
+
+    /// The vote of the escrow for the gauge in the epoch, created on the first vote
+    #[account(
+        init_if_needed,
+        seeds = [
+            GAUGE_VOTE_SEED.as_bytes(),
+            escrow.key().as_ref(),
+            gauge_state.key().as_ref(),
+            &epoch.to_be_bytes()
+        ],
+        bump,
+        payer = voter,
+        space = 8 + size_of::<GaugeVoteState>()
+    )]
+    pub gauge_vote_state: AccountLoader<'info, GaugeVoteState>,
+
+    /// To create the vote account
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(epoch: u32)]
+pub struct SyncGauge<'info> {
+    /// The gauge controller storing emissions per epoch
+    #[account(
+        seeds = [GAUGE_CONTROLLER_SEED.as_bytes()],
+        bump = gauge_controller_state.load()?.bump
+    )]
+    pub gauge_controller_state: AccountLoader<'info, GaugeControllerState>,
+
+    /// The gauge whose emissions are set
+    #[account(mut)]
+    pub gauge_state: AccountLoader<'info, GaugeState>,
+
+    /// Total votes of the epoch
+    #[account(
+        seeds = [EPOCH_SEED.as_bytes(), &epoch.to_be_bytes()],
+        bump = epoch_state.load()?.bump
+    )]
+    pub epoch_state: AccountLoader<'info, EpochState>,
+
+    /// Votes of the gauge in the epoch
+    #[account(
+        seeds = [
+            EPOCH_GAUGE_SEED.as_bytes(),
+            gauge_state.key().as_ref(),
+            &epoch.to_be_bytes()
+        ],
+        bump = epoch_gauge_state.load()?.bump
+    )]
+    pub epoch_gauge_state: AccountLoader<'info, EpochGaugeState>,
 }
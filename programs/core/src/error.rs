@@ -50,12 +50,24 @@ pub enum ErrorCode {
     #[msg("IIA")]
     IIA,
 
+    #[msg("Missing or invalid bitmap, tick or observation account for the swap")]
+    InvalidSwapAccount,
+
+    #[msg("Tick, bitmap or position account does not match its seeds")]
+    InvalidAccountSeeds,
+
+    #[msg("Missing the next observation account")]
+    MissingObservationAccount,
+
     // states/position.rs
 
     // No poke/burn for a position with 0 liquidity
     #[msg("NP")]
     NP,
 
+    #[msg("Recipient position must have no liquidity")]
+    PositionHasLiquidity,
+
     // states/tick.rs
 
     // liquidity_gross_after must be less than max_liquidity
@@ -81,6 +93,38 @@ pub enum ErrorCode {
     #[msg("LA")]
     LA,
 
+    // Factory
+    #[msg("Fee amount is disabled")]
+    FeeDisabled,
+
+    // Pool creation
+    #[msg("Token mints must be sorted, with token_0 < token_1")]
+    TokensNotSorted,
+
+    #[msg("Pair registry has no space for more pools")]
+    PairRegistryFull,
+
+    #[msg("Pool is already registered for its pair")]
+    PoolAlreadyRegistered,
+
+    #[msg("Reference pool must have the same token pair")]
+    InvalidReferencePool,
+
+    #[msg("Missing reference pool or observation accounts for the TWAP")]
+    MissingTwapAccounts,
+
+    #[msg("Observation must be initialized and older than the TWAP window")]
+    InvalidTwapObservation,
+
+    #[msg("TWAP window must be positive")]
+    InvalidTwapWindow,
+
+    #[msg("Pool has liquidity or initialized ticks")]
+    PoolNotEmpty,
+
+    #[msg("Pool was migrated without a count of its initialized ticks")]
+    TickCountNotTracked,
+
     // Non fungible position manager
     #[msg("Transaction too old")]
     TransactionTooOld,
@@ -91,7 +135,70 @@ pub enum ErrorCode {
     #[msg("Not approved")]
     NotApproved,
 
+    #[msg("Position has liquidity or uncollected tokens")]
+    PositionNotCleared,
+
+    #[msg("Basis points must be between 1 and 10000, and not round down to zero liquidity")]
+    InvalidBps,
+
+    #[msg("Split liquidity must be positive and less than the position liquidity")]
+    InvalidLiquiditySplit,
+
+    #[msg("Positions must have the same pool and tick range")]
+    PositionMismatch,
+
+    #[msg("Remaining accounts must hold one or more complete position tuples")]
+    InvalidAccountCount,
+
+    #[msg("Vault is not the token account of the pool")]
+    InvalidVault,
+
+    #[msg("Metadata URI or collection name is too long or empty")]
+    InvalidMetadataLength,
+
+    #[msg("Position is locked until its unlock time")]
+    PositionLocked,
+
+    #[msg("Unlock time must be in the future and later than the current unlock time")]
+    InvalidUnlockTime,
+
     // Swap router
     #[msg("Too little received")]
     TooLittleReceived,
+
+    #[msg("Split leg shares must add up to 10000 basis points")]
+    InvalidSplit,
+
+    #[msg("Each split leg must have a positive share and swap a positive amount")]
+    EmptySplitLeg,
+
+    #[msg("Route must have at least one hop")]
+    EmptyRoute,
+
+    #[msg("A hop after the first must swap its whole input")]
+    PartialIntermediateHop,
+
+    // Gauge voting
+    #[msg("Epoch is not open for this action")]
+    InvalidEpoch,
+
+    #[msg("Vote weights of an epoch must add up to at most 10000 basis points")]
+    InvalidVoteWeight,
+
+    #[msg("Escrow has no voting power")]
+    NoVotingPower,
+
+    #[msg("Escrow must stay locked until the end of the voted epoch")]
+    EscrowEndsBeforeEpoch,
+
+    // Hooks
+    #[msg("Invalid hook flags")]
+    InvalidHookFlags,
+
+    #[msg("Hook program does not match the pool")]
+    InvalidHookProgram,
+
+    // Account migration
+    #[msg("Account already has the current layout")]
+    AccountAlreadyMigrated,
 }
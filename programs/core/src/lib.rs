@@ -7,19 +7,24 @@ use crate::access_control::*;
 use crate::error::ErrorCode;
 use crate::libraries::liquidity_amounts;
 use crate::libraries::tick_math;
+use crate::libraries::zap_math;
 use crate::states::oracle;
 use crate::states::oracle::ObservationState;
 use crate::states::tokenized_position::{
-    CollectTokenizedEvent, DecreaseLiquidityEvent, IncreaseLiquidityEvent,
+    position_name, CollectTokenizedEvent, DecreaseLiquidityEvent, IncreaseLiquidityEvent,
+    LiquidityChange, MergeTokenizedPositionsEvent, PositionValue, SplitTokenizedPositionEvent,
+    TokenizedPositionState,
 };
 use crate::{
     libraries::{fixed_point_32, swap_math},
-    states::{oracle::OBSERVATION_SEED, tick_bitmap},
+    states::{hook, oracle::OBSERVATION_SEED, tick_bitmap},
 };
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program;
-use anchor_lang::solana_program::system_instruction::create_account;
-use anchor_lang::{solana_program::instruction::Instruction, InstructionData};
+use anchor_lang::solana_program::system_instruction::{allocate, assign, create_account, transfer};
+use anchor_lang::{
+    solana_program::instruction::Instruction, AccountsClose, InstructionData, ZeroCopy,
+};
 use anchor_spl::associated_token::get_associated_token_address;
 use anchor_spl::token;
 use anchor_spl::token::TokenAccount;
@@ -27,12 +32,23 @@ use context::*;
 use libraries::full_math::MulDiv;
 use libraries::liquidity_math;
 use libraries::sqrt_price_math;
-use metaplex_token_metadata::{instruction::create_metadata_accounts, state::Creator};
+use mpl_token_metadata::{
+    instruction::{
+        create_master_edition_v3, create_metadata_accounts_v3, set_and_verify_collection,
+        verify_collection,
+    },
+    state::{Collection, Creator},
+};
 use spl_token::instruction::AuthorityType;
 use states::factory::*;
 use states::fee::*;
+use states::gauge::*;
+use states::operator_approval::*;
+use states::pair::*;
 use states::pool::*;
 use states::position::*;
+use states::position_lock::*;
+use states::swap_router::*;
 use states::tick;
 use states::tick::*;
 use states::tick_bitmap::*;
@@ -65,6 +81,7 @@ pub mod cyclos_core {
         factory_state.bump = *ctx.bumps.get("factory_state").unwrap();
         factory_state.owner = ctx.accounts.owner.key();
         factory_state.fee_protocol = 3; // 1/3 = 33.33%
+        factory_state.set_metadata_config(DEFAULT_BASE_URI, DEFAULT_COLLECTION_NAME)?;
 
         emit!(OwnerChanged {
             old_owner: Pubkey::default(),
@@ -93,8 +110,123 @@ pub mod cyclos_core {
         Ok(())
     }
 
+    /// Sets the metadata URI and collection name of position NFTs
+    /// Must be called by the current owner. Applies to metadata created afterwards
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx`- Checks whether protocol owner has signed
+    /// * `base_uri` - Base URI of position NFT metadata. The NFT mint address is appended to it
+    /// * `collection_name` - Name of the position NFT collection
+    ///
+    pub fn set_metadata_config(
+        ctx: Context<SetMetadataConfig>,
+        base_uri: String,
+        collection_name: String,
+    ) -> Result<()> {
+        let mut factory_state = ctx.accounts.factory_state.load_mut()?;
+        factory_state.set_metadata_config(&base_uri, &collection_name)?;
+
+        emit!(MetadataConfigChanged {
+            base_uri,
+            collection_name,
+        });
+
+        Ok(())
+    }
+
+    /// Creates the collection NFT that position NFTs are verified into. Must be called
+    /// by the current owner, once, before metadata is attached to position NFTs
+    ///
+    /// The collection mint is a PDA with the factory as its update authority. Its master
+    /// edition caps the supply at one.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx`- Checks whether protocol owner has signed and initializes the collection mint
+    ///
+    pub fn create_position_collection(ctx: Context<CreatePositionCollection>) -> Result<()> {
+        let factory = *ctx.accounts.factory_state.load()?;
+        let seeds = [&[factory.bump] as &[u8]];
+
+        token::mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::MintTo {
+                    mint: ctx.accounts.collection_mint.to_account_info(),
+                    to: ctx.accounts.collection_token_account.to_account_info(),
+                    authority: ctx.accounts.factory_state.to_account_info(),
+                },
+                &[&seeds[..]],
+            ),
+            1,
+        )?;
+
+        let create_metadata_ix = create_metadata_accounts_v3(
+            ctx.accounts.metadata_program.key(),
+            ctx.accounts.collection_metadata.key(),
+            ctx.accounts.collection_mint.key(),
+            ctx.accounts.factory_state.key(),
+            ctx.accounts.owner.key(),
+            ctx.accounts.factory_state.key(),
+            factory.collection_name(),
+            String::from("CYS-POS"),
+            factory.token_uri(&ctx.accounts.collection_mint.key()),
+            Some(vec![Creator {
+                address: ctx.accounts.factory_state.key(),
+                verified: true,
+                share: 100,
+            }]),
+            0,
+            true,
+            true,
+            None,
+            None,
+            None,
+        );
+        solana_program::program::invoke_signed(
+            &create_metadata_ix,
+            &[
+                ctx.accounts.collection_metadata.to_account_info(),
+                ctx.accounts.collection_mint.to_account_info(),
+                ctx.accounts.owner.to_account_info(),
+                ctx.accounts.factory_state.to_account_info(), // mint and update authority
+                ctx.accounts.system_program.to_account_info(),
+            ],
+            &[&seeds[..]],
+        )?;
+
+        // Also revokes the mint authority of the factory
+        let create_master_edition_ix = create_master_edition_v3(
+            ctx.accounts.metadata_program.key(),
+            ctx.accounts.collection_master_edition.key(),
+            ctx.accounts.collection_mint.key(),
+            ctx.accounts.factory_state.key(),
+            ctx.accounts.factory_state.key(),
+            ctx.accounts.collection_metadata.key(),
+            ctx.accounts.owner.key(),
+            Some(0),
+        );
+        solana_program::program::invoke_signed(
+            &create_master_edition_ix,
+            &[
+                ctx.accounts.collection_master_edition.to_account_info(),
+                ctx.accounts.collection_mint.to_account_info(),
+                ctx.accounts.factory_state.to_account_info(), // mint and update authority
+                ctx.accounts.owner.to_account_info(),
+                ctx.accounts.collection_metadata.to_account_info(),
+                ctx.accounts.token_program.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+                ctx.accounts.rent.to_account_info(),
+            ],
+            &[&seeds[..]],
+        )?;
+
+        Ok(())
+    }
+
     /// Enables a fee amount with the given tick_spacing
-    /// Fee amounts may never be removed once enabled
+    /// Fee amounts may never be removed once enabled, but they can be disabled
     ///
     /// # Arguments
     ///
@@ -120,11 +252,28 @@ pub mod cyclos_core {
         fee_state.bump = *ctx.bumps.get("fee_state").unwrap();
         fee_state.fee = fee;
         fee_state.tick_spacing = tick_spacing;
+        fee_state.enabled = true;
 
         emit!(FeeAmountEnabled { fee, tick_spacing });
         Ok(())
     }
 
+    /// Disables a fee amount, so that no new pools can be created with it
+    /// Pools already created with the fee amount are unaffected
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx`- Checks whether protocol owner has signed
+    ///
+    pub fn disable_fee_amount(ctx: Context<DisableFeeAmount>) -> Result<()> {
+        let mut fee_state = ctx.accounts.fee_state.load_mut()?;
+        require!(fee_state.enabled, ErrorCode::FeeDisabled);
+        fee_state.enabled = false;
+
+        emit!(FeeAmountDisabled { fee: fee_state.fee });
+        Ok(())
+    }
+
     // ---------------------------------------------------------------------
     // Pool instructions
 
@@ -136,15 +285,36 @@ pub mod cyclos_core {
     /// # Arguments
     ///
     /// * `ctx`- Validates token addresses and fee state. Initializes pool, observation and
-    /// token accounts
+    /// token accounts. If `sqrt_price_x32` is 0, the remaining accounts hold a reference pool
+    /// for the same token pair, an older observation and the latest observation of the reference pool
     /// * `pool_state_bump` - Bump to validate Pool State address
     /// * `observation_state_bump` - Bump to validate Observation State address
-    /// * `sqrt_price_x32` - the initial sqrt price (amount_token_1 / amount_token_0) of the pool as a Q32.32
+    /// * `sqrt_price_x32` - the initial sqrt price (amount_token_1 / amount_token_0) of the pool as a Q32.32,
+    /// or 0 to use the time weighted average price of the reference pool since the older observation.
+    /// The older observation must be at least `MIN_TWAP_WINDOW` seconds old
+    /// * `hook_program` - Program invoked by the pool's hooks. Must be the default address if
+    /// `hook_flags` is zero
+    /// * `hook_flags` - Bitmask of hooks to enable, see `states::hook`
     ///
     pub fn create_and_init_pool(
         ctx: Context<CreateAndInitPool>,
         sqrt_price_x32: u64,
+        hook_program: Pubkey,
+        hook_flags: u8,
     ) -> Result<()> {
+        hook::validate_hook_config(hook_program, hook_flags)?;
+
+        let sqrt_price_x32 = if sqrt_price_x32 == 0 {
+            twap_sqrt_price_x32(
+                ctx.accounts.token_0.key(),
+                ctx.accounts.token_1.key(),
+                ctx.remaining_accounts,
+                oracle::MIN_TWAP_WINDOW,
+            )?
+        } else {
+            sqrt_price_x32
+        };
+
         let mut pool_state = ctx.accounts.pool_state.load_init()?;
         let fee_state = ctx.accounts.fee_state.load()?;
         let tick = tick_math::get_tick_at_sqrt_ratio(sqrt_price_x32)?;
@@ -159,6 +329,9 @@ pub mod cyclos_core {
         pool_state.unlocked = true;
         pool_state.observation_cardinality = 1;
         pool_state.observation_cardinality_next = 1;
+        pool_state.hook_program = hook_program;
+        pool_state.hook_flags = hook_flags;
+        pool_state.tick_count_tracked = true;
 
         let mut initial_observation_state = ctx.accounts.initial_observation_state.load_init()?;
         initial_observation_state.bump = *ctx.bumps.get("initial_observation_state").unwrap();
@@ -167,6 +340,16 @@ pub mod cyclos_core {
 
         // default value 0 for remaining variables
 
+        register_pool(
+            &ctx.accounts.pool_creator.to_account_info(),
+            &ctx.accounts.pair_state.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            *ctx.bumps.get("pair_state").unwrap(),
+            ctx.accounts.token_0.key(),
+            ctx.accounts.token_1.key(),
+            fee_state.fee,
+        )?;
+
         emit!(PoolCreatedAndInitialized {
             token_0: ctx.accounts.token_0.key(),
             token_1: ctx.accounts.token_1.key(),
@@ -175,10 +358,161 @@ pub mod cyclos_core {
             pool_state: ctx.accounts.pool_state.key(),
             sqrt_price_x32,
             tick,
+            hook_program,
+            hook_flags,
+        });
+        Ok(())
+    }
+
+    /// Registers a pool created before pair registries in the registry of its token pair.
+    /// Pools with an older layout must be migrated first. Does nothing if the registry is full.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx`- Holds the pool and the pair registry, which is created if it doesn't exist
+    ///
+    pub fn register_pool_pair(ctx: Context<RegisterPoolPair>) -> Result<()> {
+        let pool = *ctx.accounts.pool_state.load()?;
+        require!(pool.token_0 < pool.token_1, ErrorCode::TokensNotSorted);
+
+        register_pool(
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.pair_state.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            *ctx.bumps.get("pair_state").unwrap(),
+            pool.token_0,
+            pool.token_1,
+            pool.fee,
+        )
+    }
+
+    /// Sets a new price for a pool without liquidity, so that a badly priced pool can be
+    /// fixed instead of abandoned
+    ///
+    /// The pool must have no in range liquidity and no initialized ticks. Pools migrated from
+    /// a layout without a tick count can not be reinitialized. The latest observation is
+    /// written at the old price before the price is changed.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx`- Holds the pool and its latest observation. If `sqrt_price_x32` is 0, the
+    /// remaining accounts hold the reference pool and its observations, as in `create_and_init_pool`
+    /// * `sqrt_price_x32` - The new sqrt price of the pool as a Q32.32, or 0 to use the
+    /// time weighted average price of the reference pool
+    ///
+    pub fn reinitialize_price(ctx: Context<ReinitializePrice>, sqrt_price_x32: u64) -> Result<()> {
+        let mut pool = ctx.accounts.pool_state.load_mut()?;
+        require!(pool.unlocked, ErrorCode::LOK);
+        require!(pool.tick_count_tracked, ErrorCode::TickCountNotTracked);
+        require!(
+            pool.liquidity == 0 && pool.initialized_tick_count == 0,
+            ErrorCode::PoolNotEmpty
+        );
+
+        let sqrt_price_x32 = if sqrt_price_x32 == 0 {
+            twap_sqrt_price_x32(
+                pool.token_0,
+                pool.token_1,
+                ctx.remaining_accounts,
+                oracle::MIN_TWAP_WINDOW,
+            )?
+        } else {
+            sqrt_price_x32
+        };
+        let tick = tick_math::get_tick_at_sqrt_ratio(sqrt_price_x32)?;
+
+        let last_observation_state = AccountLoader::<ObservationState>::try_from(
+            &ctx.accounts.last_observation_state.to_account_info(),
+        )?;
+        let mut latest_observation = last_observation_state.load_mut()?;
+        pool.validate_observation_address(
+            &last_observation_state.key(),
+            latest_observation.bump,
+            false,
+        )?;
+        // accumulate the old tick up to the current time
+        let time = oracle::_block_timestamp();
+        if latest_observation.block_timestamp != time {
+            *latest_observation = latest_observation.transform(time, pool.tick, pool.liquidity);
+        }
+
+        let sqrt_price_x32_old = pool.sqrt_price_x32;
+        pool.sqrt_price_x32 = sqrt_price_x32;
+        pool.tick = tick;
+
+        emit!(PriceReinitialized {
+            pool_state: ctx.accounts.pool_state.key(),
+            sqrt_price_x32_old,
+            sqrt_price_x32,
+            tick,
         });
         Ok(())
     }
 
+    // ---------------------------------------------------------------------
+    // Account migration
+    // Accounts created before fields were appended to their layout fail to load until
+    // they are grown to the current layout
+
+    /// Grows a fee tier created before fee tiers could be disabled to the current layout.
+    /// Existing fee tiers stay enabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx`- Holds the payer and the fee tier to migrate
+    ///
+    pub fn migrate_fee_state(ctx: Context<MigrateFeeState>) -> Result<()> {
+        let fee_state_info = ctx.accounts.fee_state.to_account_info();
+        realloc_program_account::<FeeState>(
+            &ctx.accounts.payer.to_account_info(),
+            &fee_state_info,
+            &ctx.accounts.system_program.to_account_info(),
+        )?;
+
+        let fee_state_loader = AccountLoader::<FeeState>::try_from(&fee_state_info)?;
+        fee_state_loader.load_mut()?.enabled = true;
+        fee_state_loader.exit(ctx.program_id)?;
+        Ok(())
+    }
+
+    /// Grows a pool created before hooks and tick counting to the current layout.
+    /// Appended fields are zeroed, so the pool has no hooks.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx`- Holds the payer and the pool to migrate
+    ///
+    pub fn migrate_pool_state(ctx: Context<MigratePoolState>) -> Result<()> {
+        realloc_program_account::<PoolState>(
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.pool_state.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+        )
+    }
+
+    /// Grows a factory created before configurable NFT metadata to the current layout.
+    /// The metadata URI and collection name are set to their defaults.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx`- Holds the payer and the factory to migrate
+    ///
+    pub fn migrate_factory_state(ctx: Context<MigrateFactoryState>) -> Result<()> {
+        let factory_state_info = ctx.accounts.factory_state.to_account_info();
+        realloc_program_account::<FactoryState>(
+            &ctx.accounts.payer.to_account_info(),
+            &factory_state_info,
+            &ctx.accounts.system_program.to_account_info(),
+        )?;
+
+        let factory_state_loader = AccountLoader::<FactoryState>::try_from(&factory_state_info)?;
+        factory_state_loader
+            .load_mut()?
+            .set_metadata_config(DEFAULT_BASE_URI, DEFAULT_COLLECTION_NAME)?;
+        factory_state_loader.exit(ctx.program_id)?;
+        Ok(())
+    }
+
     // ---------------------------------------------------------------------
     // Oracle
 
@@ -550,36 +884,101 @@ pub mod cyclos_core {
         Ok(())
     }
 
-    /// Adds liquidity for the given pool/recipient/tickLower/tickUpper position
+    /// Adds liquidity for the given pool/recipient/tickLower/tickUpper position. Tick, bitmap
+    /// and position accounts that don't exist yet are created, paid for by the minter.
     ///
     /// # Arguments
     ///
     /// * `ctx` - Holds the recipient's address and program accounts for
     /// pool, position and ticks.
+    /// * `tick_lower` - The lower tick of the position
+    /// * `tick_upper` - The upper tick of the position
     /// * `amount` - The amount of liquidity to mint
     ///
     pub fn mint<'a, 'b, 'c, 'info>(
-        ctx: Context<'a, 'b, 'c, 'info, MintContext<'info>>,
+        ctx: Context<'a, 'b, 'c, 'info, MintWithInit<'info>>,
+        tick_lower: i32,
+        tick_upper: i32,
         amount: u64,
-    ) -> Result<()> {
-        let mut pool = ctx.accounts.pool_state.load_mut()?;
+    ) -> Result<TokenAmounts> {
+        init_mint_accounts(
+            &ctx.accounts.minter.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.pool_state.to_account_info(),
+            [
+                (&ctx.accounts.tick_lower_state.to_account_info(), tick_lower),
+                (&ctx.accounts.tick_upper_state.to_account_info(), tick_upper),
+            ],
+            [
+                &ctx.accounts.bitmap_lower_state.to_account_info(),
+                &ctx.accounts.bitmap_upper_state.to_account_info(),
+            ],
+            &ctx.accounts.position_state.to_account_info(),
+            &ctx.accounts.recipient.key(),
+        )?;
 
-        assert!(
-            ctx.accounts.vault_0.key()
-                == get_associated_token_address(&ctx.accounts.pool_state.key(), &pool.token_0)
-        );
-        assert!(
-            ctx.accounts.vault_1.key()
-                == get_associated_token_address(&ctx.accounts.pool_state.key(), &pool.token_1)
+        let tick_lower_state =
+            AccountLoader::<TickState>::try_from(&ctx.accounts.tick_lower_state.to_account_info())?;
+        let tick_upper_state =
+            AccountLoader::<TickState>::try_from(&ctx.accounts.tick_upper_state.to_account_info())?;
+        require!(
+            tick_lower_state.load()?.tick == tick_lower
+                && tick_upper_state.load()?.tick == tick_upper,
+            ErrorCode::InvalidAccountSeeds
         );
-        let tick_lower = *ctx.accounts.tick_lower_state.load()?.deref();
+
+        let mut accs = MintContext {
+            minter: ctx.accounts.minter.clone(),
+            token_account_0: ctx.accounts.token_account_0.clone(),
+            token_account_1: ctx.accounts.token_account_1.clone(),
+            vault_0: ctx.accounts.vault_0.clone(),
+            vault_1: ctx.accounts.vault_1.clone(),
+            recipient: ctx.accounts.recipient.clone(),
+            pool_state: ctx.accounts.pool_state.clone(),
+            tick_lower_state,
+            tick_upper_state,
+            bitmap_lower_state: ctx.accounts.bitmap_lower_state.clone(),
+            bitmap_upper_state: ctx.accounts.bitmap_upper_state.clone(),
+            position_state: ctx.accounts.position_state.clone(),
+            last_observation_state: ctx.accounts.last_observation_state.clone(),
+            token_program: ctx.accounts.token_program.clone(),
+            callback_handler: ctx.accounts.callback_handler.clone(),
+        };
+        mint_liquidity(
+            Context::new(&ID, &mut accs, ctx.remaining_accounts, BTreeMap::default()),
+            amount,
+        )
+    }
+
+    /// Burn liquidity from the sender and account tokens owed for the liquidity to the position.
+    /// Can be used to trigger a recalculation of fees owed to a position by calling with an amount of 0 (poke).
+    /// Fees must be collected separately via a call to #collect
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - Holds position and other validated accounts need to burn liquidity
+    /// * `amount` - Amount of liquidity to be burned
+    ///
+    pub fn burn<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, BurnContext<'info>>,
+        amount: u64,
+    ) -> Result<TokenAmounts> {
+        let pool_state =
+            AccountLoader::<PoolState>::try_from(&ctx.accounts.pool_state.to_account_info())?;
+        let mut pool = pool_state.load_mut()?;
+
+        let tick_lower_state =
+            AccountLoader::<TickState>::try_from(&ctx.accounts.tick_lower_state.to_account_info())?;
+        let tick_lower = *tick_lower_state.load()?.deref();
         pool.validate_tick_address(
             &ctx.accounts.tick_lower_state.key(),
             tick_lower.bump,
             tick_lower.tick,
         )?;
 
-        let tick_upper = *ctx.accounts.tick_upper_state.load()?.deref();
+        let tick_upper_state =
+            AccountLoader::<TickState>::try_from(&ctx.accounts.tick_upper_state.to_account_info())?;
+        let tick_upper = *tick_upper_state.load()?.deref();
         pool.validate_tick_address(
             &ctx.accounts.tick_upper_state.key(),
             tick_upper.bump,
@@ -609,7 +1008,7 @@ pub mod cyclos_core {
         pool.validate_position_address(
             &ctx.accounts.position_state.key(),
             position_state.load()?.bump,
-            &ctx.accounts.recipient.key(),
+            &ctx.accounts.owner.key(),
             tick_lower.tick,
             tick_upper.tick,
         )?;
@@ -618,195 +1017,98 @@ pub mod cyclos_core {
             &ctx.accounts.last_observation_state.to_account_info(),
         )?;
         pool.validate_observation_address(
-            &last_observation_state.key(),
+            &ctx.accounts.last_observation_state.key(),
             last_observation_state.load()?.bump,
             false,
         )?;
 
+        msg!("accounts validated");
+
         require!(pool.unlocked, ErrorCode::LOK);
         pool.unlocked = false;
 
-        assert!(amount > 0);
+        // pokes do not invoke hooks
+        let (hook_program, remaining_accounts) = if amount > 0
+            && pool.has_hook(hook::BEFORE_MODIFY_POSITION_FLAG | hook::AFTER_MODIFY_POSITION_FLAG)
+        {
+            let (hook_program, remaining_accounts) =
+                hook::split_hook_program(pool.hook_program, ctx.remaining_accounts)?;
+            (Some(hook_program), remaining_accounts)
+        } else {
+            (None, ctx.remaining_accounts)
+        };
+        let hook_accounts = [
+            ctx.accounts.owner.to_account_info(),
+            ctx.accounts.owner.to_account_info(),
+            ctx.accounts.pool_state.to_account_info(),
+        ];
+
+        if let Some(hook_program) = hook_program {
+            if pool.has_hook(hook::BEFORE_MODIFY_POSITION_FLAG) {
+                drop(pool);
+                hook::invoke_hook(
+                    hook_program,
+                    &hook::BeforeModifyPositionHook {
+                        tick_lower: tick_lower.tick,
+                        tick_upper: tick_upper.tick,
+                        liquidity_delta: -i64::try_from(amount).unwrap(),
+                    },
+                    &hook_accounts,
+                )?;
+                pool = pool_state.load_mut()?;
+            }
+        }
 
         let (amount_0_int, amount_1_int) = _modify_position(
-            i64::try_from(amount).unwrap(),
+            -i64::try_from(amount).unwrap(),
             pool.deref_mut(),
-            &position_state,
-            &ctx.accounts.tick_lower_state,
-            &ctx.accounts.tick_upper_state,
+            &ctx.accounts.position_state,
+            &tick_lower_state,
+            &tick_upper_state,
             &bitmap_lower_state,
             &bitmap_upper_state,
             &last_observation_state,
-            ctx.remaining_accounts,
+            remaining_accounts,
         )?;
 
-        let amount_0 = amount_0_int as u64;
-        let amount_1 = amount_1_int as u64;
-
-        let balance_0_before = if amount_0 > 0 {
-            ctx.accounts.vault_0.amount
-        } else {
-            0
-        };
-        let balance_1_before = if amount_1 > 0 {
-            ctx.accounts.vault_1.amount
-        } else {
-            0
-        };
-
-        drop(pool);
-
-        let mint_callback_ix = cyclos_core::instruction::MintCallback {
-            amount_0_owed: amount_0,
-            amount_1_owed: amount_1,
-        };
-        let ix = Instruction::new_with_bytes(
-            ctx.accounts.callback_handler.key(),
-            &mint_callback_ix.data(),
-            ctx.accounts.to_account_metas(None),
-        );
-        solana_program::program::invoke(&ix, &ctx.accounts.to_account_infos())?;
-
-        ctx.accounts.vault_0.reload()?;
-        ctx.accounts.vault_1.reload()?;
-
-        if amount_0 > 0 {
-            require!(
-                balance_0_before + amount_0 <= ctx.accounts.vault_0.amount,
-                ErrorCode::M0
-            );
-        }
-        if amount_1 > 0 {
-            require!(
-                balance_1_before + amount_1 <= ctx.accounts.vault_1.amount,
-                ErrorCode::M1
-            );
+        let amount_0 = (-amount_0_int) as u64;
+        let amount_1 = (-amount_1_int) as u64;
+        if amount_0 > 0 || amount_1 > 0 {
+            let mut position_state = ctx.accounts.position_state.load_mut()?;
+            position_state.tokens_owed_0 += amount_0;
+            position_state.tokens_owed_1 += amount_1;
         }
 
-        emit!(MintEvent {
+        emit!(BurnEvent {
             pool_state: ctx.accounts.pool_state.key(),
-            sender: ctx.accounts.minter.key(),
-            owner: ctx.accounts.recipient.key(),
+            owner: ctx.accounts.owner.key(),
             tick_lower: tick_lower.tick,
-            tick_upper: tick_upper.tick,
+            tick_upper: tick_lower.tick,
             amount,
             amount_0,
-            amount_1
+            amount_1,
         });
 
-        ctx.accounts.pool_state.load_mut()?.unlocked = true;
-        Ok(())
-    }
-
-    /// Burn liquidity from the sender and account tokens owed for the liquidity to the position.
-    /// Can be used to trigger a recalculation of fees owed to a position by calling with an amount of 0 (poke).
-    /// Fees must be collected separately via a call to #collect
-    ///
-    /// # Arguments
-    ///
-    /// * `ctx` - Holds position and other validated accounts need to burn liquidity
-    /// * `amount` - Amount of liquidity to be burned
-    ///
-    pub fn burn<'a, 'b, 'c, 'info>(
-        ctx: Context<'a, 'b, 'c, 'info, BurnContext<'info>>,
-        amount: u64,
-    ) -> Result<()> {
-        let pool_state =
-            AccountLoader::<PoolState>::try_from(&ctx.accounts.pool_state.to_account_info())?;
-        let mut pool = pool_state.load_mut()?;
-
-        let tick_lower_state =
-            AccountLoader::<TickState>::try_from(&ctx.accounts.tick_lower_state.to_account_info())?;
-        let tick_lower = *tick_lower_state.load()?.deref();
-        pool.validate_tick_address(
-            &ctx.accounts.tick_lower_state.key(),
-            tick_lower.bump,
-            tick_lower.tick,
-        )?;
-
-        let tick_upper_state =
-            AccountLoader::<TickState>::try_from(&ctx.accounts.tick_upper_state.to_account_info())?;
-        let tick_upper = *tick_upper_state.load()?.deref();
-        pool.validate_tick_address(
-            &ctx.accounts.tick_upper_state.key(),
-            tick_upper.bump,
-            tick_upper.tick,
-        )?;
-
-        let bitmap_lower_state = AccountLoader::<TickBitmapState>::try_from(
-            &ctx.accounts.bitmap_lower_state.to_account_info(),
-        )?;
-        pool.validate_bitmap_address(
-            &ctx.accounts.bitmap_lower_state.key(),
-            bitmap_lower_state.load()?.bump,
-            tick_bitmap::position(tick_lower.tick / pool.tick_spacing as i32).word_pos,
-        )?;
-        let bitmap_upper_state = AccountLoader::<TickBitmapState>::try_from(
-            &ctx.accounts.bitmap_upper_state.to_account_info(),
-        )?;
-        pool.validate_bitmap_address(
-            &ctx.accounts.bitmap_upper_state.key(),
-            bitmap_upper_state.load()?.bump,
-            tick_bitmap::position(tick_upper.tick / pool.tick_spacing as i32).word_pos,
-        )?;
-
-        let position_state = AccountLoader::<PositionState>::try_from(
-            &ctx.accounts.position_state.to_account_info(),
-        )?;
-        pool.validate_position_address(
-            &ctx.accounts.position_state.key(),
-            position_state.load()?.bump,
-            &ctx.accounts.owner.key(),
-            tick_lower.tick,
-            tick_upper.tick,
-        )?;
-
-        let last_observation_state = AccountLoader::<ObservationState>::try_from(
-            &ctx.accounts.last_observation_state.to_account_info(),
-        )?;
-        pool.validate_observation_address(
-            &ctx.accounts.last_observation_state.key(),
-            last_observation_state.load()?.bump,
-            false,
-        )?;
-
-        msg!("accounts validated");
-
-        require!(pool.unlocked, ErrorCode::LOK);
-        pool.unlocked = false;
-
-        let (amount_0_int, amount_1_int) = _modify_position(
-            -i64::try_from(amount).unwrap(),
-            pool.deref_mut(),
-            &ctx.accounts.position_state,
-            &tick_lower_state,
-            &tick_upper_state,
-            &bitmap_lower_state,
-            &bitmap_upper_state,
-            &last_observation_state,
-            ctx.remaining_accounts,
-        )?;
-
-        let amount_0 = (-amount_0_int) as u64;
-        let amount_1 = (-amount_1_int) as u64;
-        if amount_0 > 0 || amount_1 > 0 {
-            let mut position_state = ctx.accounts.position_state.load_mut()?;
-            position_state.tokens_owed_0 += amount_0;
-            position_state.tokens_owed_1 += amount_1;
+        if let Some(hook_program) = hook_program {
+            if pool.has_hook(hook::AFTER_MODIFY_POSITION_FLAG) {
+                drop(pool);
+                hook::invoke_hook(
+                    hook_program,
+                    &hook::AfterModifyPositionHook {
+                        tick_lower: tick_lower.tick,
+                        tick_upper: tick_upper.tick,
+                        liquidity_delta: -i64::try_from(amount).unwrap(),
+                        amount_0: amount_0_int,
+                        amount_1: amount_1_int,
+                    },
+                    &hook_accounts,
+                )?;
+                pool = pool_state.load_mut()?;
+            }
         }
 
-        emit!(BurnEvent {
-            pool_state: ctx.accounts.pool_state.key(),
-            owner: ctx.accounts.owner.key(),
-            tick_lower: tick_lower.tick,
-            tick_upper: tick_lower.tick,
-            amount,
-            amount_0,
-            amount_1,
-        });
-
         pool.unlocked = true;
-        Ok(())
+        Ok(TokenAmounts { amount_0, amount_1 })
     }
 
     /// Collects tokens owed to a position.
@@ -825,7 +1127,7 @@ pub mod cyclos_core {
         ctx: Context<CollectContext>,
         amount_0_requested: u64,
         amount_1_requested: u64,
-    ) -> Result<()> {
+    ) -> Result<TokenAmounts> {
         let pool_state =
             AccountLoader::<PoolState>::try_from(&ctx.accounts.pool_state.to_account_info())?;
         let mut pool = pool_state.load_mut()?;
@@ -917,6 +1219,48 @@ pub mod cyclos_core {
         });
 
         pool_state.load_mut()?.unlocked = true;
+        Ok(TokenAmounts { amount_0, amount_1 })
+    }
+
+    /// Moves the liquidity and tokens owed of a position to another owner, for the same
+    /// tick boundaries. The position account of the new owner is created if needed and must
+    /// have no liquidity.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - Holds the pool, tick and position accounts of both owners
+    ///
+    pub fn transfer_position(ctx: Context<TransferPosition>) -> Result<()> {
+        let tick_lower = ctx.accounts.tick_lower_state.load()?.tick;
+        let tick_upper = ctx.accounts.tick_upper_state.load()?.tick;
+
+        // An account created by init_if_needed has no discriminator until the instruction exits
+        let is_new = ctx
+            .accounts
+            .new_position_state
+            .to_account_info()
+            .try_borrow_data()?[..8]
+            == [0; 8];
+        let mut new_position = if is_new {
+            let mut new_position = ctx.accounts.new_position_state.load_init()?;
+            new_position.bump = *ctx.bumps.get("new_position_state").unwrap();
+            new_position
+        } else {
+            ctx.accounts.new_position_state.load_mut()?
+        };
+        let mut position = ctx.accounts.position_state.load_mut()?;
+        let liquidity = position.liquidity;
+        position.transfer_to(&mut new_position)?;
+
+        emit!(TransferPositionEvent {
+            pool_state: ctx.accounts.pool_state.key(),
+            owner: ctx.accounts.owner.key(),
+            new_owner: ctx.accounts.new_owner.key(),
+            tick_lower,
+            tick_upper,
+            liquidity,
+        });
+
         Ok(())
     }
 
@@ -953,8 +1297,12 @@ pub mod cyclos_core {
         pub fee_growth_global_x32: u64,
         // amount of input token paid as protocol fee
         pub protocol_fee: u64,
+        // amount of input token paid as fee to liquidity providers
+        pub lp_fee: u64,
         // the current liquidity in range
         pub liquidity: u64,
+        // the number of initialized ticks crossed
+        pub ticks_crossed: u32,
     }
 
     #[derive(Default)]
@@ -983,19 +1331,24 @@ pub mod cyclos_core {
     ///
     /// * `ctx` - Accounts required for the swap. Remaining accounts should contain each bitmap leading to
     /// the end tick, and each tick being flipped
-    /// account leading to the destination tick
+    /// account leading to the destination tick. Initialized bitmaps are validated with their stored bump.
+    /// An uninitialized bitmap has no bump to read, so its address is checked with `find_program_address`,
+    /// which costs more compute units. Initialize the bitmap with #init_bitmap_account to avoid this
     /// * `deadline` - The time by which the transaction must be included to effect the change
     /// * `amount_specified` - The amount of the swap, which implicitly configures the swap as exact input (positive),
     /// or exact output (negative)
     /// * `sqrt_price_limit` - The Q32.32 sqrt price √P limit. If zero for one, the price cannot
     /// be less than this value after the swap.  If one for zero, the price cannot be greater than
     /// this value after the swap.
+    /// * `partial_fill` - If true, the swap stops at the last bitmap or tick boundary that can be
+    /// crossed with the supplied accounts instead of failing. The unfilled amount is returned.
     ///
-    pub fn swap(
-        ctx: Context<SwapContext>,
+    pub fn swap<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, SwapContext<'info>>,
         amount_specified: i64,
         sqrt_price_limit_x32: u64,
-    ) -> Result<()> {
+        partial_fill: bool,
+    ) -> Result<SwapResult> {
         require!(amount_specified != 0, ErrorCode::AS);
 
         let factory_state =
@@ -1052,6 +1405,41 @@ pub mod cyclos_core {
         );
 
         pool.unlocked = false;
+
+        let mut remaining_accounts = ctx.remaining_accounts.iter().peekable();
+        let hook_program = if pool.has_hook(hook::BEFORE_SWAP_FLAG | hook::AFTER_SWAP_FLAG) {
+            let hook_program = remaining_accounts
+                .next()
+                .ok_or(ErrorCode::InvalidHookProgram)?;
+            require!(
+                hook_program.key() == pool.hook_program,
+                ErrorCode::InvalidHookProgram
+            );
+            Some(hook_program)
+        } else {
+            None
+        };
+        let hook_accounts = [
+            ctx.accounts.signer.to_account_info(),
+            ctx.accounts.pool_state.to_account_info(),
+        ];
+
+        if let Some(hook_program) = hook_program {
+            if pool.has_hook(hook::BEFORE_SWAP_FLAG) {
+                drop(pool);
+                hook::invoke_hook(
+                    hook_program,
+                    &hook::BeforeSwapHook {
+                        zero_for_one,
+                        amount_specified,
+                        sqrt_price_limit_x32,
+                    },
+                    &hook_accounts,
+                )?;
+                pool = pool_loader.load_mut()?;
+            }
+        }
+
         let mut cache = SwapCache {
             liquidity_start: pool.liquidity,
             block_timestamp: oracle::_block_timestamp(),
@@ -1062,6 +1450,7 @@ pub mod cyclos_core {
         };
 
         let exact_input = amount_specified > 0;
+        let sqrt_price_start_x32 = pool.sqrt_price_x32;
 
         let mut state = SwapState {
             amount_specified_remaining: amount_specified,
@@ -1074,11 +1463,12 @@ pub mod cyclos_core {
                 pool.fee_growth_global_1_x32
             },
             protocol_fee: 0,
+            lp_fee: 0,
             liquidity: cache.liquidity_start,
+            ticks_crossed: 0,
         };
 
         let latest_observation = last_observation_state.load_mut()?;
-        let mut remaining_accounts = ctx.remaining_accounts.iter();
 
         // cache for the current bitmap account. Cache is cleared on bitmap transitions
         let mut bitmap_cache: Option<TickBitmapState> = None;
@@ -1106,36 +1496,53 @@ pub mod cyclos_core {
             // load the next bitmap account if cache is empty (first loop instance), or if we have
             // crossed out of this bitmap
             if bitmap_cache.is_none() || bitmap_cache.unwrap().word_pos != word_pos {
-                let bitmap_account = remaining_accounts.next().unwrap();
-                msg!("check bitmap {}", word_pos);
-                // ensure this is a valid PDA, even if account is not initialized
-                assert!(
-                    bitmap_account.key()
-                        == Pubkey::find_program_address(
-                            &[
-                                BITMAP_SEED.as_bytes(),
-                                pool.token_0.as_ref(),
-                                pool.token_1.as_ref(),
-                                &pool.fee.to_be_bytes(),
-                                &word_pos.to_be_bytes(),
-                            ],
-                            &cyclos_core::id()
-                        )
-                        .0
-                );
+                let bitmap_account = remaining_accounts.next_if(|account| {
+                    match AccountLoader::<TickBitmapState>::try_from(account) {
+                        // validate with the stored bump. This is cheaper than finding the PDA
+                        Ok(bitmap_loader) => match bitmap_loader.load() {
+                            Ok(bitmap_state) => pool.is_bitmap_address(
+                                &bitmap_loader.key(),
+                                bitmap_state.bump,
+                                word_pos,
+                            ),
+                            Err(_) => false,
+                        },
+                        // an uninitialized account has no stored bump, so the PDA must be found.
+                        // Skip the search for initialized accounts, like ticks or observations
+                        Err(_) => {
+                            account.owner == &System::id()
+                                && account.data_is_empty()
+                                && account.key()
+                                    == Pubkey::find_program_address(
+                                        &[
+                                            BITMAP_SEED.as_bytes(),
+                                            pool.token_0.as_ref(),
+                                            pool.token_1.as_ref(),
+                                            &pool.fee.to_be_bytes(),
+                                            &word_pos.to_be_bytes(),
+                                        ],
+                                        &cyclos_core::id(),
+                                    )
+                                    .0
+                        }
+                    }
+                });
+                // stop at the bitmap boundary if the next bitmap was not supplied
+                let bitmap_account = match bitmap_account {
+                    Some(bitmap_account) => bitmap_account,
+                    None => {
+                        require!(partial_fill, ErrorCode::InvalidSwapAccount);
+                        break;
+                    }
+                };
 
                 // read from bitmap if account is initialized, else use default values for next initialized bit
-                if let Ok(bitmap_loader) =
-                    AccountLoader::<TickBitmapState>::try_from(bitmap_account)
-                {
-                    let bitmap_state = bitmap_loader.load()?;
-                    bitmap_cache = Some(*bitmap_state.deref());
-                } else {
+                bitmap_cache = match AccountLoader::<TickBitmapState>::try_from(bitmap_account) {
+                    Ok(bitmap_loader) => Some(*bitmap_loader.load()?),
                     // clear cache if the bitmap account was uninitialized. This way default uninitialized
                     // values will be returned for the next bit
-                    msg!("cache cleared");
-                    bitmap_cache = None;
-                }
+                    Err(_) => None,
+                };
             }
 
             // what if bitmap_cache is not updated since next account is not initialized?
@@ -1203,6 +1610,8 @@ pub mod cyclos_core {
                 state.protocol_fee += delta;
             }
 
+            state.lp_fee += step.fee_amount;
+
             // update global fee tracker
             if state.liquidity > 0 {
                 state.fee_growth_global_x32 += step
@@ -1228,15 +1637,34 @@ pub mod cyclos_core {
                         cache.computed_latest_observation = true;
                     }
 
-                    msg!("loading tick {}", step.tick_next);
-                    let tick_loader =
-                        AccountLoader::<TickState>::try_from(remaining_accounts.next().unwrap())?;
+                    let tick_account = remaining_accounts.next_if(|account| {
+                        match AccountLoader::<TickState>::try_from(account) {
+                            Ok(tick_loader) => match tick_loader.load() {
+                                Ok(tick_state) => pool.is_tick_address(
+                                    &tick_loader.key(),
+                                    tick_state.bump,
+                                    step.tick_next,
+                                ),
+                                Err(_) => false,
+                            },
+                            Err(_) => false,
+                        }
+                    });
+                    // stop on the tick boundary without crossing if the tick was not supplied
+                    let tick_account = match tick_account {
+                        Some(tick_account) => tick_account,
+                        None => {
+                            require!(partial_fill, ErrorCode::InvalidSwapAccount);
+                            state.tick = if zero_for_one {
+                                step.tick_next
+                            } else {
+                                step.tick_next - 1
+                            };
+                            break;
+                        }
+                    };
+                    let tick_loader = AccountLoader::<TickState>::try_from(tick_account)?;
                     let mut tick_state = tick_loader.load_mut()?;
-                    pool.validate_tick_address(
-                        &tick_loader.key(),
-                        tick_state.bump,
-                        step.tick_next,
-                    )?;
                     let mut liquidity_net = tick_state.deref_mut().cross(
                         if zero_for_one {
                             state.fee_growth_global_x32
@@ -1260,6 +1688,7 @@ pub mod cyclos_core {
                     }
 
                     state.liquidity = liquidity_math::add_delta(state.liquidity, liquidity_net)?;
+                    state.ticks_crossed += 1;
                 }
 
                 state.tick = if zero_for_one {
@@ -1283,7 +1712,9 @@ pub mod cyclos_core {
             let next_observation_state;
             let mut next_observation = if partition_current_timestamp > partition_last_timestamp {
                 next_observation_state = AccountLoader::<ObservationState>::try_from(
-                    &remaining_accounts.next().unwrap(),
+                    remaining_accounts
+                        .next()
+                        .ok_or(ErrorCode::InvalidSwapAccount)?,
                 )?;
                 let next_observation = next_observation_state.load_mut()?;
 
@@ -1427,13 +1858,41 @@ pub mod cyclos_core {
             token_account_1: token_account_1.key(),
             amount_0,
             amount_1,
+            sqrt_price_start_x32,
             sqrt_price_x32: state.sqrt_price_x32,
             liquidity: state.liquidity,
-            tick: state.tick
+            tick: state.tick,
+            amount_specified_remaining: state.amount_specified_remaining,
+            lp_fee_0: if zero_for_one { state.lp_fee } else { 0 },
+            lp_fee_1: if zero_for_one { 0 } else { state.lp_fee },
+            protocol_fee_0: if zero_for_one { state.protocol_fee } else { 0 },
+            protocol_fee_1: if zero_for_one { 0 } else { state.protocol_fee },
+            ticks_crossed: state.ticks_crossed,
         });
+
+        if let Some(hook_program) = hook_program {
+            if pool_loader.load()?.has_hook(hook::AFTER_SWAP_FLAG) {
+                hook::invoke_hook(
+                    hook_program,
+                    &hook::AfterSwapHook {
+                        amount_0,
+                        amount_1,
+                        sqrt_price_x32: state.sqrt_price_x32,
+                        tick: state.tick,
+                    },
+                    &hook_accounts,
+                )?;
+            }
+        }
         pool_loader.load_mut()?.unlocked = true;
 
-        Ok(())
+        Ok(SwapResult {
+            amount_0,
+            amount_1,
+            amount_specified_remaining: state.amount_specified_remaining,
+            sqrt_price_x32: state.sqrt_price_x32,
+            tick: state.tick,
+        })
     }
 
     // /// Component function for flash swaps
@@ -1460,35 +1919,207 @@ pub mod cyclos_core {
 
     // Non fungible position manager
 
-    /// Creates a new position wrapped in a NFT
+    /// Approves an operator to decrease liquidity, collect tokens, split and rebalance all
+    /// position NFTs of the owner. Increasing liquidity needs no approval. The operator passes
+    /// the approval as the first remaining account, so owners and delegates don't need it.
+    ///
+    /// Operators can't burn position NFTs, since the token program only lets the owner or a
+    /// delegate burn. They can't merge positions or burn the NFT in `zap_out`.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - Holds the owner, operator and approval accounts
+    ///
+    pub fn approve_operator(ctx: Context<ApproveOperator>) -> Result<()> {
+        let mut approval = ctx.accounts.operator_approval.load_init()?;
+        approval.bump = *ctx.bumps.get("operator_approval").unwrap();
+        approval.owner = ctx.accounts.owner.key();
+        approval.operator = ctx.accounts.operator.key();
+
+        emit!(ApprovalForAllEvent {
+            owner: ctx.accounts.owner.key(),
+            operator: ctx.accounts.operator.key(),
+            approved: true,
+        });
+        Ok(())
+    }
+
+    /// Revokes an operator approval and returns the rent to the owner
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - Holds the owner, operator and approval accounts
+    ///
+    pub fn revoke_operator(ctx: Context<RevokeOperator>) -> Result<()> {
+        emit!(ApprovalForAllEvent {
+            owner: ctx.accounts.owner.key(),
+            operator: ctx.accounts.operator.key(),
+            approved: false,
+        });
+        Ok(())
+    }
+
+    /// Locks a position NFT in escrow until an unlock time. The owner can still collect
+    /// fees of the position with `collect_from_tokenized`, but liquidity cannot be increased
+    /// or decreased until the NFT is withdrawn.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - Holds the position NFT, the lock and its escrow token account
+    /// * `unlock_time` - Unix timestamp from which the NFT can be withdrawn
+    ///
+    pub fn lock_position(ctx: Context<LockPosition>, unlock_time: i64) -> Result<()> {
+        require!(
+            unlock_time > Clock::get()?.unix_timestamp,
+            ErrorCode::InvalidUnlockTime
+        );
+
+        let mut position_lock = ctx.accounts.position_lock_state.load_init()?;
+        position_lock.bump = *ctx.bumps.get("position_lock_state").unwrap();
+        position_lock.mint = ctx.accounts.nft_mint.key();
+        position_lock.owner = ctx.accounts.owner.key();
+        position_lock.unlock_time = unlock_time;
+
+        let mut approval = ctx.accounts.operator_approval.load_init()?;
+        approval.bump = *ctx.bumps.get("operator_approval").unwrap();
+        approval.owner = ctx.accounts.position_lock_state.key();
+        approval.operator = ctx.accounts.owner.key();
+
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                token::Transfer {
+                    from: ctx.accounts.nft_account.to_account_info(),
+                    to: ctx.accounts.escrow_nft_account.to_account_info(),
+                    authority: ctx.accounts.owner.to_account_info(),
+                },
+            ),
+            1,
+        )?;
+
+        emit!(PositionLockedEvent {
+            token_id: ctx.accounts.nft_mint.key(),
+            owner: ctx.accounts.owner.key(),
+            unlock_time,
+        });
+
+        Ok(())
+    }
+
+    /// Pushes back the unlock time of a locked position NFT
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - Holds the lock owner and the lock
+    /// * `unlock_time` - The new unlock time, later than the current one
+    ///
+    pub fn extend_lock(ctx: Context<ExtendLock>, unlock_time: i64) -> Result<()> {
+        let mut position_lock = ctx.accounts.position_lock_state.load_mut()?;
+        position_lock.extend(unlock_time)?;
+
+        emit!(LockExtendedEvent {
+            token_id: position_lock.mint,
+            unlock_time,
+        });
+
+        Ok(())
+    }
+
+    /// Withdraws a position NFT from its lock once the unlock time has passed. The lock,
+    /// escrow and operator approval accounts are closed.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - Holds the lock owner, the lock and the token account receiving the NFT
+    ///
+    pub fn unlock_position(ctx: Context<UnlockPosition>) -> Result<()> {
+        let position_lock = *ctx.accounts.position_lock_state.load()?;
+        require!(
+            position_lock.is_unlocked(Clock::get()?.unix_timestamp),
+            ErrorCode::PositionLocked
+        );
+
+        let seeds = [
+            POSITION_LOCK_SEED.as_bytes(),
+            position_lock.mint.as_ref(),
+            &[position_lock.bump],
+        ];
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::Transfer {
+                    from: ctx.accounts.escrow_nft_account.to_account_info(),
+                    to: ctx.accounts.nft_account.to_account_info(),
+                    authority: ctx.accounts.position_lock_state.to_account_info(),
+                },
+                &[&seeds[..]],
+            ),
+            1,
+        )?;
+        token::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            token::CloseAccount {
+                account: ctx.accounts.escrow_nft_account.to_account_info(),
+                destination: ctx.accounts.owner.to_account_info(),
+                authority: ctx.accounts.position_lock_state.to_account_info(),
+            },
+            &[&seeds[..]],
+        ))?;
+
+        emit!(PositionUnlockedEvent {
+            token_id: position_lock.mint,
+            owner: ctx.accounts.owner.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Creates a new position wrapped in a NFT. Tick, bitmap and core position accounts
+    /// that don't exist yet are created, paid for by the minter.
     ///
     /// # Arguments
     ///
     /// * `ctx` - Holds pool, tick, bitmap, position and token accounts
+    /// * `tick_lower` - The lower tick of the position
+    /// * `tick_upper` - The upper tick of the position
     /// * `amount_0_desired` - Desired amount of token_0 to be spent
     /// * `amount_1_desired` - Desired amount of token_1 to be spent
     /// * `amount_0_min` - The minimum amount of token_0 to spend, which serves as a slippage check
     /// * `amount_1_min` - The minimum amount of token_1 to spend, which serves as a slippage check
+    /// * `sqrt_price_min_x32` - The minimum Q32.32 sqrt price of the pool before the change.
+    /// Zero for no lower bound
+    /// * `sqrt_price_max_x32` - The maximum Q32.32 sqrt price of the pool before the change.
+    /// Zero for no upper bound
     /// * `deadline` - The time by which the transaction must be included to effect the change
     ///
     #[access_control(check_deadline(deadline))]
+    #[access_control(check_sqrt_price_bounds(&ctx.accounts.pool_state, sqrt_price_min_x32, sqrt_price_max_x32))]
     pub fn mint_tokenized_position<'a, 'b, 'c, 'info>(
         ctx: Context<'a, 'b, 'c, 'info, MintTokenizedPosition<'info>>,
+        tick_lower: i32,
+        tick_upper: i32,
         amount_0_desired: u64,
         amount_1_desired: u64,
         amount_0_min: u64,
         amount_1_min: u64,
+        sqrt_price_min_x32: u64,
+        sqrt_price_max_x32: u64,
         deadline: i64,
     ) -> Result<()> {
+        init_position_accounts(ctx.accounts, tick_lower, tick_upper)?;
+
         // Validate addresses manually, as constraint checks are not applied to internal calls
         let pool_state =
             AccountLoader::<PoolState>::try_from(&ctx.accounts.pool_state.to_account_info())?;
         let tick_lower_state =
             AccountLoader::<TickState>::try_from(&ctx.accounts.tick_lower_state.to_account_info())?;
-        let tick_lower = tick_lower_state.load()?.tick;
         let tick_upper_state =
             AccountLoader::<TickState>::try_from(&ctx.accounts.tick_upper_state.to_account_info())?;
-        let tick_upper = tick_upper_state.load()?.tick;
+        require!(
+            tick_lower_state.load()?.tick == tick_lower
+                && tick_upper_state.load()?.tick == tick_upper,
+            ErrorCode::InvalidAccountSeeds
+        );
 
         let mut accs = MintContext {
             minter: ctx.accounts.minter.clone(),
@@ -1521,72 +2152,224 @@ pub mod cyclos_core {
             tick_upper,
         )?;
 
-        // Mint the NFT
-        token::mint_to(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info().clone(),
-                token::MintTo {
-                    mint: ctx.accounts.nft_mint.to_account_info().clone(),
-                    to: ctx.accounts.nft_account.to_account_info().clone(),
-                    authority: ctx.accounts.factory_state.to_account_info().clone(),
-                },
-                &[&[&[ctx.accounts.factory_state.load()?.bump] as &[u8]]],
-            ),
-            1,
-        )?;
-
-        // Write tokenized position metadata
-        let mut tokenized_position = ctx.accounts.tokenized_position_state.load_init()?;
-        tokenized_position.bump = *ctx.bumps.get("tokenized_position_state").unwrap();
-        tokenized_position.mint = ctx.accounts.nft_mint.key();
-        tokenized_position.pool_id = ctx.accounts.pool_state.key();
-
-        tokenized_position.tick_lower = tick_lower; // can read from core position
-        tokenized_position.tick_upper = tick_upper;
-        tokenized_position.liquidity = liquidity;
-        tokenized_position.fee_growth_inside_0_last_x32 = AccountLoader::<PositionState>::try_from(
-            &ctx.accounts.core_position_state.to_account_info(),
-        )?
-        .load()?
-        .fee_growth_inside_0_last_x32;
-        tokenized_position.fee_growth_inside_1_last_x32 = AccountLoader::<PositionState>::try_from(
-            &ctx.accounts.core_position_state.to_account_info(),
-        )?
-        .load()?
-        .fee_growth_inside_1_last_x32;
-
-        emit!(IncreaseLiquidityEvent {
-            token_id: ctx.accounts.nft_mint.key(),
+        issue_tokenized_position(
+            ctx.accounts,
+            *ctx.bumps.get("tokenized_position_state").unwrap(),
+            tick_lower,
+            tick_upper,
             liquidity,
             amount_0,
-            amount_1
-        });
+            amount_1,
+        )
+    }
 
-        Ok(())
+    /// Creates a new position wrapped in a NFT from a single token. Part of the deposit is
+    /// swapped in the same pool so that the remainder and the swap output fit the range at
+    /// the post-swap price. Unused dust stays in the user's token accounts.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - Holds pool, tick, bitmap, position and token accounts. The first
+    /// `swap_accounts` remaining accounts are passed to the swap. If the swap writes a new
+    /// observation, the next observation account must be the last of them.
+    /// * `tick_lower` - The lower tick of the position
+    /// * `tick_upper` - The upper tick of the position
+    /// * `amount_in` - The amount of the deposited token
+    /// * `zero_for_one` - Whether token_0 is deposited, otherwise token_1
+    /// * `liquidity_min` - The minimum liquidity to mint, which serves as a slippage check
+    /// * `swap_accounts` - The number of bitmap, tick and observation accounts for the swap
+    /// * `deadline` - The time by which the transaction must be included to effect the change
+    ///
+    #[access_control(check_deadline(deadline))]
+    pub fn zap_in<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, MintTokenizedPosition<'info>>,
+        tick_lower: i32,
+        tick_upper: i32,
+        amount_in: u64,
+        zero_for_one: bool,
+        liquidity_min: u64,
+        swap_accounts: u8,
+        deadline: i64,
+    ) -> Result<LiquidityChange> {
+        init_position_accounts(ctx.accounts, tick_lower, tick_upper)?;
+
+        let pool_state =
+            AccountLoader::<PoolState>::try_from(&ctx.accounts.pool_state.to_account_info())?;
+        let tick_lower_state =
+            AccountLoader::<TickState>::try_from(&ctx.accounts.tick_lower_state.to_account_info())?;
+        let tick_upper_state =
+            AccountLoader::<TickState>::try_from(&ctx.accounts.tick_upper_state.to_account_info())?;
+        require!(
+            tick_lower_state.load()?.tick == tick_lower
+                && tick_upper_state.load()?.tick == tick_upper,
+            ErrorCode::InvalidAccountSeeds
+        );
+
+        let swap_accounts = swap_accounts as usize;
+        require!(
+            ctx.remaining_accounts.len() >= swap_accounts,
+            ErrorCode::InvalidSwapAccount
+        );
+        let (swap_remaining_accounts, mint_remaining_accounts) =
+            ctx.remaining_accounts.split_at(swap_accounts);
+
+        let pool = pool_state.load()?;
+        let swap_amount = zap_math::get_swap_amount_for_range(
+            pool.sqrt_price_x32,
+            pool.liquidity,
+            pool.fee,
+            tick_math::get_sqrt_ratio_at_tick(tick_lower)?,
+            tick_math::get_sqrt_ratio_at_tick(tick_upper)?,
+            amount_in,
+            zero_for_one,
+        );
+        drop(pool);
+
+        let (mut amount_0_desired, mut amount_1_desired) = if zero_for_one {
+            (amount_in, 0)
+        } else {
+            (0, amount_in)
+        };
+        if swap_amount > 0 {
+            let (input_token_account, output_token_account, input_vault, output_vault) =
+                if zero_for_one {
+                    (
+                        &ctx.accounts.token_account_0,
+                        &ctx.accounts.token_account_1,
+                        &ctx.accounts.vault_0,
+                        &ctx.accounts.vault_1,
+                    )
+                } else {
+                    (
+                        &ctx.accounts.token_account_1,
+                        &ctx.accounts.token_account_0,
+                        &ctx.accounts.vault_1,
+                        &ctx.accounts.vault_0,
+                    )
+                };
+            let result = swap(
+                Context::new(
+                    &ID,
+                    &mut SwapContext {
+                        signer: ctx.accounts.minter.clone(),
+                        factory_state: UncheckedAccount::try_from(
+                            ctx.accounts.factory_state.to_account_info(),
+                        ),
+                        input_token_account: input_token_account.clone(),
+                        output_token_account: output_token_account.clone(),
+                        input_vault: input_vault.clone(),
+                        output_vault: output_vault.clone(),
+                        token_program: ctx.accounts.token_program.clone(),
+                        pool_state: ctx.accounts.pool_state.clone(),
+                        last_observation_state: ctx.accounts.last_observation_state.clone(),
+                        callback_handler: UncheckedAccount::try_from(
+                            ctx.accounts.core_program.to_account_info(),
+                        ),
+                    },
+                    swap_remaining_accounts,
+                    BTreeMap::default(),
+                ),
+                i64::try_from(swap_amount).unwrap(),
+                if zero_for_one {
+                    tick_math::MIN_SQRT_RATIO + 1
+                } else {
+                    tick_math::MAX_SQRT_RATIO - 1
+                },
+                false,
+            )?;
+            if zero_for_one {
+                amount_0_desired -= result.amount_0 as u64;
+                amount_1_desired = (-result.amount_1) as u64;
+            } else {
+                amount_0_desired = (-result.amount_0) as u64;
+                amount_1_desired -= result.amount_1 as u64;
+            }
+            ctx.accounts.vault_0.reload()?;
+            ctx.accounts.vault_1.reload()?;
+        }
+
+        let mut accs = MintContext {
+            minter: ctx.accounts.minter.clone(),
+            token_account_0: ctx.accounts.token_account_0.clone(),
+            token_account_1: ctx.accounts.token_account_1.clone(),
+            vault_0: ctx.accounts.vault_0.clone(),
+            vault_1: ctx.accounts.vault_1.clone(),
+            recipient: UncheckedAccount::try_from(ctx.accounts.factory_state.to_account_info()),
+            pool_state,
+            tick_lower_state,
+            tick_upper_state,
+            bitmap_lower_state: ctx.accounts.bitmap_lower_state.clone(),
+            bitmap_upper_state: ctx.accounts.bitmap_upper_state.clone(),
+            position_state: ctx.accounts.core_position_state.clone(),
+            last_observation_state: ctx.accounts.last_observation_state.clone(),
+            token_program: ctx.accounts.token_program.clone(),
+            callback_handler: UncheckedAccount::try_from(
+                ctx.accounts.core_program.to_account_info(),
+            ),
+        };
+        let (liquidity, amount_0, amount_1) = add_liquidity(
+            &mut accs,
+            mint_remaining_accounts,
+            amount_0_desired,
+            amount_1_desired,
+            0,
+            0,
+            tick_lower,
+            tick_upper,
+        )?;
+        require!(liquidity >= liquidity_min, ErrorCode::PriceSlippageCheck);
+
+        issue_tokenized_position(
+            ctx.accounts,
+            *ctx.bumps.get("tokenized_position_state").unwrap(),
+            tick_lower,
+            tick_upper,
+            liquidity,
+            amount_0,
+            amount_1,
+        )?;
+
+        Ok(LiquidityChange {
+            liquidity,
+            amount_0,
+            amount_1,
+        })
     }
 
-    /// Attach metaplex metadata to a tokenized position. Permissionless to call.
-    /// Optional and cosmetic in nature.
+    /// Attach metaplex metadata to a tokenized position and verify it into the position
+    /// collection. Permissionless to call. Optional and cosmetic in nature.
+    ///
+    /// The NFT is named after the pair, fee tier and tick range of the position. The URI is
+    /// the factory base URI followed by the mint address.
+    ///
+    /// Fails until the factory owner has created the collection with
+    /// `create_position_collection`.
     ///
     /// # Arguments
     ///
     /// * `ctx` - Holds validated metadata account and tokenized position addresses
     ///
     pub fn add_metaplex_metadata(ctx: Context<AddMetaplexMetadata>) -> Result<()> {
-        let seeds = [&[ctx.accounts.factory_state.load()?.bump] as &[u8]];
-        let create_metadata_ix = create_metadata_accounts(
+        let factory = *ctx.accounts.factory_state.load()?;
+        let pool = *ctx.accounts.pool_state.load()?;
+        let tokenized_position = *ctx.accounts.tokenized_position_state.load()?;
+        let seeds = [&[factory.bump] as &[u8]];
+        let create_metadata_ix = create_metadata_accounts_v3(
             ctx.accounts.metadata_program.key(),
             ctx.accounts.metadata_account.key(),
             ctx.accounts.nft_mint.key(),
             ctx.accounts.factory_state.key(),
             ctx.accounts.payer.key(),
             ctx.accounts.factory_state.key(),
-            String::from("Cyclos Positions NFT-V1"),
-            String::from("CYS-POS"),
-            format!(
-                "https://asia-south1-cyclos-finance.cloudfunctions.net/nft?mint={}",
-                ctx.accounts.nft_mint.key()
+            position_name(
+                &pool.token_0,
+                &pool.token_1,
+                pool.fee,
+                tokenized_position.tick_lower,
+                tokenized_position.tick_upper,
             ),
+            String::from("CYS-POS"),
+            factory.token_uri(&ctx.accounts.nft_mint.key()),
             Some(vec![Creator {
                 address: ctx.accounts.factory_state.key(),
                 verified: true,
@@ -1595,6 +2378,12 @@ pub mod cyclos_core {
             0,
             true,
             false,
+            Some(Collection {
+                verified: false,
+                key: ctx.accounts.collection_mint.key(),
+            }),
+            None,
+            None,
         );
         solana_program::program::invoke_signed(
             &create_metadata_ix,
@@ -1604,7 +2393,30 @@ pub mod cyclos_core {
                 ctx.accounts.payer.to_account_info().clone(),
                 ctx.accounts.factory_state.to_account_info().clone(), // mint and update authority
                 ctx.accounts.system_program.to_account_info().clone(),
-                ctx.accounts.rent.to_account_info().clone(),
+            ],
+            &[&seeds[..]],
+        )?;
+
+        // The factory is the collection update authority
+        let verify_collection_ix = verify_collection(
+            ctx.accounts.metadata_program.key(),
+            ctx.accounts.metadata_account.key(),
+            ctx.accounts.factory_state.key(),
+            ctx.accounts.payer.key(),
+            ctx.accounts.collection_mint.key(),
+            ctx.accounts.collection_metadata.key(),
+            ctx.accounts.collection_master_edition.key(),
+            None,
+        );
+        solana_program::program::invoke_signed(
+            &verify_collection_ix,
+            &[
+                ctx.accounts.metadata_account.to_account_info(),
+                ctx.accounts.factory_state.to_account_info(),
+                ctx.accounts.payer.to_account_info(),
+                ctx.accounts.collection_mint.to_account_info(),
+                ctx.accounts.collection_metadata.to_account_info(),
+                ctx.accounts.collection_master_edition.to_account_info(),
             ],
             &[&seeds[..]],
         )?;
@@ -1626,26 +2438,73 @@ pub mod cyclos_core {
         Ok(())
     }
 
-    /// Increases liquidity in a tokenized position, with amount paid by `payer`
+    /// Sets and verifies the collection of a position NFT whose metadata was attached
+    /// before the position collection existed. Permissionless to call.
     ///
     /// # Arguments
     ///
-    /// * `ctx` - Holds the pool, tick, bitmap, position and token accounts
+    /// * `ctx` - Holds the position metadata and the collection accounts
+    ///
+    pub fn verify_position_collection(ctx: Context<VerifyPositionCollection>) -> Result<()> {
+        let seeds = [&[ctx.accounts.factory_state.load()?.bump] as &[u8]];
+
+        // The factory is update authority of both the position metadata and the collection
+        let set_and_verify_collection_ix = set_and_verify_collection(
+            ctx.accounts.metadata_program.key(),
+            ctx.accounts.metadata_account.key(),
+            ctx.accounts.factory_state.key(),
+            ctx.accounts.payer.key(),
+            ctx.accounts.factory_state.key(),
+            ctx.accounts.collection_mint.key(),
+            ctx.accounts.collection_metadata.key(),
+            ctx.accounts.collection_master_edition.key(),
+            None,
+        );
+        solana_program::program::invoke_signed(
+            &set_and_verify_collection_ix,
+            &[
+                ctx.accounts.metadata_account.to_account_info(),
+                ctx.accounts.factory_state.to_account_info(),
+                ctx.accounts.payer.to_account_info(),
+                ctx.accounts.collection_mint.to_account_info(),
+                ctx.accounts.collection_metadata.to_account_info(),
+                ctx.accounts.collection_master_edition.to_account_info(),
+            ],
+            &[&seeds[..]],
+        )?;
+
+        Ok(())
+    }
+
+    /// Increases liquidity in a tokenized position, with amount paid by `payer`. Fails if the
+    /// position NFT is locked
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - Holds the pool, tick, bitmap, position, NFT and token accounts
     /// * `amount_0_desired` - Desired amount of token_0 to be spent
     /// * `amount_1_desired` - Desired amount of token_1 to be spent
     /// * `amount_0_min` - The minimum amount of token_0 to spend, which serves as a slippage check
     /// * `amount_1_min` - The minimum amount of token_1 to spend, which serves as a slippage check
+    /// * `sqrt_price_min_x32` - The minimum Q32.32 sqrt price of the pool before the change.
+    /// Zero for no lower bound
+    /// * `sqrt_price_max_x32` - The maximum Q32.32 sqrt price of the pool before the change.
+    /// Zero for no upper bound
     /// * `deadline` - The time by which the transaction must be included to effect the change
     ///
     #[access_control(check_deadline(deadline))]
+    #[access_control(check_sqrt_price_bounds(&ctx.accounts.pool_state, sqrt_price_min_x32, sqrt_price_max_x32))]
+    #[access_control(is_not_locked(&ctx.accounts.nft_account))]
     pub fn increase_liquidity<'a, 'b, 'c, 'info>(
         ctx: Context<'a, 'b, 'c, 'info, IncreaseLiquidity<'info>>,
         amount_0_desired: u64,
         amount_1_desired: u64,
         amount_0_min: u64,
         amount_1_min: u64,
+        sqrt_price_min_x32: u64,
+        sqrt_price_max_x32: u64,
         deadline: i64,
-    ) -> Result<()> {
+    ) -> Result<LiquidityChange> {
         let pool_state =
             AccountLoader::<PoolState>::try_from(&ctx.accounts.pool_state.to_account_info())?;
         let tick_lower_state =
@@ -1716,28 +2575,40 @@ pub mod cyclos_core {
             amount_1
         });
 
-        Ok(())
+        Ok(LiquidityChange {
+            liquidity,
+            amount_0,
+            amount_1,
+        })
     }
 
     /// Decreases the amount of liquidity in a position and accounts it to the position
     ///
     /// # Arguments
     ///
-    /// * `ctx` - Holds the pool, tick, bitmap, position and token accounts
+    /// * `ctx` - Holds the pool, tick, bitmap, position and token accounts. An approved operator
+    /// passes the operator approval of the NFT owner as the first remaining account
     /// * `liquidity` - The amount by which liquidity will be decreased
     /// * `amount_0_min` - The minimum amount of token_0 that should be accounted for the burned liquidity
     /// * `amount_1_min` - The minimum amount of token_1 that should be accounted for the burned liquidity
+    /// * `sqrt_price_min_x32` - The minimum Q32.32 sqrt price of the pool before the change.
+    /// Zero for no lower bound
+    /// * `sqrt_price_max_x32` - The maximum Q32.32 sqrt price of the pool before the change.
+    /// Zero for no upper bound
     /// * `deadline` - The time by which the transaction must be included to effect the change
     ///
     #[access_control(check_deadline(deadline))]
-    #[access_control(is_authorized_for_token(&ctx.accounts.owner_or_delegate, &ctx.accounts.nft_account))]
+    #[access_control(check_sqrt_price_bounds(&ctx.accounts.pool_state, sqrt_price_min_x32, sqrt_price_max_x32))]
+    #[access_control(is_authorized_for_token(&ctx.accounts.owner_or_delegate, &ctx.accounts.nft_account, ctx.remaining_accounts))]
     pub fn decrease_liquidity<'a, 'b, 'c, 'info>(
         ctx: Context<'a, 'b, 'c, 'info, DecreaseLiquidity<'info>>,
         liquidity: u64,
         amount_0_min: u64,
         amount_1_min: u64,
+        sqrt_price_min_x32: u64,
+        sqrt_price_max_x32: u64,
         deadline: i64,
-    ) -> Result<()> {
+    ) -> Result<LiquidityChange> {
         assert!(liquidity > 0);
 
         let position_state = AccountLoader::<PositionState>::try_from(
@@ -1758,13 +2629,13 @@ pub mod cyclos_core {
             position_state,
             last_observation_state: ctx.accounts.last_observation_state.clone(),
         };
+        let remaining_accounts = skip_operator_approval(
+            &ctx.accounts.owner_or_delegate.key(),
+            &ctx.accounts.nft_account,
+            ctx.remaining_accounts,
+        );
         burn(
-            Context::new(
-                &ID,
-                &mut accounts,
-                ctx.remaining_accounts,
-                BTreeMap::default(),
-            ),
+            Context::new(&ID, &mut accounts, remaining_accounts, BTreeMap::default()),
             liquidity,
         )?;
         let updated_core_position = accounts.position_state.load()?;
@@ -1801,7 +2672,11 @@ pub mod cyclos_core {
             amount_1
         });
 
-        Ok(())
+        Ok(LiquidityChange {
+            liquidity,
+            amount_0,
+            amount_1,
+        })
     }
 
     /// Collects up to a maximum amount of fees owed to a specific tokenized position to the recipient
@@ -1809,16 +2684,17 @@ pub mod cyclos_core {
     /// # Arguments
     ///
     /// * `ctx` - Validated addresses of the tokenized position and token accounts. Fees can be sent
-    /// to third parties
+    /// to third parties. An approved operator passes the operator approval of the NFT owner as
+    /// the first remaining account
     /// * `amount_0_max` - The maximum amount of token0 to collect
     /// * `amount_1_max` - The maximum amount of token0 to collect
     ///
-    #[access_control(is_authorized_for_token(&ctx.accounts.owner_or_delegate, &ctx.accounts.nft_account))]
+    #[access_control(is_authorized_to_collect(&ctx.accounts.owner_or_delegate, &ctx.accounts.nft_account, ctx.remaining_accounts))]
     pub fn collect_from_tokenized<'a, 'b, 'c, 'info>(
         ctx: Context<'a, 'b, 'c, 'info, CollectFromTokenized<'info>>,
         amount_0_max: u64,
         amount_1_max: u64,
-    ) -> Result<()> {
+    ) -> Result<TokenAmounts> {
         assert!(amount_0_max > 0 || amount_1_max > 0);
 
         let mut tokenized_position = ctx.accounts.tokenized_position_state.load_mut()?;
@@ -1843,11 +2719,16 @@ pub mod cyclos_core {
                 position_state,
                 last_observation_state: ctx.accounts.last_observation_state.clone(),
             };
+            let remaining_accounts = skip_operator_approval(
+                &ctx.accounts.owner_or_delegate.key(),
+                &ctx.accounts.nft_account,
+                ctx.remaining_accounts,
+            );
             burn(
                 Context::new(
                     &ID,
                     &mut burn_accounts,
-                    ctx.remaining_accounts,
+                    remaining_accounts,
                     BTreeMap::default(),
                 ),
                 0,
@@ -1916,185 +2797,1245 @@ pub mod cyclos_core {
             amount_1
         });
 
-        Ok(())
+        Ok(TokenAmounts { amount_0, amount_1 })
     }
 
-    /// Swaps `amount_in` of one token for as much as possible of another token,
-    /// across a single pool
+    /// Collects all tokens owed to a batch of position NFTs in the same pool. Each position
+    /// is poked to update its fees, and the amounts are sent in a single transfer per token.
+    ///
+    /// Every position is passed as remaining accounts, in the order tokenized position state,
+    /// NFT token account, core position state, lower tick, upper tick, lower bitmap and upper
+    /// bitmap.
     ///
     /// # Arguments
     ///
-    /// * `ctx` - Accounts required for the swap
-    /// * `deadline` - The time by which the transaction must be included to effect the change
-    /// * `amount_in` - Token amount to be swapped in
-    /// * `amount_out_minimum` - The minimum amount to swap out, which serves as a slippage check
-    /// * `sqrt_price_limit` - The Q32.32 sqrt price √P limit. If zero for one, the price cannot
-    /// be less than this value after the swap.  If one for zero, the price cannot be greater than
-    /// this value after the swap.
+    /// * `ctx` - Holds the pool, vault and recipient accounts, followed by the positions
+    /// * `has_operator_approval` - Whether the operator approval of the position owner for
+    /// the signer is passed as the first remaining account, before the positions
     ///
-    #[access_control(check_deadline(deadline))]
-    pub fn exact_input_single<'a, 'b, 'c, 'info>(
-        ctx: Context<'a, 'b, 'c, 'info, ExactInputSingle<'info>>,
-        deadline: i64,
-        amount_in: u64,
-        amount_out_minimum: u64,
-        sqrt_price_limit_x32: u64,
-    ) -> Result<()> {
-        let amount_out = exact_input_internal(
-            &mut SwapContext {
-                signer: ctx.accounts.signer.clone(),
-                factory_state: ctx.accounts.factory_state.clone(),
-                input_token_account: ctx.accounts.input_token_account.clone(),
-                output_token_account: ctx.accounts.output_token_account.clone(),
-                input_vault: ctx.accounts.input_vault.clone(),
-                output_vault: ctx.accounts.output_vault.clone(),
-                token_program: ctx.accounts.token_program.clone(),
-                pool_state: ctx.accounts.pool_state.clone(),
-                last_observation_state: ctx.accounts.last_observation_state.clone(),
-                // next_observation_state: ctx.accounts.next_observation_state.clone(),
-                callback_handler: UncheckedAccount::try_from(
-                    ctx.accounts.core_program.to_account_info(),
-                ),
-            },
-            ctx.remaining_accounts,
-            amount_in,
-            sqrt_price_limit_x32,
-        )?;
+    pub fn collect_from_tokenized_batch<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, CollectFromTokenizedBatch<'info>>,
+        has_operator_approval: bool,
+    ) -> Result<TokenAmounts> {
+        let pool_state =
+            AccountLoader::<PoolState>::try_from(&ctx.accounts.pool_state.to_account_info())?;
+        let pool = *pool_state.load()?;
         require!(
-            amount_out >= amount_out_minimum,
-            ErrorCode::TooLittleReceived
+            ctx.accounts.vault_0.key()
+                == get_associated_token_address(&pool_state.key(), &pool.token_0)
+                && ctx.accounts.vault_1.key()
+                    == get_associated_token_address(&pool_state.key(), &pool.token_1),
+            ErrorCode::InvalidVault
         );
-        Ok(())
-    }
+        let approval_account_count = if has_operator_approval { 1 } else { 0 };
+        require!(
+            ctx.remaining_accounts.len() > approval_account_count
+                && (ctx.remaining_accounts.len() - approval_account_count).is_multiple_of(7),
+            ErrorCode::InvalidAccountCount
+        );
+        let (approval_accounts, position_accounts) =
+            ctx.remaining_accounts.split_at(approval_account_count);
+        if has_operator_approval {
+            AccountLoader::<OperatorApprovalState>::try_from(&approval_accounts[0])
+                .map_err(|_| ErrorCode::NotApproved)?;
+        }
 
-    /// Swaps `amount_in` of one token for as much as possible of another token,
-    /// across the path provided
-    ///
-    /// # Arguments
-    ///
-    /// * `ctx` - Accounts for token transfer and swap route
-    /// * `deadline` - Swap should if fail if past deadline
-    /// * `amount_in` - Token amount to be swapped in
-    /// * `amount_out_minimum` - Panic if output amount is below minimum amount. For slippage.
-    /// * `additional_accounts_per_pool` - Additional observation, bitmap and tick accounts per pool
-    ///
-    #[access_control(check_deadline(deadline))]
-    pub fn exact_input<'a, 'b, 'c, 'info>(
-        ctx: Context<'a, 'b, 'c, 'info, ExactInput<'info>>,
-        deadline: i64,
-        amount_in: u64,
-        amount_out_minimum: u64,
-        additional_accounts_per_pool: Vec<u8>,
-    ) -> Result<()> {
-        let mut remaining_accounts = ctx.remaining_accounts.iter();
+        let mut core_position_owner = ctx.accounts.factory_state.to_account_info();
+        core_position_owner.is_signer = true;
 
-        let mut amount_in_internal = amount_in;
-        let mut input_token_account = ctx.accounts.input_token_account.clone();
-        for i in 0..additional_accounts_per_pool.len() {
-            let pool_state = UncheckedAccount::try_from(remaining_accounts.next().unwrap().clone());
-            let output_token_account =
-                UncheckedAccount::try_from(remaining_accounts.next().unwrap().clone());
-            let input_vault = Box::new(Account::<TokenAccount>::try_from(
-                remaining_accounts.next().unwrap(),
-            )?);
-            let output_vault = Box::new(Account::<TokenAccount>::try_from(
-                remaining_accounts.next().unwrap(),
-            )?);
+        let mut amount_0 = 0;
+        let mut amount_1 = 0;
+        for accounts in position_accounts.chunks(7) {
+            let tokenized_position_state =
+                AccountLoader::<TokenizedPositionState>::try_from(&accounts[0])?;
+            let mut tokenized_position = tokenized_position_state.load_mut()?;
+            require!(
+                tokenized_position.pool_id == ctx.accounts.pool_state.key(),
+                ErrorCode::PositionMismatch
+            );
 
-            amount_in_internal = exact_input_internal(
-                &mut SwapContext {
-                    signer: ctx.accounts.signer.clone(),
-                    factory_state: ctx.accounts.factory_state.clone(),
-                    input_token_account: input_token_account.clone(),
-                    pool_state,
-                    output_token_account: output_token_account.clone(),
-                    input_vault,
-                    output_vault,
-                    last_observation_state: UncheckedAccount::try_from(
-                        remaining_accounts.next().unwrap().clone(),
-                    ),
-                    token_program: ctx.accounts.token_program.clone(),
-                    callback_handler: UncheckedAccount::try_from(
-                        ctx.accounts.core_program.to_account_info(),
-                    ),
-                },
-                remaining_accounts.as_slice(),
-                amount_in_internal,
-                0,
+            let nft_account = Box::new(Account::<TokenAccount>::try_from(&accounts[1])?);
+            require!(
+                nft_account.mint == tokenized_position.mint,
+                ErrorCode::NotApproved
+            );
+            is_authorized_to_collect(
+                &ctx.accounts.owner_or_delegate,
+                &nft_account,
+                approval_accounts,
             )?;
 
-            if i < additional_accounts_per_pool.len() - 1 {
-                // reach accounts needed for the next swap
-                for _j in 0..additional_accounts_per_pool[i] {
-                    remaining_accounts.next();
-                }
-                // output token account is the new input
-                input_token_account = output_token_account;
+            let position_state = AccountLoader::<PositionState>::try_from(&accounts[2])?;
+            pool.validate_position_address(
+                &position_state.key(),
+                position_state.load()?.bump,
+                &ctx.accounts.factory_state.key(),
+                tokenized_position.tick_lower,
+                tokenized_position.tick_upper,
+            )?;
+
+            // trigger an update of the position fees owed and fee growth snapshots
+            if tokenized_position.liquidity > 0 {
+                let mut burn_accounts = BurnContext {
+                    owner: Signer::try_from(&core_position_owner)?,
+                    pool_state: ctx.accounts.pool_state.clone(),
+                    tick_lower_state: UncheckedAccount::try_from(accounts[3].clone()),
+                    tick_upper_state: UncheckedAccount::try_from(accounts[4].clone()),
+                    bitmap_lower_state: UncheckedAccount::try_from(accounts[5].clone()),
+                    bitmap_upper_state: UncheckedAccount::try_from(accounts[6].clone()),
+                    position_state: position_state.clone(),
+                    last_observation_state: ctx.accounts.last_observation_state.clone(),
+                };
+                // pokes do not invoke hooks, so no hook program is needed
+                burn(
+                    Context::new(&ID, &mut burn_accounts, &[], BTreeMap::default()),
+                    0,
+                )?;
             }
+
+            let mut core_position = position_state.load_mut()?;
+            tokenized_position.update_tokens_owed(
+                core_position.fee_growth_inside_0_last_x32,
+                core_position.fee_growth_inside_1_last_x32,
+            );
+            let tokens_owed_0 = tokenized_position.tokens_owed_0;
+            let tokens_owed_1 = tokenized_position.tokens_owed_1;
+
+            // core may hold a few less wei than expected due to rounding down, but the full
+            // amount is cleared from the tokenized position so the token can be burned
+            let collected_0 = tokens_owed_0.min(core_position.tokens_owed_0);
+            let collected_1 = tokens_owed_1.min(core_position.tokens_owed_1);
+            core_position.tokens_owed_0 -= collected_0;
+            core_position.tokens_owed_1 -= collected_1;
+            tokenized_position.tokens_owed_0 = 0;
+            tokenized_position.tokens_owed_1 = 0;
+            amount_0 += collected_0;
+            amount_1 += collected_1;
+
+            emit!(CollectTokenizedEvent {
+                token_id: tokenized_position.mint,
+                recipient_wallet_0: ctx.accounts.recipient_wallet_0.key(),
+                recipient_wallet_1: ctx.accounts.recipient_wallet_1.key(),
+                amount_0: collected_0,
+                amount_1: collected_1
+            });
         }
-        require!(
-            amount_in_internal >= amount_out_minimum,
+
+        require!(pool_state.load()?.unlocked, ErrorCode::LOK);
+        let pool_state_seeds = [
+            POOL_SEED.as_bytes(),
+            &pool.token_0.to_bytes() as &[u8],
+            &pool.token_1.to_bytes() as &[u8],
+            &pool.fee.to_be_bytes(),
+            &[pool.bump],
+        ];
+        if amount_0 > 0 {
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    token::Transfer {
+                        from: ctx.accounts.vault_0.to_account_info(),
+                        to: ctx.accounts.recipient_wallet_0.to_account_info(),
+                        authority: ctx.accounts.pool_state.to_account_info(),
+                    },
+                    &[&pool_state_seeds[..]],
+                ),
+                amount_0,
+            )?;
+        }
+        if amount_1 > 0 {
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    token::Transfer {
+                        from: ctx.accounts.vault_1.to_account_info(),
+                        to: ctx.accounts.recipient_wallet_1.to_account_info(),
+                        authority: ctx.accounts.pool_state.to_account_info(),
+                    },
+                    &[&pool_state_seeds[..]],
+                ),
+                amount_1,
+            )?;
+        }
+
+        Ok(TokenAmounts { amount_0, amount_1 })
+    }
+
+    /// Decreases the liquidity of a position by a fraction of its current liquidity, and
+    /// accounts it to the position
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - Holds the pool, tick, bitmap, position and token accounts
+    /// * `liquidity_bps` - The fraction of the position liquidity to remove, in basis points.
+    /// Fails if it rounds down to zero liquidity
+    /// * `amount_0_min` - The minimum amount of token_0 that should be accounted for the burned liquidity
+    /// * `amount_1_min` - The minimum amount of token_1 that should be accounted for the burned liquidity
+    /// * `sqrt_price_min_x32` - The minimum Q32.32 sqrt price of the pool before the change.
+    /// Zero for no lower bound
+    /// * `sqrt_price_max_x32` - The maximum Q32.32 sqrt price of the pool before the change.
+    /// Zero for no upper bound
+    /// * `deadline` - The time by which the transaction must be included to effect the change
+    ///
+    pub fn decrease_liquidity_bps<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, DecreaseLiquidity<'info>>,
+        liquidity_bps: u16,
+        amount_0_min: u64,
+        amount_1_min: u64,
+        sqrt_price_min_x32: u64,
+        sqrt_price_max_x32: u64,
+        deadline: i64,
+    ) -> Result<LiquidityChange> {
+        let liquidity = ctx
+            .accounts
+            .tokenized_position_state
+            .load()?
+            .liquidity_for_bps(liquidity_bps)?;
+
+        decrease_liquidity(
+            Context::new(
+                &ID,
+                ctx.accounts,
+                ctx.remaining_accounts,
+                BTreeMap::default(),
+            ),
+            liquidity,
+            amount_0_min,
+            amount_1_min,
+            sqrt_price_min_x32,
+            sqrt_price_max_x32,
+            deadline,
+        )
+    }
+
+    /// Decreases the liquidity of a position, and collects the principal together with
+    /// all fees owed to the position
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - Holds the pool, tick, bitmap, position and token accounts. If the decrease
+    /// writes a new observation, the next observation account must be the first remaining
+    /// account, after the operator approval if the signer is an approved operator.
+    /// * `liquidity` - The amount by which liquidity will be decreased
+    /// * `amount_0_min` - The minimum amount of token_0 that should be accounted for the burned liquidity
+    /// * `amount_1_min` - The minimum amount of token_1 that should be accounted for the burned liquidity
+    /// * `sqrt_price_min_x32` - The minimum Q32.32 sqrt price of the pool before the change.
+    /// Zero for no lower bound
+    /// * `sqrt_price_max_x32` - The maximum Q32.32 sqrt price of the pool before the change.
+    /// Zero for no upper bound
+    /// * `deadline` - The time by which the transaction must be included to effect the change
+    ///
+    pub fn decrease_and_collect<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, CollectFromTokenized<'info>>,
+        liquidity: u64,
+        amount_0_min: u64,
+        amount_1_min: u64,
+        sqrt_price_min_x32: u64,
+        sqrt_price_max_x32: u64,
+        deadline: i64,
+    ) -> Result<TokenAmounts> {
+        let pool_state =
+            AccountLoader::<PoolState>::try_from(&ctx.accounts.pool_state.to_account_info())?;
+        let observation_index = pool_state.load()?.observation_index;
+
+        decrease_liquidity(
+            Context::new(
+                &ID,
+                &mut DecreaseLiquidity {
+                    owner_or_delegate: ctx.accounts.owner_or_delegate.clone(),
+                    nft_account: ctx.accounts.nft_account.clone(),
+                    tokenized_position_state: ctx.accounts.tokenized_position_state.clone(),
+                    factory_state: ctx.accounts.factory_state.clone(),
+                    pool_state: ctx.accounts.pool_state.clone(),
+                    core_position_state: ctx.accounts.core_position_state.clone(),
+                    tick_lower_state: ctx.accounts.tick_lower_state.clone(),
+                    tick_upper_state: ctx.accounts.tick_upper_state.clone(),
+                    bitmap_lower_state: ctx.accounts.bitmap_lower_state.clone(),
+                    bitmap_upper_state: ctx.accounts.bitmap_upper_state.clone(),
+                    last_observation_state: ctx.accounts.last_observation_state.clone(),
+                    core_program: ctx.accounts.core_program.clone(),
+                },
+                ctx.remaining_accounts,
+                BTreeMap::default(),
+            ),
+            liquidity,
+            amount_0_min,
+            amount_1_min,
+            sqrt_price_min_x32,
+            sqrt_price_max_x32,
+            deadline,
+        )?;
+
+        // The decrease may have written the next observation
+        if pool_state.load()?.observation_index != observation_index {
+            let remaining_accounts = skip_operator_approval(
+                &ctx.accounts.owner_or_delegate.key(),
+                &ctx.accounts.nft_account,
+                ctx.remaining_accounts,
+            );
+            let next_observation = pool_state
+                .load()?
+                .next_observation_account(remaining_accounts)?;
+            ctx.accounts.last_observation_state =
+                UncheckedAccount::try_from(next_observation.clone());
+        }
+
+        collect_from_tokenized(
+            Context::new(
+                &ID,
+                ctx.accounts,
+                ctx.remaining_accounts,
+                BTreeMap::default(),
+            ),
+            u64::MAX,
+            u64::MAX,
+        )
+    }
+
+    /// Withdraws liquidity and owed tokens from a tokenized position, and swaps the unwanted
+    /// token in the same pool so that the user receives a single token
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - Holds the pool, tick, bitmap, position and token accounts. The last
+    /// `swap_accounts` remaining accounts are passed to the swap, the others to the liquidity
+    /// decrease. If the decrease writes a new observation, the next observation account must
+    /// be the first remaining account, after the operator approval if the signer is an
+    /// approved operator.
+    /// * `liquidity` - The amount by which liquidity will be decreased. Can be 0 to only
+    /// collect owed tokens.
+    /// * `zero_for_one` - Whether token_0 is swapped and token_1 received, otherwise token_0
+    /// is received
+    /// * `amount_out_minimum` - The minimum total amount of the received token, which serves
+    /// as a slippage check
+    /// * `swap_accounts` - The number of bitmap, tick and observation accounts for the swap
+    /// * `burn_nft` - Burn the position NFT and close its position account to the signer. The
+    /// position must be fully exited, and the signer must own the NFT. Operators rebalance
+    /// without burning, and mint the new range to the owner with `zap_in`.
+    /// * `deadline` - The time by which the transaction must be included to effect the change
+    ///
+    #[access_control(check_deadline(deadline))]
+    #[access_control(is_authorized_for_token(&ctx.accounts.owner_or_delegate, &ctx.accounts.nft_account, ctx.remaining_accounts))]
+    pub fn zap_out<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, ZapOut<'info>>,
+        liquidity: u64,
+        zero_for_one: bool,
+        amount_out_minimum: u64,
+        swap_accounts: u8,
+        burn_nft: bool,
+        deadline: i64,
+    ) -> Result<u64> {
+        let swap_accounts = swap_accounts as usize;
+        require!(
+            ctx.remaining_accounts.len() >= swap_accounts,
+            ErrorCode::InvalidSwapAccount
+        );
+        let (liquidity_remaining_accounts, swap_remaining_accounts) = ctx
+            .remaining_accounts
+            .split_at(ctx.remaining_accounts.len() - swap_accounts);
+
+        let pool_state =
+            AccountLoader::<PoolState>::try_from(&ctx.accounts.pool_state.to_account_info())?;
+        let observation_index = pool_state.load()?.observation_index;
+
+        if liquidity > 0 {
+            decrease_liquidity(
+                Context::new(
+                    &ID,
+                    &mut DecreaseLiquidity {
+                        owner_or_delegate: ctx.accounts.owner_or_delegate.clone(),
+                        nft_account: ctx.accounts.nft_account.clone(),
+                        tokenized_position_state: ctx.accounts.tokenized_position_state.clone(),
+                        factory_state: ctx.accounts.factory_state.clone(),
+                        pool_state: ctx.accounts.pool_state.clone(),
+                        core_position_state: ctx.accounts.core_position_state.clone(),
+                        tick_lower_state: ctx.accounts.tick_lower_state.clone(),
+                        tick_upper_state: ctx.accounts.tick_upper_state.clone(),
+                        bitmap_lower_state: ctx.accounts.bitmap_lower_state.clone(),
+                        bitmap_upper_state: ctx.accounts.bitmap_upper_state.clone(),
+                        last_observation_state: ctx.accounts.last_observation_state.clone(),
+                        core_program: ctx.accounts.core_program.clone(),
+                    },
+                    liquidity_remaining_accounts,
+                    BTreeMap::default(),
+                ),
+                liquidity,
+                0,
+                0,
+                0,
+                0,
+                deadline,
+            )?;
+        }
+
+        // The decrease may have written the next observation
+        let last_observation_state = if pool_state.load()?.observation_index != observation_index
+        {
+            let remaining_accounts = skip_operator_approval(
+                &ctx.accounts.owner_or_delegate.key(),
+                &ctx.accounts.nft_account,
+                liquidity_remaining_accounts,
+            );
+            let next_observation = pool_state
+                .load()?
+                .next_observation_account(remaining_accounts)?;
+            UncheckedAccount::try_from(next_observation.clone())
+        } else {
+            ctx.accounts.last_observation_state.clone()
+        };
+
+        let collected = collect_from_tokenized(
+            Context::new(
+                &ID,
+                &mut CollectFromTokenized {
+                    owner_or_delegate: ctx.accounts.owner_or_delegate.clone(),
+                    nft_account: ctx.accounts.nft_account.clone(),
+                    tokenized_position_state: ctx.accounts.tokenized_position_state.clone(),
+                    factory_state: ctx.accounts.factory_state.clone(),
+                    pool_state: ctx.accounts.pool_state.clone(),
+                    core_position_state: ctx.accounts.core_position_state.clone(),
+                    tick_lower_state: ctx.accounts.tick_lower_state.clone(),
+                    tick_upper_state: ctx.accounts.tick_upper_state.clone(),
+                    bitmap_lower_state: ctx.accounts.bitmap_lower_state.clone(),
+                    bitmap_upper_state: ctx.accounts.bitmap_upper_state.clone(),
+                    last_observation_state: last_observation_state.clone(),
+                    vault_0: ctx.accounts.vault_0.clone(),
+                    vault_1: ctx.accounts.vault_1.clone(),
+                    recipient_wallet_0: ctx.accounts.token_account_0.clone(),
+                    recipient_wallet_1: ctx.accounts.token_account_1.clone(),
+                    core_program: ctx.accounts.core_program.clone(),
+                    token_program: ctx.accounts.token_program.clone(),
+                },
+                liquidity_remaining_accounts,
+                BTreeMap::default(),
+            ),
+            u64::MAX,
+            u64::MAX,
+        )?;
+        ctx.accounts.vault_0.reload()?;
+        ctx.accounts.vault_1.reload()?;
+
+        let (amount_in, mut amount_out) = if zero_for_one {
+            (collected.amount_0, collected.amount_1)
+        } else {
+            (collected.amount_1, collected.amount_0)
+        };
+        if amount_in > 0 {
+            let (input_token_account, output_token_account, input_vault, output_vault) =
+                if zero_for_one {
+                    (
+                        &ctx.accounts.token_account_0,
+                        &ctx.accounts.token_account_1,
+                        &ctx.accounts.vault_0,
+                        &ctx.accounts.vault_1,
+                    )
+                } else {
+                    (
+                        &ctx.accounts.token_account_1,
+                        &ctx.accounts.token_account_0,
+                        &ctx.accounts.vault_1,
+                        &ctx.accounts.vault_0,
+                    )
+                };
+            let result = exact_input_internal(
+                &mut SwapContext {
+                    signer: ctx.accounts.owner_or_delegate.clone(),
+                    factory_state: UncheckedAccount::try_from(
+                        ctx.accounts.factory_state.to_account_info(),
+                    ),
+                    input_token_account: input_token_account.clone(),
+                    output_token_account: output_token_account.clone(),
+                    input_vault: input_vault.clone(),
+                    output_vault: output_vault.clone(),
+                    token_program: ctx.accounts.token_program.clone(),
+                    pool_state: ctx.accounts.pool_state.clone(),
+                    last_observation_state,
+                    callback_handler: UncheckedAccount::try_from(
+                        ctx.accounts.core_program.to_account_info(),
+                    ),
+                },
+                swap_remaining_accounts,
+                amount_in,
+                0,
+                false,
+            )?;
+            amount_out += result.amount_out;
+        }
+        require!(
+            amount_out >= amount_out_minimum,
             ErrorCode::TooLittleReceived
         );
 
+        if burn_nft {
+            // the position rent belongs to the NFT owner, not to a delegate or operator
+            require!(
+                ctx.accounts.owner_or_delegate.key() == ctx.accounts.nft_account.owner,
+                ErrorCode::NotApproved
+            );
+            let tokenized_position = ctx.accounts.tokenized_position_state.load()?;
+            require!(
+                tokenized_position.liquidity == 0
+                    && tokenized_position.tokens_owed_0 == 0
+                    && tokenized_position.tokens_owed_1 == 0,
+                ErrorCode::PositionNotCleared
+            );
+            drop(tokenized_position);
+            token::burn(
+                CpiContext::new(
+                    ctx.accounts.token_program.to_account_info(),
+                    token::Burn {
+                        mint: ctx.accounts.nft_mint.to_account_info(),
+                        from: ctx.accounts.nft_account.to_account_info(),
+                        authority: ctx.accounts.owner_or_delegate.to_account_info(),
+                    },
+                ),
+                1,
+            )?;
+            // the position has no NFT left, so return its rent
+            ctx.accounts
+                .tokenized_position_state
+                .close(ctx.accounts.owner_or_delegate.to_account_info())?;
+        }
+
+        Ok(amount_out)
+    }
+
+    /// Splits part of the liquidity of a position NFT into a new position NFT with the same
+    /// range. Tokens owed are shared in proportion to liquidity. No tokens move through the
+    /// pool vaults.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - Holds the position to split and the accounts of the new position. The new NFT
+    /// is minted to the owner of the split NFT. An approved operator passes the operator
+    /// approval as the only remaining account
+    /// * `liquidity` - The liquidity to move to the new position
+    ///
+    #[access_control(is_authorized_for_token(&ctx.accounts.owner_or_delegate, &ctx.accounts.nft_account, ctx.remaining_accounts))]
+    pub fn split_tokenized_position<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, SplitTokenizedPosition<'info>>,
+        liquidity: u64,
+    ) -> Result<()> {
+        let core_position = *ctx.accounts.core_position_state.load()?;
+        let mut tokenized_position = ctx.accounts.tokenized_position_state.load_mut()?;
+        tokenized_position.update_tokens_owed(
+            core_position.fee_growth_inside_0_last_x32,
+            core_position.fee_growth_inside_1_last_x32,
+        );
+
+        let mut new_tokenized_position = ctx.accounts.new_tokenized_position_state.load_init()?;
+        new_tokenized_position.bump = *ctx.bumps.get("new_tokenized_position_state").unwrap();
+        new_tokenized_position.mint = ctx.accounts.new_nft_mint.key();
+        tokenized_position.split_into(&mut new_tokenized_position, liquidity)?;
+
+        token::mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::MintTo {
+                    mint: ctx.accounts.new_nft_mint.to_account_info(),
+                    to: ctx.accounts.new_nft_account.to_account_info(),
+                    authority: ctx.accounts.factory_state.to_account_info(),
+                },
+                &[&[&[ctx.accounts.factory_state.load()?.bump]]],
+            ),
+            1,
+        )?;
+
+        emit!(SplitTokenizedPositionEvent {
+            token_id: tokenized_position.mint,
+            new_token_id: new_tokenized_position.mint,
+            liquidity,
+        });
+
+        Ok(())
+    }
+
+    /// Merges a position NFT into another position NFT with the same pool and range. The
+    /// merged NFT is burned and its position account closed to the NFT owner. No tokens move
+    /// through the pool vaults.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - Holds both positions and the NFT to burn. The signer must be the owner or a
+    /// delegate of both NFTs, since the token program only lets them burn. Operators can't merge
+    ///
+    #[access_control(is_authorized_for_token(&ctx.accounts.owner_or_delegate, &ctx.accounts.nft_account, ctx.remaining_accounts))]
+    #[access_control(is_authorized_for_token(&ctx.accounts.owner_or_delegate, &ctx.accounts.source_nft_account, ctx.remaining_accounts))]
+    pub fn merge_tokenized_positions<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, MergeTokenizedPositions<'info>>,
+    ) -> Result<()> {
+        let core_position = *ctx.accounts.core_position_state.load()?;
+        let mut tokenized_position = ctx.accounts.tokenized_position_state.load_mut()?;
+        let mut source_tokenized_position =
+            ctx.accounts.source_tokenized_position_state.load_mut()?;
+        for position in [&mut tokenized_position, &mut source_tokenized_position] {
+            position.update_tokens_owed(
+                core_position.fee_growth_inside_0_last_x32,
+                core_position.fee_growth_inside_1_last_x32,
+            );
+        }
+
+        let liquidity = source_tokenized_position.liquidity;
+        tokenized_position.merge(&mut source_tokenized_position)?;
+
+        token::burn(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                token::Burn {
+                    mint: ctx.accounts.source_nft_mint.to_account_info(),
+                    from: ctx.accounts.source_nft_account.to_account_info(),
+                    authority: ctx.accounts.owner_or_delegate.to_account_info(),
+                },
+            ),
+            1,
+        )?;
+
+        emit!(MergeTokenizedPositionsEvent {
+            token_id: tokenized_position.mint,
+            source_token_id: source_tokenized_position.mint,
+            liquidity,
+        });
+
+        Ok(())
+    }
+
+    /// Values a position NFT as the token amounts of its liquidity and its uncollected fees,
+    /// for integrations such as lending protocols. Read only, the value is returned through
+    /// return data.
+    ///
+    /// Fees are always valued as of the current fee growth of the pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - Holds the tokenized position, its pool and ticks. For a TWAP, the remaining
+    /// accounts hold an older observation and the latest observation of the pool
+    /// * `twap_window` - Value liquidity at the time weighted average price of the pool since
+    /// the older observation, which must be at least this many seconds old. Integrations should
+    /// pick a window over which the pool price is too costly to move. `None` values liquidity
+    /// at the spot price
+    ///
+    pub fn value_tokenized_position<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, ValueTokenizedPosition<'info>>,
+        twap_window: Option<u32>,
+    ) -> Result<PositionValue> {
+        let pool = *ctx.accounts.pool_state.load()?;
+        let tokenized_position = *ctx.accounts.tokenized_position_state.load()?;
+
+        let sqrt_price_x32 = match twap_window {
+            Some(twap_window) => {
+                require!(twap_window > 0, ErrorCode::InvalidTwapWindow);
+                let mut twap_accounts = vec![ctx.accounts.pool_state.to_account_info()];
+                twap_accounts.extend_from_slice(ctx.remaining_accounts);
+                twap_sqrt_price_x32(pool.token_0, pool.token_1, &twap_accounts, twap_window)?
+            }
+            None => pool.sqrt_price_x32,
+        };
+
+        let (fee_growth_inside_0_x32, fee_growth_inside_1_x32) = tick::get_fee_growth_inside(
+            ctx.accounts.tick_lower_state.load()?.deref(),
+            ctx.accounts.tick_upper_state.load()?.deref(),
+            pool.tick,
+            pool.fee_growth_global_0_x32,
+            pool.fee_growth_global_1_x32,
+        );
+
+        tokenized_position.value(
+            sqrt_price_x32,
+            fee_growth_inside_0_x32,
+            fee_growth_inside_1_x32,
+        )
+    }
+
+    /// Swaps `amount_in` of one token for as much as possible of another token,
+    /// across a single pool
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - Accounts required for the swap
+    /// * `deadline` - The time by which the transaction must be included to effect the change
+    /// * `amount_in` - Token amount to be swapped in
+    /// * `amount_out_minimum` - The minimum amount to swap out, which serves as a slippage check
+    /// * `sqrt_price_limit` - The Q32.32 sqrt price √P limit. If zero for one, the price cannot
+    /// be less than this value after the swap.  If one for zero, the price cannot be greater than
+    /// this value after the swap.
+    /// * `partial_fill` - Swap as much as possible with the supplied bitmap and tick accounts
+    /// instead of failing. `amount_out_minimum` still applies to the filled amount.
+    ///
+    #[access_control(check_deadline(deadline))]
+    pub fn exact_input_single<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, ExactInputSingle<'info>>,
+        deadline: i64,
+        amount_in: u64,
+        amount_out_minimum: u64,
+        sqrt_price_limit_x32: u64,
+        partial_fill: bool,
+    ) -> Result<ExactInputResult> {
+        let result = exact_input_internal(
+            &mut SwapContext {
+                signer: ctx.accounts.signer.clone(),
+                factory_state: ctx.accounts.factory_state.clone(),
+                input_token_account: ctx.accounts.input_token_account.clone(),
+                output_token_account: ctx.accounts.output_token_account.clone(),
+                input_vault: ctx.accounts.input_vault.clone(),
+                output_vault: ctx.accounts.output_vault.clone(),
+                token_program: ctx.accounts.token_program.clone(),
+                pool_state: ctx.accounts.pool_state.clone(),
+                last_observation_state: ctx.accounts.last_observation_state.clone(),
+                // next_observation_state: ctx.accounts.next_observation_state.clone(),
+                callback_handler: UncheckedAccount::try_from(
+                    ctx.accounts.core_program.to_account_info(),
+                ),
+            },
+            ctx.remaining_accounts,
+            amount_in,
+            sqrt_price_limit_x32,
+            partial_fill,
+        )?;
+        require!(
+            result.amount_out >= amount_out_minimum,
+            ErrorCode::TooLittleReceived
+        );
+        Ok(result)
+    }
+
+    /// Swaps `amount_in` of one token for as much as possible of another token,
+    /// across the path provided
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - Accounts for token transfer and swap route
+    /// * `deadline` - Swap should if fail if past deadline
+    /// * `amount_in` - Token amount to be swapped in
+    /// * `amount_out_minimum` - Panic if output amount is below minimum amount. For slippage.
+    /// * `hops` - Additional observation, bitmap and tick accounts, price limit and
+    /// minimum output per pool. A price limit on the first hop can leave part of the input
+    /// unswapped, while every later hop must swap its whole input.
+    ///
+    #[access_control(check_deadline(deadline))]
+    pub fn exact_input<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, ExactInput<'info>>,
+        deadline: i64,
+        amount_in: u64,
+        amount_out_minimum: u64,
+        hops: Vec<SwapHop>,
+    ) -> Result<ExactInputResult> {
+        require!(!hops.is_empty(), ErrorCode::EmptyRoute);
+        let mut remaining_accounts = ctx.remaining_accounts.iter();
+
+        let mut result = ExactInputResult {
+            amount_in,
+            amount_out: amount_in,
+            sqrt_price_x32: 0,
+            tick: 0,
+        };
+        let mut input_token_account = ctx.accounts.input_token_account.clone();
+        for (i, params) in hops.iter().enumerate() {
+            let pool_state = UncheckedAccount::try_from(
+                remaining_accounts
+                    .next()
+                    .ok_or(ErrorCode::InvalidSwapAccount)?
+                    .clone(),
+            );
+            let output_token_account = UncheckedAccount::try_from(
+                remaining_accounts
+                    .next()
+                    .ok_or(ErrorCode::InvalidSwapAccount)?
+                    .clone(),
+            );
+            let input_vault = Box::new(Account::<TokenAccount>::try_from(
+                remaining_accounts
+                    .next()
+                    .ok_or(ErrorCode::InvalidSwapAccount)?,
+            )?);
+            let output_vault = Box::new(Account::<TokenAccount>::try_from(
+                remaining_accounts
+                    .next()
+                    .ok_or(ErrorCode::InvalidSwapAccount)?,
+            )?);
+            let last_observation_state = UncheckedAccount::try_from(
+                remaining_accounts
+                    .next()
+                    .ok_or(ErrorCode::InvalidSwapAccount)?
+                    .clone(),
+            );
+
+            // the output of each swap is the input of the next one
+            let hop = exact_input_internal(
+                &mut SwapContext {
+                    signer: ctx.accounts.signer.clone(),
+                    factory_state: ctx.accounts.factory_state.clone(),
+                    input_token_account: input_token_account.clone(),
+                    pool_state,
+                    output_token_account: output_token_account.clone(),
+                    input_vault,
+                    output_vault,
+                    last_observation_state,
+                    token_program: ctx.accounts.token_program.clone(),
+                    callback_handler: UncheckedAccount::try_from(
+                        ctx.accounts.core_program.to_account_info(),
+                    ),
+                },
+                remaining_accounts.as_slice(),
+                result.amount_out,
+                params.sqrt_price_limit_x32,
+                false,
+            )?;
+            require!(
+                hop.amount_out >= params.amount_out_minimum,
+                ErrorCode::TooLittleReceived
+            );
+            if i == 0 {
+                // a price limit on the first hop can leave part of the input unswapped
+                result.amount_in = hop.amount_in;
+            } else {
+                // unswapped intermediate tokens would be left in the user's account
+                require!(
+                    hop.amount_in == result.amount_out,
+                    ErrorCode::PartialIntermediateHop
+                );
+            }
+            result.amount_out = hop.amount_out;
+            result.sqrt_price_x32 = hop.sqrt_price_x32;
+            result.tick = hop.tick;
+
+            if i < hops.len() - 1 {
+                // reach accounts needed for the next swap
+                for _j in 0..params.additional_accounts {
+                    remaining_accounts.next();
+                }
+                // output token account is the new input
+                input_token_account = output_token_account;
+            }
+        }
+        require!(
+            result.amount_out >= amount_out_minimum,
+            ErrorCode::TooLittleReceived
+        );
+
+        Ok(result)
+    }
+
+    /// Swaps until the pool reaches a target price or the input cap is consumed, whichever
+    /// comes first. Only the consumed input is charged.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - Accounts required for the swap
+    /// * `deadline` - The time by which the transaction must be included to effect the change
+    /// * `target_sqrt_price_x32` - The Q32.32 sqrt price to move the pool to. Must be below the
+    /// current price for a zero for one swap, and above it for a one for zero swap.
+    /// * `max_amount_in` - The maximum amount of the input token to swap
+    ///
+    #[access_control(check_deadline(deadline))]
+    pub fn swap_to_price<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, ExactInputSingle<'info>>,
+        deadline: i64,
+        target_sqrt_price_x32: u64,
+        max_amount_in: u64,
+    ) -> Result<ExactInputResult> {
+        require!(target_sqrt_price_x32 != 0, ErrorCode::SPL);
+        exact_input_internal(
+            &mut SwapContext {
+                signer: ctx.accounts.signer.clone(),
+                factory_state: ctx.accounts.factory_state.clone(),
+                input_token_account: ctx.accounts.input_token_account.clone(),
+                output_token_account: ctx.accounts.output_token_account.clone(),
+                input_vault: ctx.accounts.input_vault.clone(),
+                output_vault: ctx.accounts.output_vault.clone(),
+                token_program: ctx.accounts.token_program.clone(),
+                pool_state: ctx.accounts.pool_state.clone(),
+                last_observation_state: ctx.accounts.last_observation_state.clone(),
+                callback_handler: UncheckedAccount::try_from(
+                    ctx.accounts.core_program.to_account_info(),
+                ),
+            },
+            ctx.remaining_accounts,
+            max_amount_in,
+            target_sqrt_price_x32,
+            false,
+        )
+    }
+
+    /// Swaps `amount_in` of one token for as much as possible of another token,
+    /// split across several pools for the same token pair
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - Accounts for token transfer. Each leg reads the pool, input vault,
+    /// output vault, last observation and its additional accounts from remaining accounts
+    /// * `deadline` - Swap should if fail if past deadline
+    /// * `amount_in` - Token amount to be swapped in
+    /// * `amount_out_minimum` - Panic if the total output amount is below minimum amount.
+    /// For slippage.
+    /// * `legs` - Share of the input and number of additional accounts per pool. Shares must
+    /// be positive and add up to 10000 basis points. The last leg swaps the rounding remainder.
+    ///
+    #[access_control(check_deadline(deadline))]
+    pub fn exact_input_split<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, ExactInputSplit<'info>>,
+        deadline: i64,
+        amount_in: u64,
+        amount_out_minimum: u64,
+        legs: Vec<SplitLeg>,
+    ) -> Result<Vec<ExactInputResult>> {
+        require!(
+            legs.iter().map(|leg| leg.share_bps as u64).sum::<u64>() == 10_000,
+            ErrorCode::InvalidSplit
+        );
+        require!(
+            legs.iter().all(|leg| leg.share_bps > 0),
+            ErrorCode::EmptySplitLeg
+        );
+        let mut remaining_accounts = ctx.remaining_accounts.iter();
+
+        let mut results = Vec::with_capacity(legs.len());
+        let mut pools = Vec::with_capacity(legs.len());
+        let mut amount_in_remaining = amount_in;
+        for (i, leg) in legs.iter().enumerate() {
+            let amount_in_leg = if i == legs.len() - 1 {
+                amount_in_remaining
+            } else {
+                (amount_in as u128 * leg.share_bps as u128 / 10_000) as u64
+            };
+            // a small input can round a leg down to nothing
+            require!(amount_in_leg > 0, ErrorCode::EmptySplitLeg);
+            amount_in_remaining -= amount_in_leg;
+
+            let pool_state = UncheckedAccount::try_from(
+                remaining_accounts
+                    .next()
+                    .ok_or(ErrorCode::InvalidSwapAccount)?
+                    .clone(),
+            );
+            pools.push(pool_state.key());
+            let input_vault = Box::new(Account::<TokenAccount>::try_from(
+                remaining_accounts
+                    .next()
+                    .ok_or(ErrorCode::InvalidSwapAccount)?,
+            )?);
+            let output_vault = Box::new(Account::<TokenAccount>::try_from(
+                remaining_accounts
+                    .next()
+                    .ok_or(ErrorCode::InvalidSwapAccount)?,
+            )?);
+            let last_observation_state = UncheckedAccount::try_from(
+                remaining_accounts
+                    .next()
+                    .ok_or(ErrorCode::InvalidSwapAccount)?
+                    .clone(),
+            );
+
+            let leg_accounts = remaining_accounts.as_slice();
+            let additional_accounts = leg.additional_accounts as usize;
+            require!(
+                leg_accounts.len() >= additional_accounts,
+                ErrorCode::InvalidSwapAccount
+            );
+            results.push(exact_input_internal(
+                &mut SwapContext {
+                    signer: ctx.accounts.signer.clone(),
+                    factory_state: ctx.accounts.factory_state.clone(),
+                    input_token_account: ctx.accounts.input_token_account.clone(),
+                    pool_state,
+                    output_token_account: ctx.accounts.output_token_account.clone(),
+                    input_vault,
+                    output_vault,
+                    last_observation_state,
+                    token_program: ctx.accounts.token_program.clone(),
+                    callback_handler: UncheckedAccount::try_from(
+                        ctx.accounts.core_program.to_account_info(),
+                    ),
+                },
+                &leg_accounts[..additional_accounts],
+                amount_in_leg,
+                0,
+                false,
+            )?);
+
+            // reach accounts needed for the next leg
+            for _j in 0..additional_accounts {
+                remaining_accounts.next();
+            }
+        }
+
+        let amount_out = results.iter().map(|result| result.amount_out).sum::<u64>();
+        require!(
+            amount_out >= amount_out_minimum,
+            ErrorCode::TooLittleReceived
+        );
+
+        emit!(SplitSwapEvent {
+            signer: ctx.accounts.signer.key(),
+            pools,
+            amounts_in: results.iter().map(|result| result.amount_in).collect(),
+            amounts_out: results.iter().map(|result| result.amount_out).collect(),
+            amount_in: results.iter().map(|result| result.amount_in).sum(),
+            amount_out,
+        });
+
+        Ok(results)
+    }
+
+    //  /// Swaps as little as possible of one token for `amount_out` of another token,
+    // /// across a single pool
+    // ///
+    // /// # Arguments
+    // ///
+    // /// * `ctx` - Token and pool accounts for swap
+    // /// * `zero_for_one` - Direction of swap. Swap token_0 for token_1 if true
+    // /// * `deadline` - Swap should if fail if past deadline
+    // /// * `amount_out` - Token amount to be swapped out
+    // /// * `amount_in_maximum` - For slippage. Panic if required input exceeds max limit.
+    // /// * `sqrt_price_limit` - Limit price √P for slippage
+    // ///
+    // pub fn exact_output_single(
+    //     ctx: Context<ExactInputSingle>,
+    //     zero_for_one: bool,
+    //     deadline: u64,
+    //     amount_out: u64,
+    //     amount_in_maximum: u64,
+    //     sqrt_price_limit_x32: u64,
+    // ) -> Result<()> {
+    //     todo!()
+    // }
+
+    // /// Swaps as little as possible of one token for `amount_out` of another
+    // /// along the specified path (reversed)
+    // ///
+    // /// # Arguments
+    // ///
+    // /// * `ctx` - Accounts for token transfer and swap route
+    // /// * `deadline` - Swap should if fail if past deadline
+    // /// * `amount_out` - Token amount to be swapped out
+    // /// * `amount_in_maximum` - For slippage. Panic if required input exceeds max limit.
+    // ///
+    // pub fn exact_output(
+    //     ctx: Context<ExactInput>,
+    //     deadline: u64,
+    //     amount_out: u64,
+    //     amount_out_maximum: u64,
+    // ) -> Result<()> {
+    //     todo!()
+    // }
+
+    // ---------------------------------------------------------------------
+    // Gauge voting
+    // Escrows of the governance token locker vote every epoch on how liquidity
+    // incentive emissions are split across pools
+
+    /// Creates the gauge controller for a locker. Must be called by the current owner.
+    /// Epoch 0 starts now.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx`- Checks whether protocol owner has signed and initializes the controller
+    /// * `epoch_duration` - Duration of an epoch in seconds
+    /// * `emissions_per_epoch` - The emissions split across gauges every epoch
+    ///
+    pub fn create_gauge_controller(
+        ctx: Context<CreateGaugeController>,
+        epoch_duration: u32,
+        emissions_per_epoch: u64,
+    ) -> Result<()> {
+        require!(epoch_duration > 0, ErrorCode::InvalidEpoch);
+        let mut controller = ctx.accounts.gauge_controller_state.load_init()?;
+        controller.bump = *ctx.bumps.get("gauge_controller_state").unwrap();
+        controller.locker = ctx.accounts.locker.key();
+        controller.start_time = Clock::get()?.unix_timestamp;
+        controller.epoch_duration = epoch_duration;
+        controller.emissions_per_epoch = emissions_per_epoch;
+        Ok(())
+    }
+
+    /// Sets the emissions split across gauges every epoch. Must be called by the current
+    /// owner. Applies to gauges synced afterwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx`- Checks whether protocol owner has signed
+    /// * `emissions_per_epoch` - The new emissions per epoch
+    ///
+    pub fn set_gauge_emissions(
+        ctx: Context<SetGaugeEmissions>,
+        emissions_per_epoch: u64,
+    ) -> Result<()> {
+        let mut controller = ctx.accounts.gauge_controller_state.load_mut()?;
+        controller.emissions_per_epoch = emissions_per_epoch;
+        Ok(())
+    }
+
+    /// Creates a gauge to direct incentive emissions to a pool. Must be called by the
+    /// current owner.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx`- Checks whether protocol owner has signed and initializes the gauge
+    ///
+    pub fn create_gauge(ctx: Context<CreateGauge>) -> Result<()> {
+        let mut gauge = ctx.accounts.gauge_state.load_init()?;
+        gauge.bump = *ctx.bumps.get("gauge_state").unwrap();
+        gauge.pool = ctx.accounts.pool_state.key();
+        Ok(())
+    }
+
+    /// Initializes an empty program account to total the votes of an epoch
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - Contains accounts to initialize an empty epoch account
+    /// * `epoch` - The epoch for which the account is created
+    ///
+    pub fn init_epoch_account(ctx: Context<InitEpochAccount>, epoch: u32) -> Result<()> {
+        let mut epoch_state = ctx.accounts.epoch_state.load_init()?;
+        epoch_state.bump = *ctx.bumps.get("epoch_state").unwrap();
+        epoch_state.epoch = epoch;
+        Ok(())
+    }
+
+    /// Initializes an empty program account to total the votes of a gauge in an epoch
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - Contains accounts to initialize an empty epoch gauge account
+    /// * `epoch` - The epoch for which the account is created
+    ///
+    pub fn init_epoch_gauge_account(ctx: Context<InitEpochGaugeAccount>, epoch: u32) -> Result<()> {
+        let mut epoch_gauge = ctx.accounts.epoch_gauge_state.load_init()?;
+        epoch_gauge.bump = *ctx.bumps.get("epoch_gauge_state").unwrap();
+        epoch_gauge.gauge = ctx.accounts.gauge_state.key();
+        epoch_gauge.epoch = epoch;
+        Ok(())
+    }
+
+    /// Initializes an empty program account to track the vote weight allocated by an escrow
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - Contains accounts to initialize an empty gauge voter account
+    ///
+    pub fn init_gauge_voter_account(ctx: Context<InitGaugeVoterAccount>) -> Result<()> {
+        let mut gauge_voter = ctx.accounts.gauge_voter_state.load_init()?;
+        gauge_voter.bump = *ctx.bumps.get("gauge_voter_state").unwrap();
+        gauge_voter.escrow = ctx.accounts.escrow.key();
+        Ok(())
+    }
+
+    /// Votes a share of an escrow's voting power for a gauge in the next epoch. Voting power
+    /// is read at the time of the vote. An escrow can split its power across gauges, up to
+    /// 10000 basis points per epoch. Voting again for a gauge before the epoch starts
+    /// replaces the previous vote, and a zero weight revokes it.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - Holds the escrow, its voter account, and the gauge, epoch and vote accounts
+    /// * `epoch` - The epoch voted for, which must be the next epoch. The escrow must stay
+    /// locked until the epoch ends
+    /// * `weight_bps` - The share of voting power to cast, in basis points
+    ///
+    pub fn vote_gauge(ctx: Context<VoteGauge>, epoch: u32, weight_bps: u16) -> Result<()> {
+        let controller = *ctx.accounts.gauge_controller_state.load()?;
+        let time = Clock::get()?.unix_timestamp;
+        require!(
+            epoch == controller.epoch_at(time) + 1,
+            ErrorCode::InvalidEpoch
+        );
+        require!(
+            ctx.accounts.escrow.escrow_ends_at >= controller.epoch_end(epoch),
+            ErrorCode::EscrowEndsBeforeEpoch
+        );
+
+        let power = ctx
+            .accounts
+            .escrow
+            .voting_power_at_time(&ctx.accounts.locker.params, time);
+        require!(power > 0, ErrorCode::NoVotingPower);
+
+        // An account created by init_if_needed has no discriminator until the instruction exits
+        let is_new = ctx
+            .accounts
+            .gauge_vote_state
+            .to_account_info()
+            .try_borrow_data()?[..8]
+            == [0; 8];
+        let mut vote = if is_new {
+            let mut vote = ctx.accounts.gauge_vote_state.load_init()?;
+            vote.bump = *ctx.bumps.get("gauge_vote_state").unwrap();
+            vote.escrow = ctx.accounts.escrow.key();
+            vote.gauge = ctx.accounts.gauge_state.key();
+            vote.epoch = epoch;
+            vote
+        } else {
+            ctx.accounts.gauge_vote_state.load_mut()?
+        };
+
+        let mut gauge_voter = ctx.accounts.gauge_voter_state.load_mut()?;
+        gauge_voter.allocate(epoch, vote.weight_bps, weight_bps)?;
+
+        let weight = power
+            .mul_div_floor(weight_bps as u64, MAX_VOTE_WEIGHT_BPS as u64)
+            .unwrap();
+        let mut epoch_state = ctx.accounts.epoch_state.load_mut()?;
+        epoch_state.total_weight = epoch_state.total_weight - vote.weight + weight;
+        let mut epoch_gauge = ctx.accounts.epoch_gauge_state.load_mut()?;
+        epoch_gauge.weight = epoch_gauge.weight - vote.weight + weight;
+        vote.weight_bps = weight_bps;
+        vote.weight = weight;
+
+        emit!(GaugeVoteEvent {
+            escrow: ctx.accounts.escrow.key(),
+            pool: ctx.accounts.gauge_state.load()?.pool,
+            epoch,
+            weight,
+        });
+
         Ok(())
     }
 
-    //  /// Swaps as little as possible of one token for `amount_out` of another token,
-    // /// across a single pool
-    // ///
-    // /// # Arguments
-    // ///
-    // /// * `ctx` - Token and pool accounts for swap
-    // /// * `zero_for_one` - Direction of swap. Swap token_0 for token_1 if true
-    // /// * `deadline` - Swap should if fail if past deadline
-    // /// * `amount_out` - Token amount to be swapped out
-    // /// * `amount_in_maximum` - For slippage. Panic if required input exceeds max limit.
-    // /// * `sqrt_price_limit` - Limit price √P for slippage
-    // ///
-    // pub fn exact_output_single(
-    //     ctx: Context<ExactInputSingle>,
-    //     zero_for_one: bool,
-    //     deadline: u64,
-    //     amount_out: u64,
-    //     amount_in_maximum: u64,
-    //     sqrt_price_limit_x32: u64,
-    // ) -> Result<()> {
-    //     todo!()
-    // }
+    /// Sets the emissions of a gauge for the current epoch from its share of the epoch
+    /// votes. Permissionless to call.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - Holds the gauge and its epoch accounts
+    /// * `epoch` - The current epoch
+    ///
+    pub fn sync_gauge(ctx: Context<SyncGauge>, epoch: u32) -> Result<()> {
+        let controller = *ctx.accounts.gauge_controller_state.load()?;
+        require!(
+            epoch == controller.epoch_at(Clock::get()?.unix_timestamp),
+            ErrorCode::InvalidEpoch
+        );
 
-    // /// Swaps as little as possible of one token for `amount_out` of another
-    // /// along the specified path (reversed)
-    // ///
-    // /// # Arguments
-    // ///
-    // /// * `ctx` - Accounts for token transfer and swap route
-    // /// * `deadline` - Swap should if fail if past deadline
-    // /// * `amount_out` - Token amount to be swapped out
-    // /// * `amount_in_maximum` - For slippage. Panic if required input exceeds max limit.
-    // ///
-    // pub fn exact_output(
-    //     ctx: Context<ExactInput>,
-    //     deadline: u64,
-    //     amount_out: u64,
-    //     amount_out_maximum: u64,
-    // ) -> Result<()> {
-    //     todo!()
-    // }
+        let mut gauge = ctx.accounts.gauge_state.load_mut()?;
+        gauge.epoch = epoch;
+        gauge.emissions = gauge_emissions(
+            controller.emissions_per_epoch,
+            ctx.accounts.epoch_gauge_state.load()?.weight,
+            ctx.accounts.epoch_state.load()?.total_weight,
+        );
+
+        emit!(GaugeSyncedEvent {
+            pool: gauge.pool,
+            epoch,
+            emissions: gauge.emissions,
+        });
+
+        Ok(())
+    }
 }
 
-/// Performs a single exact input swap
+/// Performs a single exact input swap, returning the amounts swapped and the final pool price
 pub fn exact_input_internal<'info>(
     accounts: &mut SwapContext<'info>,
     remaining_accounts: &[AccountInfo<'info>],
     amount_in: u64,
     sqrt_price_limit_x32: u64,
-) -> Result<u64> {
+    partial_fill: bool,
+) -> Result<ExactInputResult> {
     let pool_state = AccountLoader::<PoolState>::try_from(&accounts.pool_state)?;
     let zero_for_one = accounts.input_vault.mint == pool_state.load()?.token_0;
 
-    let balance_before = accounts.input_vault.amount;
-    swap(
+    let result = swap(
         Context::new(&ID, accounts, remaining_accounts, BTreeMap::default()),
         i64::try_from(amount_in).unwrap(),
         if sqrt_price_limit_x32 == 0 {
@@ -2106,10 +4047,232 @@ pub fn exact_input_internal<'info>(
         } else {
             sqrt_price_limit_x32
         },
+        partial_fill,
+    )?;
+
+    let (amount_in, amount_out) = if zero_for_one {
+        (result.amount_0, -result.amount_1)
+    } else {
+        (result.amount_1, -result.amount_0)
+    };
+    Ok(ExactInputResult {
+        amount_in: amount_in as u64,
+        amount_out: amount_out as u64,
+        sqrt_price_x32: result.sqrt_price_x32,
+        tick: result.tick,
+    })
+}
+
+/// Creates a program owned account at a PDA, paid for by the payer
+///
+/// `create_account` fails for an address holding lamports, so anyone could block creation by
+/// sending lamports to the PDA. A funded PDA is topped up to rent exemption, then allocated
+/// and assigned instead.
+///
+/// # Arguments
+///
+/// * `payer` - Pays rent for the new account
+/// * `account` - The PDA to create
+/// * `system_program` - To create the account
+/// * `space` - Space to allocate, including the account discriminator
+/// * `seeds` - Seeds of the PDA, including the bump
+///
+pub fn create_program_account<'info>(
+    payer: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    space: usize,
+    seeds: &[&[u8]],
+) -> Result<()> {
+    let lamports = Rent::get()?.minimum_balance(space);
+    if account.lamports() == 0 {
+        let ix = create_account(payer.key, account.key, lamports, space as u64, &ID);
+        solana_program::program::invoke_signed(
+            &ix,
+            &[payer.clone(), account.clone(), system_program.clone()],
+            &[seeds],
+        )?;
+        return Ok(());
+    }
+
+    let top_up = lamports.saturating_sub(account.lamports());
+    if top_up > 0 {
+        solana_program::program::invoke(
+            &transfer(payer.key, account.key, top_up),
+            &[payer.clone(), account.clone(), system_program.clone()],
+        )?;
+    }
+    solana_program::program::invoke_signed(
+        &allocate(account.key, space as u64),
+        &[account.clone(), system_program.clone()],
+        &[seeds],
+    )?;
+    solana_program::program::invoke_signed(
+        &assign(account.key, &ID),
+        &[account.clone(), system_program.clone()],
+        &[seeds],
+    )?;
+    Ok(())
+}
+
+/// Adds a pool to the registry of its token pair, creating the registry for the first pool
+/// of the pair. The pool is not registered if the registry is full, so that the registry
+/// never blocks pool creation.
+///
+/// # Arguments
+///
+/// * `payer` - Pays rent for a new registry
+/// * `pair_state` - The pair registry
+/// * `system_program` - To create the registry
+/// * `bump` - Bump of the pair registry
+/// * `token_0` - The first token of the pair
+/// * `token_1` - The second token of the pair
+/// * `fee` - Fee amount of the pool
+///
+pub fn register_pool<'info>(
+    payer: &AccountInfo<'info>,
+    pair_state: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    bump: u8,
+    token_0: Pubkey,
+    token_1: Pubkey,
+    fee: u32,
+) -> Result<()> {
+    if !pair_state.data_is_empty() {
+        let pair_state_loader = AccountLoader::<PairState>::try_from(pair_state)?;
+        let mut pair = pair_state_loader.load_mut()?;
+        require!(
+            !pair.registered_fees().contains(&fee),
+            ErrorCode::PoolAlreadyRegistered
+        );
+        if pair.is_full() {
+            msg!("pair registry is full, pool not registered");
+            return Ok(());
+        }
+        return pair.add_fee(fee);
+    }
+
+    create_program_account(
+        payer,
+        pair_state,
+        system_program,
+        8 + size_of::<PairState>(),
+        &[
+            PAIR_SEED.as_bytes(),
+            token_0.as_ref(),
+            token_1.as_ref(),
+            &[bump],
+        ],
+    )?;
+
+    let pair_state_loader = AccountLoader::<PairState>::try_from_unchecked(&ID, pair_state)?;
+    let mut pair = pair_state_loader.load_init()?;
+    pair.bump = bump;
+    pair.token_0 = token_0;
+    pair.token_1 = token_1;
+    pair.add_fee(fee)?;
+
+    drop(pair);
+    pair_state_loader.exit(&ID)
+}
+
+/// Grows a program account created with an older layout of `T` to the current layout, paid
+/// for by the payer. Fields appended to the layout are zeroed.
+///
+/// # Arguments
+///
+/// * `payer` - Tops up rent of the grown account
+/// * `account` - The account to grow. Must be owned by the program and hold a `T`
+/// * `system_program` - To transfer rent
+///
+pub fn realloc_program_account<'info, T: ZeroCopy + Owner>(
+    payer: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<()> {
+    // checks owner and discriminator, but not the length
+    AccountLoader::<T>::try_from(account)?;
+    let space = 8 + size_of::<T>();
+    require!(
+        account.data_len() < space,
+        ErrorCode::AccountAlreadyMigrated
+    );
+
+    let lamports = Rent::get()?
+        .minimum_balance(space)
+        .saturating_sub(account.lamports());
+    if lamports > 0 {
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                system_program.clone(),
+                anchor_lang::system_program::Transfer {
+                    from: payer.clone(),
+                    to: account.clone(),
+                },
+            ),
+            lamports,
+        )?;
+    }
+    account.realloc(space, true)?;
+    Ok(())
+}
+
+/// Returns the time weighted average sqrt price of a reference pool with the same token pair
+///
+/// # Arguments
+///
+/// * `token_0` - The first token of the pair
+/// * `token_1` - The second token of the pair
+/// * `accounts` - The reference pool, an older observation and the latest observation
+/// of the reference pool. The average is taken from the older observation till now.
+/// * `min_window` - The minimum age in seconds of the older observation
+///
+pub fn twap_sqrt_price_x32<'info>(
+    token_0: Pubkey,
+    token_1: Pubkey,
+    accounts: &[AccountInfo<'info>],
+    min_window: u32,
+) -> Result<u64> {
+    require!(accounts.len() >= 3, ErrorCode::MissingTwapAccounts);
+    let reference_pool_state = AccountLoader::<PoolState>::try_from(&accounts[0])?;
+    let reference_pool = *reference_pool_state.load()?;
+    require!(
+        reference_pool.token_0 == token_0 && reference_pool.token_1 == token_1,
+        ErrorCode::InvalidReferencePool
+    );
+
+    let older_observation_state = AccountLoader::<ObservationState>::try_from(&accounts[1])?;
+    let older_observation = *older_observation_state.load()?;
+    reference_pool.validate_observation_address_at_index(
+        &older_observation_state.key(),
+        older_observation.bump,
+        older_observation.index,
+    )?;
+
+    let latest_observation_state = AccountLoader::<ObservationState>::try_from(&accounts[2])?;
+    let latest_observation = *latest_observation_state.load()?;
+    reference_pool.validate_observation_address(
+        &latest_observation_state.key(),
+        latest_observation.bump,
+        false,
     )?;
 
-    accounts.input_vault.reload()?;
-    Ok(accounts.input_vault.amount - balance_before)
+    let time = oracle::_block_timestamp();
+    require!(
+        older_observation.initialized
+            && older_observation.block_timestamp < time
+            && time - older_observation.block_timestamp >= min_window,
+        ErrorCode::InvalidTwapObservation
+    );
+    let (tick_cumulative, _) =
+        latest_observation.observe_latest(time, reference_pool.tick, reference_pool.liquidity);
+    let tick = oracle::time_weighted_average_tick(
+        older_observation.tick_cumulative,
+        tick_cumulative,
+        time - older_observation.block_timestamp,
+    );
+
+    tick_math::get_sqrt_ratio_at_tick(tick)
 }
 
 /// Common checks for a valid tick input.
@@ -2176,7 +4339,7 @@ pub fn _modify_position<'info>(
 
     _update_position(
         liquidity_delta,
-        pool_state.deref(),
+        pool_state,
         latest_observation.deref(),
         position_state,
         tick_lower_state,
@@ -2210,8 +4373,11 @@ pub fn _modify_position<'info>(
 
             let next_observation_state;
             let mut new_observation = if partition_current_timestamp > partition_last_timestamp {
-                next_observation_state =
-                    AccountLoader::<ObservationState>::try_from(&remaining_accounts[0])?;
+                next_observation_state = AccountLoader::<ObservationState>::try_from(
+                    remaining_accounts
+                        .first()
+                        .ok_or(ErrorCode::MissingObservationAccount)?,
+                )?;
                 let next_observation = next_observation_state.load_mut()?;
                 pool_state.validate_observation_address(
                     &next_observation_state.key(),
@@ -2278,7 +4444,7 @@ pub fn _modify_position<'info>(
 ///
 pub fn _update_position<'info>(
     liquidity_delta: i64,
-    pool_state: &PoolState,
+    pool_state: &mut PoolState,
     last_observation_state: &ObservationState,
     position_state: &AccountLoader<'info, PositionState>,
     tick_lower_state: &AccountLoader<'info, TickState>,
@@ -2337,6 +4503,16 @@ pub fn _update_position<'info>(
                 bitmap_upper.load_mut()?.flip_bit(bit_pos);
             }
         }
+
+        // migrated pools don't know how many ticks were initialized before the migration
+        if pool_state.tick_count_tracked {
+            let flipped = flipped_lower as u32 + flipped_upper as u32;
+            pool_state.initialized_tick_count = if liquidity_delta > 0 {
+                pool_state.initialized_tick_count + flipped
+            } else {
+                pool_state.initialized_tick_count - flipped
+            };
+        }
     }
     // Update fees accrued to the position
     let (fee_growth_inside_0_x32, fee_growth_inside_1_x32) = tick::get_fee_growth_inside(
@@ -2365,6 +4541,201 @@ pub fn _update_position<'info>(
     Ok(())
 }
 
+/// Adds liquidity for the given pool/recipient/tickLower/tickUpper position. The tick,
+/// bitmap and position accounts must exist.
+///
+/// # Arguments
+///
+/// * `ctx` - Holds the recipient's address and program accounts for
+/// pool, position and ticks.
+/// * `amount` - The amount of liquidity to mint
+///
+pub fn mint_liquidity<'a, 'b, 'c, 'info>(
+    ctx: Context<'a, 'b, 'c, 'info, MintContext<'info>>,
+    amount: u64,
+) -> Result<TokenAmounts> {
+    let mut pool = ctx.accounts.pool_state.load_mut()?;
+
+    assert!(
+        ctx.accounts.vault_0.key()
+            == get_associated_token_address(&ctx.accounts.pool_state.key(), &pool.token_0)
+    );
+    assert!(
+        ctx.accounts.vault_1.key()
+            == get_associated_token_address(&ctx.accounts.pool_state.key(), &pool.token_1)
+    );
+    let tick_lower = *ctx.accounts.tick_lower_state.load()?.deref();
+    pool.validate_tick_address(
+        &ctx.accounts.tick_lower_state.key(),
+        tick_lower.bump,
+        tick_lower.tick,
+    )?;
+
+    let tick_upper = *ctx.accounts.tick_upper_state.load()?.deref();
+    pool.validate_tick_address(
+        &ctx.accounts.tick_upper_state.key(),
+        tick_upper.bump,
+        tick_upper.tick,
+    )?;
+
+    let bitmap_lower_state = AccountLoader::<TickBitmapState>::try_from(
+        &ctx.accounts.bitmap_lower_state.to_account_info(),
+    )?;
+    pool.validate_bitmap_address(
+        &ctx.accounts.bitmap_lower_state.key(),
+        bitmap_lower_state.load()?.bump,
+        tick_bitmap::position(tick_lower.tick / pool.tick_spacing as i32).word_pos,
+    )?;
+    let bitmap_upper_state = AccountLoader::<TickBitmapState>::try_from(
+        &ctx.accounts.bitmap_upper_state.to_account_info(),
+    )?;
+    pool.validate_bitmap_address(
+        &ctx.accounts.bitmap_upper_state.key(),
+        bitmap_upper_state.load()?.bump,
+        tick_bitmap::position(tick_upper.tick / pool.tick_spacing as i32).word_pos,
+    )?;
+
+    let position_state =
+        AccountLoader::<PositionState>::try_from(&ctx.accounts.position_state.to_account_info())?;
+    pool.validate_position_address(
+        &ctx.accounts.position_state.key(),
+        position_state.load()?.bump,
+        &ctx.accounts.recipient.key(),
+        tick_lower.tick,
+        tick_upper.tick,
+    )?;
+
+    let last_observation_state = AccountLoader::<ObservationState>::try_from(
+        &ctx.accounts.last_observation_state.to_account_info(),
+    )?;
+    pool.validate_observation_address(
+        &last_observation_state.key(),
+        last_observation_state.load()?.bump,
+        false,
+    )?;
+
+    require!(pool.unlocked, ErrorCode::LOK);
+    pool.unlocked = false;
+
+    assert!(amount > 0);
+
+    let (hook_program, remaining_accounts) =
+        if pool.has_hook(hook::BEFORE_MODIFY_POSITION_FLAG | hook::AFTER_MODIFY_POSITION_FLAG) {
+            let (hook_program, remaining_accounts) =
+                hook::split_hook_program(pool.hook_program, ctx.remaining_accounts)?;
+            (Some(hook_program), remaining_accounts)
+        } else {
+            (None, ctx.remaining_accounts)
+        };
+    let hook_accounts = [
+        ctx.accounts.minter.to_account_info(),
+        ctx.accounts.recipient.to_account_info(),
+        ctx.accounts.pool_state.to_account_info(),
+    ];
+
+    if let Some(hook_program) = hook_program {
+        if pool.has_hook(hook::BEFORE_MODIFY_POSITION_FLAG) {
+            drop(pool);
+            hook::invoke_hook(
+                hook_program,
+                &hook::BeforeModifyPositionHook {
+                    tick_lower: tick_lower.tick,
+                    tick_upper: tick_upper.tick,
+                    liquidity_delta: i64::try_from(amount).unwrap(),
+                },
+                &hook_accounts,
+            )?;
+            pool = ctx.accounts.pool_state.load_mut()?;
+        }
+    }
+
+    let (amount_0_int, amount_1_int) = _modify_position(
+        i64::try_from(amount).unwrap(),
+        pool.deref_mut(),
+        &position_state,
+        &ctx.accounts.tick_lower_state,
+        &ctx.accounts.tick_upper_state,
+        &bitmap_lower_state,
+        &bitmap_upper_state,
+        &last_observation_state,
+        remaining_accounts,
+    )?;
+
+    let amount_0 = amount_0_int as u64;
+    let amount_1 = amount_1_int as u64;
+    let call_after_hook = pool.has_hook(hook::AFTER_MODIFY_POSITION_FLAG);
+
+    let balance_0_before = if amount_0 > 0 {
+        ctx.accounts.vault_0.amount
+    } else {
+        0
+    };
+    let balance_1_before = if amount_1 > 0 {
+        ctx.accounts.vault_1.amount
+    } else {
+        0
+    };
+
+    drop(pool);
+
+    let mint_callback_ix = instruction::MintCallback {
+        amount_0_owed: amount_0,
+        amount_1_owed: amount_1,
+    };
+    let ix = Instruction::new_with_bytes(
+        ctx.accounts.callback_handler.key(),
+        &mint_callback_ix.data(),
+        ctx.accounts.to_account_metas(None),
+    );
+    solana_program::program::invoke(&ix, &ctx.accounts.to_account_infos())?;
+
+    ctx.accounts.vault_0.reload()?;
+    ctx.accounts.vault_1.reload()?;
+
+    if amount_0 > 0 {
+        require!(
+            balance_0_before + amount_0 <= ctx.accounts.vault_0.amount,
+            ErrorCode::M0
+        );
+    }
+    if amount_1 > 0 {
+        require!(
+            balance_1_before + amount_1 <= ctx.accounts.vault_1.amount,
+            ErrorCode::M1
+        );
+    }
+
+    if let Some(hook_program) = hook_program {
+        if call_after_hook {
+            hook::invoke_hook(
+                hook_program,
+                &hook::AfterModifyPositionHook {
+                    tick_lower: tick_lower.tick,
+                    tick_upper: tick_upper.tick,
+                    liquidity_delta: i64::try_from(amount).unwrap(),
+                    amount_0: amount_0_int,
+                    amount_1: amount_1_int,
+                },
+                &hook_accounts,
+            )?;
+        }
+    }
+
+    emit!(MintEvent {
+        pool_state: ctx.accounts.pool_state.key(),
+        sender: ctx.accounts.minter.key(),
+        owner: ctx.accounts.recipient.key(),
+        tick_lower: tick_lower.tick,
+        tick_upper: tick_upper.tick,
+        amount,
+        amount_0,
+        amount_1
+    });
+
+    ctx.accounts.pool_state.load_mut()?.unlocked = true;
+    Ok(TokenAmounts { amount_0, amount_1 })
+}
+
 /// Add liquidity to an initialized pool
 ///
 /// # Arguments
@@ -2402,7 +4773,7 @@ pub fn add_liquidity<'info>(
     let balance_0_before = accounts.vault_0.amount;
     let balance_1_before = accounts.vault_1.amount;
 
-    mint(
+    mint_liquidity(
         Context::new(&ID, accounts, remaining_accounts, BTreeMap::default()),
         liquidity,
     )?;
@@ -2418,3 +4789,234 @@ pub fn add_liquidity<'info>(
 
     Ok((liquidity, amount_0, amount_1))
 }
+
+/// Mints the NFT for a new tokenized position and writes its metadata
+///
+/// # Arguments
+///
+/// * `accounts` - Accounts of the tokenized position mint
+/// * `bump` - Bump of the tokenized position account
+/// * `tick_lower` - The lower tick of the position
+/// * `tick_upper` - The upper tick of the position
+/// * `liquidity` - The liquidity minted for the position
+/// * `amount_0` - The amount of token_0 paid for the liquidity
+/// * `amount_1` - The amount of token_1 paid for the liquidity
+///
+pub fn issue_tokenized_position(
+    accounts: &MintTokenizedPosition,
+    bump: u8,
+    tick_lower: i32,
+    tick_upper: i32,
+    liquidity: u64,
+    amount_0: u64,
+    amount_1: u64,
+) -> Result<()> {
+    // Mint the NFT
+    token::mint_to(
+        CpiContext::new_with_signer(
+            accounts.token_program.to_account_info().clone(),
+            token::MintTo {
+                mint: accounts.nft_mint.to_account_info().clone(),
+                to: accounts.nft_account.to_account_info().clone(),
+                authority: accounts.factory_state.to_account_info().clone(),
+            },
+            &[&[&[accounts.factory_state.load()?.bump] as &[u8]]],
+        ),
+        1,
+    )?;
+
+    // Write tokenized position metadata
+    let mut tokenized_position = accounts.tokenized_position_state.load_init()?;
+    tokenized_position.bump = bump;
+    tokenized_position.mint = accounts.nft_mint.key();
+    tokenized_position.pool_id = accounts.pool_state.key();
+
+    tokenized_position.tick_lower = tick_lower; // can read from core position
+    tokenized_position.tick_upper = tick_upper;
+    tokenized_position.liquidity = liquidity;
+    let core_position_state =
+        AccountLoader::<PositionState>::try_from(&accounts.core_position_state.to_account_info())?;
+    let core_position = core_position_state.load()?;
+    tokenized_position.fee_growth_inside_0_last_x32 = core_position.fee_growth_inside_0_last_x32;
+    tokenized_position.fee_growth_inside_1_last_x32 = core_position.fee_growth_inside_1_last_x32;
+
+    emit!(IncreaseLiquidityEvent {
+        token_id: accounts.nft_mint.key(),
+        liquidity,
+        amount_0,
+        amount_1
+    });
+
+    Ok(())
+}
+
+/// Creates the tick, bitmap and core position accounts of a new tokenized position if they
+/// don't exist yet, paid for by the minter. Existing accounts are validated by `mint`.
+///
+/// # Arguments
+///
+/// * `accounts` - Accounts of the tokenized position mint
+/// * `tick_lower` - The lower tick of the position
+/// * `tick_upper` - The upper tick of the position
+///
+pub fn init_position_accounts<'info>(
+    accounts: &MintTokenizedPosition<'info>,
+    tick_lower: i32,
+    tick_upper: i32,
+) -> Result<()> {
+    init_mint_accounts(
+        &accounts.minter.to_account_info(),
+        &accounts.system_program.to_account_info(),
+        &accounts.pool_state.to_account_info(),
+        [
+            (&accounts.tick_lower_state.to_account_info(), tick_lower),
+            (&accounts.tick_upper_state.to_account_info(), tick_upper),
+        ],
+        [
+            &accounts.bitmap_lower_state.to_account_info(),
+            &accounts.bitmap_upper_state.to_account_info(),
+        ],
+        &accounts.core_position_state.to_account_info(),
+        &accounts.factory_state.key(),
+    )
+}
+
+/// Creates the tick, bitmap and position accounts for a liquidity mint if they don't exist
+/// yet. Accounts are created at their PDA, so a missing account can not be substituted.
+///
+/// # Arguments
+///
+/// * `payer` - Pays rent for the new accounts
+/// * `system_program` - To create the accounts
+/// * `pool_state` - The pool in which liquidity is minted
+/// * `tick_states` - The lower and upper tick accounts, with their ticks
+/// * `bitmap_states` - The bitmap accounts of the lower and upper tick
+/// * `position_state` - The position into which liquidity is minted
+/// * `position_owner` - The owner from which the position address is derived
+///
+pub fn init_mint_accounts<'info>(
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    pool_state: &AccountInfo<'info>,
+    tick_states: [(&AccountInfo<'info>, i32); 2],
+    bitmap_states: [&AccountInfo<'info>; 2],
+    position_state: &AccountInfo<'info>,
+    position_owner: &Pubkey,
+) -> Result<()> {
+    let pool_state = AccountLoader::<PoolState>::try_from(pool_state)?;
+    let pool = *pool_state.load()?;
+    let (tick_lower, tick_upper) = (tick_states[0].1, tick_states[1].1);
+    check_ticks(tick_lower, tick_upper)?;
+
+    let fee_seed = pool.fee.to_be_bytes();
+    let pool_seeds = [
+        pool.token_0.as_ref(),
+        pool.token_1.as_ref(),
+        fee_seed.as_ref(),
+    ];
+
+    for (tick_state_info, tick) in tick_states {
+        if !tick_state_info.data_is_empty() {
+            continue;
+        }
+        check_tick(tick, pool.tick_spacing)?;
+        let tick_seed = tick.to_be_bytes();
+        let seeds = [
+            &[TICK_SEED.as_bytes()],
+            &pool_seeds[..],
+            &[tick_seed.as_ref()],
+        ]
+        .concat();
+        let bump = find_pda_bump(tick_state_info, &seeds)?;
+        create_program_account(
+            payer,
+            tick_state_info,
+            system_program,
+            8 + size_of::<TickState>(),
+            &[&seeds[..], &[&[bump]]].concat(),
+        )?;
+
+        let tick_loader = AccountLoader::<TickState>::try_from_unchecked(&ID, tick_state_info)?;
+        let mut tick_state = tick_loader.load_init()?;
+        tick_state.bump = bump;
+        tick_state.tick = tick;
+        drop(tick_state);
+        tick_loader.exit(&ID)?;
+    }
+
+    for (bitmap_state_info, tick) in [
+        (bitmap_states[0], tick_lower),
+        (bitmap_states[1], tick_upper),
+    ] {
+        if !bitmap_state_info.data_is_empty() {
+            continue;
+        }
+        let word_pos = tick_bitmap::position(tick / pool.tick_spacing as i32).word_pos;
+        let word_pos_seed = word_pos.to_be_bytes();
+        let seeds = [
+            &[BITMAP_SEED.as_bytes()],
+            &pool_seeds[..],
+            &[word_pos_seed.as_ref()],
+        ]
+        .concat();
+        let bump = find_pda_bump(bitmap_state_info, &seeds)?;
+        create_program_account(
+            payer,
+            bitmap_state_info,
+            system_program,
+            8 + size_of::<TickBitmapState>(),
+            &[&seeds[..], &[&[bump]]].concat(),
+        )?;
+
+        let bitmap_loader =
+            AccountLoader::<TickBitmapState>::try_from_unchecked(&ID, bitmap_state_info)?;
+        let mut bitmap = bitmap_loader.load_init()?;
+        bitmap.bump = bump;
+        bitmap.word_pos = word_pos;
+        drop(bitmap);
+        bitmap_loader.exit(&ID)?;
+    }
+
+    if position_state.data_is_empty() {
+        let tick_lower_seed = tick_lower.to_be_bytes();
+        let tick_upper_seed = tick_upper.to_be_bytes();
+        let seeds = [
+            &[POSITION_SEED.as_bytes()],
+            &pool_seeds[..],
+            &[
+                position_owner.as_ref(),
+                tick_lower_seed.as_ref(),
+                tick_upper_seed.as_ref(),
+            ],
+        ]
+        .concat();
+        let bump = find_pda_bump(position_state, &seeds)?;
+        create_program_account(
+            payer,
+            position_state,
+            system_program,
+            8 + size_of::<PositionState>(),
+            &[&seeds[..], &[&[bump]]].concat(),
+        )?;
+
+        let position_loader =
+            AccountLoader::<PositionState>::try_from_unchecked(&ID, position_state)?;
+        position_loader.load_init()?.bump = bump;
+        position_loader.exit(&ID)?;
+    }
+
+    Ok(())
+}
+
+/// Returns the bump of a program address, ensuring that the account matches the seeds
+///
+/// # Arguments
+///
+/// * `account` - The account expected at the program address
+/// * `seeds` - Seeds of the program address, without the bump
+///
+fn find_pda_bump(account: &AccountInfo, seeds: &[&[u8]]) -> Result<u8> {
+    let (address, bump) = Pubkey::find_program_address(seeds, &ID);
+    require!(address == account.key(), ErrorCode::InvalidAccountSeeds);
+    Ok(bump)
+}
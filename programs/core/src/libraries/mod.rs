@@ -10,3 +10,4 @@ pub mod swap_math;
 pub mod test_utils;
 pub mod tick_math;
 pub mod unsafe_math;
+pub mod zap_math;
@@ -0,0 +1,265 @@
+//! Zap math
+//! Computes how much of a single token deposit must be swapped so that the remainder and
+//! the swap output can be deposited in a price range at the post-swap price
+//!
+
+use super::liquidity_amounts;
+use super::swap_math;
+use super::tick_math;
+
+/// Returns the amount of the deposited token to swap before adding liquidity in a range
+///
+/// The swap is assumed to be performed within the current tick range, with the active
+/// liquidity. Tokens left unbalanced by crossed ticks or rounding remain with the user.
+///
+/// # Arguments
+///
+/// * `sqrt_price_x32` - The current sqrt price of the pool
+/// * `liquidity` - The active liquidity of the pool
+/// * `fee` - The fee of the pool, in hundredths of a bip
+/// * `sqrt_ratio_a_x32` - A sqrt price representing the lower tick boundary
+/// * `sqrt_ratio_b_x32` - A sqrt price representing the upper tick boundary
+/// * `amount_in` - The amount of the deposited token
+/// * `zero_for_one` - Whether token_0 is deposited and swapped for token_1
+///
+pub fn get_swap_amount_for_range(
+    sqrt_price_x32: u64,
+    liquidity: u64,
+    fee: u32,
+    sqrt_ratio_a_x32: u64,
+    sqrt_ratio_b_x32: u64,
+    amount_in: u64,
+    zero_for_one: bool,
+) -> u64 {
+    // Ranges outside the current price are funded with a single token
+    if sqrt_price_x32 <= sqrt_ratio_a_x32 {
+        return if zero_for_one { 0 } else { amount_in };
+    }
+    if sqrt_price_x32 >= sqrt_ratio_b_x32 {
+        return if zero_for_one { amount_in } else { 0 };
+    }
+    if liquidity == 0 {
+        return 0;
+    }
+
+    let sqrt_price_target_x32 = if zero_for_one {
+        tick_math::MIN_SQRT_RATIO + 1
+    } else {
+        tick_math::MAX_SQRT_RATIO - 1
+    };
+
+    // Binary search for the smallest swap amount after which the remaining input is no
+    // longer in excess of the ratio required by the range
+    let mut low = 0;
+    let mut high = amount_in;
+    while low < high {
+        let amount = low + (high - low) / 2;
+        let step = swap_math::compute_swap_step(
+            sqrt_price_x32,
+            sqrt_price_target_x32,
+            liquidity,
+            amount as i64,
+            fee,
+        );
+        let sqrt_price_next_x32 = step.sqrt_ratio_next_x32;
+        let remaining = amount_in - amount;
+
+        let swap_more = if zero_for_one {
+            sqrt_price_next_x32 > sqrt_ratio_a_x32
+                && liquidity_amounts::get_liquidity_for_amount_0(
+                    sqrt_price_next_x32,
+                    sqrt_ratio_b_x32,
+                    remaining,
+                ) > liquidity_amounts::get_liquidity_for_amount_1(
+                    sqrt_ratio_a_x32,
+                    sqrt_price_next_x32,
+                    step.amount_out,
+                )
+        } else {
+            sqrt_price_next_x32 < sqrt_ratio_b_x32
+                && liquidity_amounts::get_liquidity_for_amount_1(
+                    sqrt_ratio_a_x32,
+                    sqrt_price_next_x32,
+                    remaining,
+                ) > liquidity_amounts::get_liquidity_for_amount_0(
+                    sqrt_price_next_x32,
+                    sqrt_ratio_b_x32,
+                    step.amount_out,
+                )
+        };
+        if swap_more {
+            low = amount + 1;
+        } else {
+            high = amount;
+        }
+    }
+    low
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::libraries::test_utils::encode_price_sqrt_x32;
+
+    mod get_swap_amount_for_range {
+        use super::*;
+
+        const LIQUIDITY: u64 = 1_000_000_000_000;
+
+        #[test]
+        fn swaps_nothing_for_range_funded_by_input_token() {
+            let sqrt_price_x32 = encode_price_sqrt_x32(1, 1);
+            let sqrt_ratio_a_x32 = encode_price_sqrt_x32(110, 100);
+            let sqrt_ratio_b_x32 = encode_price_sqrt_x32(120, 100);
+
+            assert_eq!(
+                get_swap_amount_for_range(
+                    sqrt_price_x32,
+                    LIQUIDITY,
+                    3000,
+                    sqrt_ratio_a_x32,
+                    sqrt_ratio_b_x32,
+                    1_000,
+                    true
+                ),
+                0
+            );
+            assert_eq!(
+                get_swap_amount_for_range(
+                    sqrt_price_x32,
+                    LIQUIDITY,
+                    3000,
+                    sqrt_ratio_a_x32,
+                    sqrt_ratio_b_x32,
+                    1_000,
+                    false
+                ),
+                1_000
+            );
+        }
+
+        #[test]
+        fn swaps_everything_for_range_funded_by_output_token() {
+            let sqrt_price_x32 = encode_price_sqrt_x32(1, 1);
+            let sqrt_ratio_a_x32 = encode_price_sqrt_x32(80, 100);
+            let sqrt_ratio_b_x32 = encode_price_sqrt_x32(90, 100);
+
+            assert_eq!(
+                get_swap_amount_for_range(
+                    sqrt_price_x32,
+                    LIQUIDITY,
+                    3000,
+                    sqrt_ratio_a_x32,
+                    sqrt_ratio_b_x32,
+                    1_000,
+                    true
+                ),
+                1_000
+            );
+        }
+
+        #[test]
+        fn swaps_about_half_for_symmetric_range_without_fee() {
+            let sqrt_price_x32 = encode_price_sqrt_x32(1, 1);
+            let sqrt_ratio_a_x32 = encode_price_sqrt_x32(100, 110);
+            let sqrt_ratio_b_x32 = encode_price_sqrt_x32(110, 100);
+
+            for zero_for_one in [true, false] {
+                let amount = get_swap_amount_for_range(
+                    sqrt_price_x32,
+                    LIQUIDITY,
+                    0,
+                    sqrt_ratio_a_x32,
+                    sqrt_ratio_b_x32,
+                    1_000_000,
+                    zero_for_one,
+                );
+                assert!((499_000..=501_000).contains(&amount), "{}", amount);
+            }
+        }
+
+        #[test]
+        fn swaps_more_to_cover_the_fee() {
+            let sqrt_price_x32 = encode_price_sqrt_x32(1, 1);
+            let sqrt_ratio_a_x32 = encode_price_sqrt_x32(100, 110);
+            let sqrt_ratio_b_x32 = encode_price_sqrt_x32(110, 100);
+
+            let without_fee = get_swap_amount_for_range(
+                sqrt_price_x32,
+                LIQUIDITY,
+                0,
+                sqrt_ratio_a_x32,
+                sqrt_ratio_b_x32,
+                1_000_000,
+                true,
+            );
+            let with_fee = get_swap_amount_for_range(
+                sqrt_price_x32,
+                LIQUIDITY,
+                10_000,
+                sqrt_ratio_a_x32,
+                sqrt_ratio_b_x32,
+                1_000_000,
+                true,
+            );
+            assert!(with_fee > without_fee);
+        }
+
+        #[test]
+        fn leaves_balanced_amounts_for_the_range() {
+            let sqrt_price_x32 = encode_price_sqrt_x32(1, 1);
+            let sqrt_ratio_a_x32 = encode_price_sqrt_x32(100, 150);
+            let sqrt_ratio_b_x32 = encode_price_sqrt_x32(110, 100);
+            let amount_in = 1_000_000;
+
+            let amount = get_swap_amount_for_range(
+                sqrt_price_x32,
+                LIQUIDITY,
+                3000,
+                sqrt_ratio_a_x32,
+                sqrt_ratio_b_x32,
+                amount_in,
+                true,
+            );
+            let step = swap_math::compute_swap_step(
+                sqrt_price_x32,
+                tick_math::MIN_SQRT_RATIO + 1,
+                LIQUIDITY,
+                amount as i64,
+                3000,
+            );
+            let liquidity_0 = liquidity_amounts::get_liquidity_for_amount_0(
+                step.sqrt_ratio_next_x32,
+                sqrt_ratio_b_x32,
+                amount_in - amount,
+            );
+            let liquidity_1 = liquidity_amounts::get_liquidity_for_amount_1(
+                sqrt_ratio_a_x32,
+                step.sqrt_ratio_next_x32,
+                step.amount_out,
+            );
+            // within 0.1% of each other
+            assert!(liquidity_0.abs_diff(liquidity_1) * 1000 <= liquidity_0.max(liquidity_1));
+        }
+
+        #[test]
+        fn swaps_nothing_without_liquidity() {
+            let sqrt_price_x32 = encode_price_sqrt_x32(1, 1);
+            let sqrt_ratio_a_x32 = encode_price_sqrt_x32(100, 110);
+            let sqrt_ratio_b_x32 = encode_price_sqrt_x32(110, 100);
+
+            assert_eq!(
+                get_swap_amount_for_range(
+                    sqrt_price_x32,
+                    0,
+                    3000,
+                    sqrt_ratio_a_x32,
+                    sqrt_ratio_b_x32,
+                    1_000,
+                    true
+                ),
+                0
+            );
+        }
+    }
+}
@@ -1,5 +1,24 @@
+use crate::error::ErrorCode;
 use anchor_lang::prelude::*;
 
+/// The maximum length of the position NFT base URI. Leaves space in the Metaplex URI for
+/// the mint address
+pub const MAX_BASE_URI_LENGTH: usize = 128;
+
+/// The maximum length of the position NFT collection name, as allowed by Metaplex
+pub const MAX_COLLECTION_NAME_LENGTH: usize = 32;
+
+/// Base URI of position NFT metadata on factory initialization
+pub const DEFAULT_BASE_URI: &str =
+    "https://asia-south1-cyclos-finance.cloudfunctions.net/nft?mint=";
+
+/// Collection name of position NFTs on factory initialization
+pub const DEFAULT_COLLECTION_NAME: &str = "Cyclos Positions NFT-V1";
+
+/// Seed to derive the mint of the position NFT collection. The factory is its mint and update
+/// authority
+pub const COLLECTION_SEED: &str = "c";
+
 /// Holds the current owner of the factory
 ///
 /// # The owner can
@@ -7,11 +26,12 @@ use anchor_lang::prelude::*;
 /// 1. Set and collect a pool's protocol fees
 /// 2. Enable a new fee amount for pool creation
 /// 3. Set another address as an owner
+/// 4. Set the metadata URI and collection name of position NFTs
+/// 5. Create the collection NFT that position NFTs are verified into
 ///
 /// PDA of `[]`
 ///
 #[account(zero_copy)]
-#[derive(Default)]
 #[repr(packed)]
 pub struct FactoryState {
     /// Bump to identify PDA
@@ -22,6 +42,82 @@ pub struct FactoryState {
 
     /// The global protocol fee
     pub fee_protocol: u8,
+
+    /// Base URI of position NFT metadata, followed by the NFT mint address. Zero padded
+    pub base_uri: [u8; MAX_BASE_URI_LENGTH],
+
+    /// Name of the collection that position NFTs belong to. Zero padded
+    pub collection_name: [u8; MAX_COLLECTION_NAME_LENGTH],
+}
+
+impl Default for FactoryState {
+    fn default() -> Self {
+        Self {
+            bump: 0,
+            owner: Pubkey::default(),
+            fee_protocol: 0,
+            base_uri: [0; MAX_BASE_URI_LENGTH],
+            collection_name: [0; MAX_COLLECTION_NAME_LENGTH],
+        }
+    }
+}
+
+impl FactoryState {
+    /// Sets the base URI and collection name of position NFTs
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - The factory state
+    /// * `base_uri` - Base URI of position NFT metadata
+    /// * `collection_name` - Name of the position NFT collection
+    ///
+    pub fn set_metadata_config(&mut self, base_uri: &str, collection_name: &str) -> Result<()> {
+        require!(
+            base_uri.len() <= MAX_BASE_URI_LENGTH
+                && !collection_name.is_empty()
+                && collection_name.len() <= MAX_COLLECTION_NAME_LENGTH,
+            ErrorCode::InvalidMetadataLength
+        );
+
+        let mut base_uri_bytes = [0u8; MAX_BASE_URI_LENGTH];
+        base_uri_bytes[..base_uri.len()].copy_from_slice(base_uri.as_bytes());
+        self.base_uri = base_uri_bytes;
+
+        let mut collection_name_bytes = [0u8; MAX_COLLECTION_NAME_LENGTH];
+        collection_name_bytes[..collection_name.len()].copy_from_slice(collection_name.as_bytes());
+        self.collection_name = collection_name_bytes;
+
+        Ok(())
+    }
+
+    /// Returns the base URI of position NFT metadata
+    pub fn base_uri(&self) -> String {
+        let base_uri = self.base_uri;
+        from_padded_bytes(&base_uri)
+    }
+
+    /// Returns the name of the position NFT collection
+    pub fn collection_name(&self) -> String {
+        let collection_name = self.collection_name;
+        from_padded_bytes(&collection_name)
+    }
+
+    /// Returns the metadata URI of a position NFT
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - The factory state
+    /// * `mint` - Mint address of the position NFT
+    ///
+    pub fn token_uri(&self, mint: &Pubkey) -> String {
+        format!("{}{}", self.base_uri(), mint)
+    }
+}
+
+/// Reads a string stored in a zero padded byte array
+fn from_padded_bytes(bytes: &[u8]) -> String {
+    let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..len]).into_owned()
 }
 
 /// Emitted when the owner of the factory is changed
@@ -45,3 +141,62 @@ pub struct SetFeeProtocolEvent {
     /// The updated value of the protocol fee
     pub fee_protocol: u8,
 }
+
+/// Emitted when the metadata URI or collection name of position NFTs is changed
+#[event]
+pub struct MetadataConfigChanged {
+    /// The new base URI of position NFT metadata
+    pub base_uri: String,
+
+    /// The new name of the position NFT collection
+    pub collection_name: String,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stores_metadata_config() {
+        let mut factory = FactoryState::default();
+        factory
+            .set_metadata_config(DEFAULT_BASE_URI, DEFAULT_COLLECTION_NAME)
+            .unwrap();
+        assert_eq!(factory.base_uri(), DEFAULT_BASE_URI);
+        assert_eq!(factory.collection_name(), DEFAULT_COLLECTION_NAME);
+
+        factory
+            .set_metadata_config("https://example.com/", "Positions")
+            .unwrap();
+        assert_eq!(factory.base_uri(), "https://example.com/");
+        assert_eq!(factory.collection_name(), "Positions");
+    }
+
+    #[test]
+    fn appends_the_mint_to_the_base_uri() {
+        let mut factory = FactoryState::default();
+        factory
+            .set_metadata_config("https://example.com/nft/", "Positions")
+            .unwrap();
+        let mint = Pubkey::new_unique();
+        assert_eq!(
+            factory.token_uri(&mint),
+            format!("https://example.com/nft/{}", mint)
+        );
+    }
+
+    #[test]
+    fn fails_for_long_or_empty_values() {
+        let mut factory = FactoryState::default();
+        let long_uri = "a".repeat(MAX_BASE_URI_LENGTH + 1);
+        assert!(factory.set_metadata_config(&long_uri, "Positions").is_err());
+
+        let long_name = "a".repeat(MAX_COLLECTION_NAME_LENGTH + 1);
+        assert!(factory.set_metadata_config("", &long_name).is_err());
+        assert!(factory.set_metadata_config("", "").is_err());
+
+        factory
+            .set_metadata_config(&"a".repeat(MAX_BASE_URI_LENGTH), "Positions")
+            .unwrap();
+    }
+}
@@ -4,8 +4,8 @@ pub const FEE_SEED: &str = "f";
 
 /// Stores a fee amount and tick spacing pair enabled by the protocol owner
 ///
-/// A fee amount can never be removed, so this value should be hard coded
-/// or cached in the calling context
+/// A fee amount can never be removed, but the owner can disable it to stop new
+/// pools from being created on the tier. Existing pools are unaffected.
 ///
 /// PDA of `[FEE_SEED, fee]`
 ///
@@ -22,6 +22,9 @@ pub struct FeeState {
     /// The minimum number of ticks between initialized ticks for pools
     /// created with the given fee
     pub tick_spacing: u16,
+
+    /// Whether new pools can be created with this fee
+    pub enabled: bool,
 }
 
 /// Emitted when a new fee amount is enabled for pool creation via the factory
@@ -36,3 +39,11 @@ pub struct FeeAmountEnabled {
     #[index]
     pub tick_spacing: u16,
 }
+
+/// Emitted when a fee amount is disabled for pool creation via the factory
+#[event]
+pub struct FeeAmountDisabled {
+    /// The disabled fee, denominated in hundredths of a bip (10^-6)
+    #[index]
+    pub fee: u32,
+}
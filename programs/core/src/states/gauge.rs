@@ -0,0 +1,350 @@
+use crate::error::ErrorCode;
+use crate::libraries::full_math::MulDiv;
+use anchor_lang::prelude::*;
+
+/// Seed to derive the gauge controller address and signature
+pub const GAUGE_CONTROLLER_SEED: &str = "gc";
+
+/// Seed to derive gauge addresses
+pub const GAUGE_SEED: &str = "g";
+
+/// Seed to derive epoch addresses
+pub const EPOCH_SEED: &str = "e";
+
+/// Seed to derive the addresses of gauge votes in an epoch
+pub const EPOCH_GAUGE_SEED: &str = "eg";
+
+/// Seed to derive gauge voter addresses
+pub const GAUGE_VOTER_SEED: &str = "gv";
+
+/// Seed to derive the addresses of an escrow's vote for a gauge in an epoch
+pub const GAUGE_VOTE_SEED: &str = "gvt";
+
+/// The sum of vote weights a voter can allocate in an epoch, in basis points
+pub const MAX_VOTE_WEIGHT_BPS: u16 = 10_000;
+
+/// Splits liquidity incentive emissions across pools by the votes of locked voter
+/// escrows. Votes are cast during an epoch for the next epoch.
+///
+/// PDA of `[GAUGE_CONTROLLER_SEED]`
+///
+#[account(zero_copy)]
+#[derive(Default)]
+#[repr(packed)]
+pub struct GaugeControllerState {
+    /// Bump to identify PDA
+    pub bump: u8,
+
+    /// The locker whose escrows vote
+    pub locker: Pubkey,
+
+    /// Unix timestamp when epoch 0 starts
+    pub start_time: i64,
+
+    /// Duration of an epoch in seconds
+    pub epoch_duration: u32,
+
+    /// The emissions split across gauges every epoch
+    pub emissions_per_epoch: u64,
+}
+
+impl GaugeControllerState {
+    /// Returns the epoch at a time
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - The gauge controller
+    /// * `time` - Unix timestamp, not before the start time
+    ///
+    pub fn epoch_at(&self, time: i64) -> u32 {
+        ((time - self.start_time) / self.epoch_duration as i64) as u32
+    }
+
+    /// Returns the time when an epoch ends
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - The gauge controller
+    /// * `epoch` - The epoch
+    ///
+    pub fn epoch_end(&self, epoch: u32) -> i64 {
+        self.start_time + (epoch as i64 + 1) * self.epoch_duration as i64
+    }
+}
+
+/// Liquidity incentives of a pool, set from votes
+///
+/// PDA of `[GAUGE_SEED, pool]`
+///
+#[account(zero_copy)]
+#[derive(Default)]
+#[repr(packed)]
+pub struct GaugeState {
+    /// Bump to identify PDA
+    pub bump: u8,
+
+    /// The pool receiving incentives
+    pub pool: Pubkey,
+
+    /// The epoch of the current emissions
+    pub epoch: u32,
+
+    /// The emissions for the pool's liquidity providers in the epoch
+    pub emissions: u64,
+}
+
+/// Total votes cast for an epoch
+///
+/// PDA of `[EPOCH_SEED, epoch]`
+///
+#[account(zero_copy)]
+#[derive(Default)]
+#[repr(packed)]
+pub struct EpochState {
+    /// Bump to identify PDA
+    pub bump: u8,
+
+    /// The epoch
+    pub epoch: u32,
+
+    /// Voting power cast for all gauges
+    pub total_weight: u64,
+}
+
+/// Votes cast for a gauge in an epoch
+///
+/// PDA of `[EPOCH_GAUGE_SEED, gauge, epoch]`
+///
+#[account(zero_copy)]
+#[derive(Default)]
+#[repr(packed)]
+pub struct EpochGaugeState {
+    /// Bump to identify PDA
+    pub bump: u8,
+
+    /// The gauge voted for
+    pub gauge: Pubkey,
+
+    /// The epoch
+    pub epoch: u32,
+
+    /// Voting power cast for the gauge
+    pub weight: u64,
+}
+
+/// Tracks the vote weight allocated by an escrow in its latest voting epoch
+///
+/// PDA of `[GAUGE_VOTER_SEED, escrow]`
+///
+#[account(zero_copy)]
+#[derive(Default)]
+#[repr(packed)]
+pub struct GaugeVoterState {
+    /// Bump to identify PDA
+    pub bump: u8,
+
+    /// The locked voter escrow
+    pub escrow: Pubkey,
+
+    /// The latest epoch voted for
+    pub epoch: u32,
+
+    /// Vote weight allocated in the epoch, in basis points
+    pub weight_bps: u16,
+}
+
+impl GaugeVoterState {
+    /// Replaces the vote weight allocated to a gauge in an epoch. Allocations restart in a
+    /// new epoch.
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - The voter
+    /// * `epoch` - The epoch voted for
+    /// * `previous_bps` - The weight previously allocated to the gauge in the epoch
+    /// * `weight_bps` - The new weight for the gauge, in basis points. Zero revokes the vote
+    ///
+    pub fn allocate(&mut self, epoch: u32, previous_bps: u16, weight_bps: u16) -> Result<()> {
+        let allocated_bps = if self.epoch == epoch {
+            self.weight_bps - previous_bps
+        } else {
+            0
+        };
+        require!(
+            (weight_bps > 0 || previous_bps > 0)
+                && allocated_bps + weight_bps <= MAX_VOTE_WEIGHT_BPS,
+            ErrorCode::InvalidVoteWeight
+        );
+        self.epoch = epoch;
+        self.weight_bps = allocated_bps + weight_bps;
+        Ok(())
+    }
+}
+
+/// The vote of an escrow for a gauge in an epoch. Replaced when the escrow votes again for
+/// the gauge before the epoch starts.
+///
+/// PDA of `[GAUGE_VOTE_SEED, escrow, gauge, epoch]`
+///
+#[account(zero_copy)]
+#[derive(Default)]
+#[repr(packed)]
+pub struct GaugeVoteState {
+    /// Bump to identify PDA
+    pub bump: u8,
+
+    /// The locked voter escrow
+    pub escrow: Pubkey,
+
+    /// The gauge voted for
+    pub gauge: Pubkey,
+
+    /// The epoch voted for
+    pub epoch: u32,
+
+    /// Share of voting power cast, in basis points
+    pub weight_bps: u16,
+
+    /// Voting power cast
+    pub weight: u64,
+}
+
+/// Returns the emissions of a gauge for its share of the epoch votes
+///
+/// # Arguments
+///
+/// * `emissions_per_epoch` - The emissions split across gauges
+/// * `weight` - Votes cast for the gauge
+/// * `total_weight` - Votes cast for all gauges
+///
+pub fn gauge_emissions(emissions_per_epoch: u64, weight: u64, total_weight: u64) -> u64 {
+    if total_weight == 0 {
+        return 0;
+    }
+    emissions_per_epoch
+        .mul_div_floor(weight, total_weight)
+        .unwrap()
+}
+
+/// Emitted when an escrow votes for a gauge
+#[event]
+pub struct GaugeVoteEvent {
+    /// The voting escrow
+    #[index]
+    pub escrow: Pubkey,
+
+    /// The pool of the gauge
+    #[index]
+    pub pool: Pubkey,
+
+    /// The epoch voted for
+    pub epoch: u32,
+
+    /// Voting power cast, replacing the previous vote for the gauge. Zero if revoked
+    pub weight: u64,
+}
+
+/// Emitted when the emissions of a gauge are set for an epoch
+#[event]
+pub struct GaugeSyncedEvent {
+    /// The pool of the gauge
+    #[index]
+    pub pool: Pubkey,
+
+    /// The epoch
+    pub epoch: u32,
+
+    /// The emissions for the pool's liquidity providers in the epoch
+    pub emissions: u64,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    mod epoch_at {
+        use super::*;
+
+        #[test]
+        fn counts_epochs_from_the_start_time() {
+            let controller = GaugeControllerState {
+                start_time: 1000,
+                epoch_duration: 100,
+                ..Default::default()
+            };
+            assert_eq!(controller.epoch_at(1000), 0);
+            assert_eq!(controller.epoch_at(1099), 0);
+            assert_eq!(controller.epoch_at(1100), 1);
+            assert_eq!(controller.epoch_at(1550), 5);
+        }
+    }
+
+    mod epoch_end {
+        use super::*;
+
+        #[test]
+        fn is_the_start_of_the_next_epoch() {
+            let controller = GaugeControllerState {
+                start_time: 1000,
+                epoch_duration: 100,
+                ..Default::default()
+            };
+            assert_eq!(controller.epoch_end(0), 1100);
+            assert_eq!(controller.epoch_at(controller.epoch_end(4)), 5);
+        }
+    }
+
+    mod allocate {
+        use super::*;
+
+        #[test]
+        fn adds_up_weights_within_an_epoch() {
+            let mut voter = GaugeVoterState::default();
+            voter.allocate(1, 0, 6000).unwrap();
+            voter.allocate(1, 0, 4000).unwrap();
+            assert_eq!({ voter.weight_bps }, 10_000);
+            assert!(voter.allocate(1, 0, 1).is_err());
+        }
+
+        #[test]
+        fn replaces_a_previous_vote() {
+            let mut voter = GaugeVoterState::default();
+            voter.allocate(1, 0, 6000).unwrap();
+            voter.allocate(1, 0, 4000).unwrap();
+            voter.allocate(1, 6000, 1000).unwrap();
+            assert_eq!({ voter.weight_bps }, 5000);
+            voter.allocate(1, 4000, 0).unwrap();
+            assert_eq!({ voter.weight_bps }, 1000);
+        }
+
+        #[test]
+        fn restarts_in_a_new_epoch() {
+            let mut voter = GaugeVoterState::default();
+            voter.allocate(1, 0, 10_000).unwrap();
+            voter.allocate(2, 0, 3000).unwrap();
+            assert_eq!({ voter.epoch }, 2);
+            assert_eq!({ voter.weight_bps }, 3000);
+        }
+
+        #[test]
+        fn fails_for_zero_weight_without_a_previous_vote() {
+            let mut voter = GaugeVoterState::default();
+            assert!(voter.allocate(1, 0, 0).is_err());
+        }
+    }
+
+    mod gauge_emissions {
+        use super::*;
+
+        #[test]
+        fn splits_emissions_by_weight() {
+            assert_eq!(gauge_emissions(1000, 1, 4), 250);
+            assert_eq!(gauge_emissions(1000, 4, 4), 1000);
+        }
+
+        #[test]
+        fn is_zero_without_votes() {
+            assert_eq!(gauge_emissions(1000, 0, 0), 0);
+        }
+    }
+}
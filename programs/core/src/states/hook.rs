@@ -0,0 +1,330 @@
+//! Hooks let a pool delegate custom logic, such as KYC gating, fee rebates or on-chain
+//! order books, to a separate program
+//!
+//! The hook program and its flags are fixed at pool creation. Hooks are invoked by instruction,
+//! like `mint_callback` and `swap_callback`. A hook program implements the instructions
+//! `before_swap_hook`, `after_swap_hook`, `before_modify_position_hook` and
+//! `after_modify_position_hook` with the accounts and arguments defined in this module.
+//! Instruction data follows the Anchor layout, so an Anchor program can implement the hooks
+//! as regular instructions.
+//!
+//! Hooks are invoked while the pool is locked, so a hook can not reenter the pool.
+//!
+use crate::error::ErrorCode;
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program;
+use anchor_lang::solana_program::{hash, instruction::Instruction};
+
+/// Invoke the hook program before a swap
+pub const BEFORE_SWAP_FLAG: u8 = 1 << 0;
+
+/// Invoke the hook program after a swap
+pub const AFTER_SWAP_FLAG: u8 = 1 << 1;
+
+/// Invoke the hook program before liquidity is minted or burned
+pub const BEFORE_MODIFY_POSITION_FLAG: u8 = 1 << 2;
+
+/// Invoke the hook program after liquidity is minted or burned
+pub const AFTER_MODIFY_POSITION_FLAG: u8 = 1 << 3;
+
+/// All valid hook flags
+pub const HOOK_FLAGS_MASK: u8 =
+    BEFORE_SWAP_FLAG | AFTER_SWAP_FLAG | BEFORE_MODIFY_POSITION_FLAG | AFTER_MODIFY_POSITION_FLAG;
+
+/// Arguments of a hook instruction
+pub trait HookArgs: AnchorSerialize {
+    /// Name of the instruction in the hook program
+    const NAME: &'static str;
+
+    /// Serializes the instruction data, the Anchor sighash of the instruction name
+    /// followed by the Borsh serialized arguments
+    fn data(&self) -> Vec<u8> {
+        let preimage = format!("global:{}", Self::NAME);
+        let mut data = hash::hash(preimage.as_bytes()).to_bytes()[..8].to_vec();
+        self.serialize(&mut data).unwrap();
+        data
+    }
+}
+
+/// Arguments of `before_swap_hook`, invoked before a swap if `BEFORE_SWAP_FLAG` is set
+///
+/// The hook receives the accounts `[sender, pool_state]`.
+///
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct BeforeSwapHook {
+    /// The direction of the swap, true for token_0 to token_1
+    pub zero_for_one: bool,
+
+    /// The amount of the swap, positive for exact input and negative for exact output
+    pub amount_specified: i64,
+
+    /// The Q32.32 sqrt price limit of the swap
+    pub sqrt_price_limit_x32: u64,
+}
+
+impl HookArgs for BeforeSwapHook {
+    const NAME: &'static str = "before_swap_hook";
+}
+
+/// Arguments of `after_swap_hook`, invoked after a swap if `AFTER_SWAP_FLAG` is set
+///
+/// The hook receives the accounts `[sender, pool_state]`.
+///
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct AfterSwapHook {
+    /// The delta of the token_0 balance of the pool
+    pub amount_0: i64,
+
+    /// The delta of the token_1 balance of the pool
+    pub amount_1: i64,
+
+    /// The sqrt(price) of the pool after the swap, as a Q32.32
+    pub sqrt_price_x32: u64,
+
+    /// The tick of the pool after the swap
+    pub tick: i32,
+}
+
+impl HookArgs for AfterSwapHook {
+    const NAME: &'static str = "after_swap_hook";
+}
+
+/// Arguments of `before_modify_position_hook`, invoked before liquidity is minted or burned
+/// if `BEFORE_MODIFY_POSITION_FLAG` is set. Pokes do not invoke hooks.
+///
+/// The hook receives the accounts `[sender, owner, pool_state]`.
+///
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct BeforeModifyPositionHook {
+    /// The lower tick of the position
+    pub tick_lower: i32,
+
+    /// The upper tick of the position
+    pub tick_upper: i32,
+
+    /// The change in liquidity, positive for mint and negative for burn
+    pub liquidity_delta: i64,
+}
+
+impl HookArgs for BeforeModifyPositionHook {
+    const NAME: &'static str = "before_modify_position_hook";
+}
+
+/// Arguments of `after_modify_position_hook`, invoked after liquidity is minted or burned
+/// if `AFTER_MODIFY_POSITION_FLAG` is set. Pokes do not invoke hooks.
+///
+/// The hook receives the accounts `[sender, owner, pool_state]`.
+///
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct AfterModifyPositionHook {
+    /// The lower tick of the position
+    pub tick_lower: i32,
+
+    /// The upper tick of the position
+    pub tick_upper: i32,
+
+    /// The change in liquidity, positive for mint and negative for burn
+    pub liquidity_delta: i64,
+
+    /// The amount of token_0 owed to the pool, negative if owed by the pool
+    pub amount_0: i64,
+
+    /// The amount of token_1 owed to the pool, negative if owed by the pool
+    pub amount_1: i64,
+}
+
+impl HookArgs for AfterModifyPositionHook {
+    const NAME: &'static str = "after_modify_position_hook";
+}
+
+/// Validates the hook program and flags of a new pool
+///
+/// # Arguments
+///
+/// * `hook_program` - Program invoked by the pool's hooks. Must be the default address if
+/// `hook_flags` is zero
+/// * `hook_flags` - Bitmask of hooks to enable
+///
+pub fn validate_hook_config(hook_program: Pubkey, hook_flags: u8) -> Result<()> {
+    require!(
+        hook_flags & !HOOK_FLAGS_MASK == 0
+            && (hook_flags == 0) == (hook_program == Pubkey::default()),
+        ErrorCode::InvalidHookFlags
+    );
+    Ok(())
+}
+
+/// Splits the hook program from the front of the remaining accounts
+///
+/// Pools with hooks expect the hook program as the first remaining account of
+/// `swap`, `mint` and `burn`. Other remaining accounts follow in their usual order.
+///
+/// # Arguments
+///
+/// * `hook_program` - The hook program registered for the pool
+/// * `remaining_accounts` - Remaining accounts passed to the instruction
+///
+pub fn split_hook_program<'a, 'info>(
+    hook_program: Pubkey,
+    remaining_accounts: &'a [AccountInfo<'info>],
+) -> Result<(&'a AccountInfo<'info>, &'a [AccountInfo<'info>])> {
+    let (program, rest) = remaining_accounts
+        .split_first()
+        .ok_or(ErrorCode::InvalidHookProgram)?;
+    require!(program.key() == hook_program, ErrorCode::InvalidHookProgram);
+    Ok((program, rest))
+}
+
+/// Invokes a hook instruction
+///
+/// Accounts are passed as read only and without signer privileges, so the hook program can
+/// not act on behalf of the user.
+///
+/// # Arguments
+///
+/// * `hook_program` - The program implementing the hook
+/// * `args` - Arguments of the hook instruction
+/// * `accounts` - Accounts expected by the hook instruction
+///
+pub fn invoke_hook<'info>(
+    hook_program: &AccountInfo<'info>,
+    args: &impl HookArgs,
+    accounts: &[AccountInfo<'info>],
+) -> Result<()> {
+    let ix = Instruction::new_with_bytes(
+        hook_program.key(),
+        &args.data(),
+        accounts
+            .iter()
+            .map(|account| AccountMeta::new_readonly(account.key(), false))
+            .collect(),
+    );
+    let mut account_infos = accounts.to_vec();
+    account_infos.push(hook_program.clone());
+    solana_program::program::invoke(&ix, &account_infos)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    mod validate_hook_config {
+        use super::*;
+
+        #[test]
+        fn accepts_pools_without_hooks() {
+            validate_hook_config(Pubkey::default(), 0).unwrap();
+        }
+
+        #[test]
+        fn accepts_valid_flags_with_a_program() {
+            validate_hook_config(Pubkey::new_unique(), BEFORE_SWAP_FLAG).unwrap();
+            validate_hook_config(Pubkey::new_unique(), HOOK_FLAGS_MASK).unwrap();
+        }
+
+        #[test]
+        fn fails_for_unknown_flags() {
+            assert!(validate_hook_config(Pubkey::new_unique(), 1 << 4).is_err());
+        }
+
+        #[test]
+        fn fails_for_flags_without_a_program() {
+            assert!(validate_hook_config(Pubkey::default(), AFTER_SWAP_FLAG).is_err());
+        }
+
+        #[test]
+        fn fails_for_a_program_without_flags() {
+            assert!(validate_hook_config(Pubkey::new_unique(), 0).is_err());
+        }
+    }
+
+    mod hook_args {
+        use super::*;
+
+        #[test]
+        fn prefixes_the_anchor_sighash() {
+            let data = BeforeSwapHook {
+                zero_for_one: true,
+                amount_specified: -5,
+                sqrt_price_limit_x32: 7,
+            }
+            .data();
+            assert_eq!(
+                data[..8],
+                hash::hash(b"global:before_swap_hook").to_bytes()[..8]
+            );
+
+            let mut args = vec![1];
+            args.extend_from_slice(&(-5i64).to_le_bytes());
+            args.extend_from_slice(&7u64.to_le_bytes());
+            assert_eq!(data[8..], args[..]);
+        }
+
+        #[test]
+        fn uses_a_distinct_sighash_per_hook() {
+            let before = BeforeModifyPositionHook {
+                tick_lower: 0,
+                tick_upper: 0,
+                liquidity_delta: 0,
+            }
+            .data();
+            let after = AfterModifyPositionHook {
+                tick_lower: 0,
+                tick_upper: 0,
+                liquidity_delta: 0,
+                amount_0: 0,
+                amount_1: 0,
+            }
+            .data();
+            assert_ne!(before[..8], after[..8]);
+        }
+    }
+
+    mod split_hook_program {
+        use super::*;
+
+        #[test]
+        fn splits_the_hook_program_from_other_accounts() {
+            let hook_program = Pubkey::new_unique();
+            let other = Pubkey::new_unique();
+            let owner = Pubkey::default();
+            let (mut lamports_0, mut lamports_1) = (0, 0);
+            let (mut data_0, mut data_1) = (vec![], vec![]);
+            let accounts = [
+                AccountInfo::new(
+                    &hook_program,
+                    false,
+                    false,
+                    &mut lamports_0,
+                    &mut data_0,
+                    &owner,
+                    true,
+                    0,
+                ),
+                AccountInfo::new(
+                    &other,
+                    false,
+                    true,
+                    &mut lamports_1,
+                    &mut data_1,
+                    &owner,
+                    false,
+                    0,
+                ),
+            ];
+
+            let (program, rest) = split_hook_program(hook_program, &accounts).unwrap();
+            assert_eq!(program.key(), hook_program);
+            assert_eq!(rest.len(), 1);
+            assert_eq!(rest[0].key(), other);
+
+            assert!(split_hook_program(other, &accounts).is_err());
+        }
+
+        #[test]
+        fn fails_without_remaining_accounts() {
+            assert!(split_hook_program(Pubkey::new_unique(), &[]).is_err());
+        }
+    }
+}
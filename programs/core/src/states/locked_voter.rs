@@ -0,0 +1,199 @@
+//! Accounts of the Tribeca locked voter program, read to weigh gauge votes.
+//! Layouts mirror the `locked_voter` build bundled in `external-programs`.
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+use std::io::Write;
+
+/// The locked voter program
+pub mod locked_voter_program {
+    anchor_lang::declare_id!("LocktDzaV1W2Bm9DeZeiyz4J9zs4fRqNiYqQyracRXw");
+}
+
+/// Parameters of a locker
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default)]
+pub struct LockerParams {
+    /// Whether only whitelisted programs can lock tokens through CPI
+    pub whitelist_enabled: bool,
+
+    /// Voting power of a token locked for the maximum duration
+    pub max_stake_vote_multiplier: u8,
+
+    /// Minimum lock duration in seconds
+    pub min_stake_duration: u64,
+
+    /// Maximum lock duration in seconds
+    pub max_stake_duration: u64,
+
+    /// Minimum voting power to activate a governance proposal
+    pub proposal_activation_min_votes: u64,
+}
+
+/// Locks governance tokens in escrows for voting power
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default)]
+pub struct Locker {
+    /// Base address used to derive the locker
+    pub base: Pubkey,
+
+    /// Bump to identify PDA
+    pub bump: u8,
+
+    /// Mint of the locked governance token
+    pub token_mint: Pubkey,
+
+    /// Total amount of tokens locked in escrows
+    pub locked_supply: u64,
+
+    /// Governor of the locker
+    pub governor: Pubkey,
+
+    /// Parameters of the locker
+    pub params: LockerParams,
+}
+
+/// Tokens locked by an owner until a time
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default)]
+pub struct Escrow {
+    /// The locker of the escrow
+    pub locker: Pubkey,
+
+    /// Owner of the locked tokens
+    pub owner: Pubkey,
+
+    /// Bump to identify PDA
+    pub bump: u8,
+
+    /// Token account holding the locked tokens
+    pub tokens: Pubkey,
+
+    /// Amount of locked tokens
+    pub amount: u64,
+
+    /// Unix timestamp when the lock started
+    pub escrow_started_at: i64,
+
+    /// Unix timestamp when the lock ends
+    pub escrow_ends_at: i64,
+
+    /// Address allowed to vote on behalf of the owner
+    pub vote_delegate: Pubkey,
+}
+
+impl Escrow {
+    /// Returns the voting power of the escrow at a time. Power decays linearly to zero
+    /// over the remaining lock duration, capped at the maximum stake duration.
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - The escrow
+    /// * `params` - Parameters of the escrow's locker
+    /// * `time` - Unix timestamp at which power is computed
+    ///
+    pub fn voting_power_at_time(&self, params: &LockerParams, time: i64) -> u64 {
+        if self.escrow_ends_at <= time || time < self.escrow_started_at {
+            return 0;
+        }
+        let seconds_until_expiry =
+            ((self.escrow_ends_at - time) as u64).min(params.max_stake_duration);
+        (self.amount as u128
+            * params.max_stake_vote_multiplier as u128
+            * seconds_until_expiry as u128
+            / params.max_stake_duration as u128) as u64
+    }
+}
+
+/// Implements account traits for a locked voter account, so that it can be loaded with
+/// `Account` after owner and discriminator checks
+macro_rules! locked_voter_account {
+    ($name:ident, $discriminator:expr) => {
+        impl Discriminator for $name {
+            fn discriminator() -> [u8; 8] {
+                $discriminator
+            }
+        }
+
+        impl Owner for $name {
+            fn owner() -> Pubkey {
+                locked_voter_program::ID
+            }
+        }
+
+        impl AccountDeserialize for $name {
+            fn try_deserialize(buf: &mut &[u8]) -> Result<Self> {
+                require!(
+                    buf.len() >= 8 && buf[..8] == $name::discriminator(),
+                    ErrorCode::AccountDiscriminatorMismatch
+                );
+                Self::try_deserialize_unchecked(buf)
+            }
+
+            fn try_deserialize_unchecked(buf: &mut &[u8]) -> Result<Self> {
+                let mut data: &[u8] = &buf[8..];
+                AnchorDeserialize::deserialize(&mut data)
+                    .map_err(|_| ErrorCode::AccountDidNotDeserialize.into())
+            }
+        }
+
+        // Accounts of another program are never written
+        impl AccountSerialize for $name {
+            fn try_serialize<W: Write>(&self, _writer: &mut W) -> Result<()> {
+                Ok(())
+            }
+        }
+    };
+}
+
+locked_voter_account!(Locker, [74, 246, 6, 113, 249, 228, 75, 169]);
+locked_voter_account!(Escrow, [31, 213, 123, 187, 186, 22, 218, 155]);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const DAY: i64 = 86_400;
+
+    fn params() -> LockerParams {
+        LockerParams {
+            max_stake_vote_multiplier: 10,
+            max_stake_duration: 100 * DAY as u64,
+            ..Default::default()
+        }
+    }
+
+    fn escrow(amount: u64, escrow_ends_at: i64) -> Escrow {
+        Escrow {
+            amount,
+            escrow_ends_at,
+            ..Default::default()
+        }
+    }
+
+    mod voting_power_at_time {
+        use super::*;
+
+        #[test]
+        fn is_multiplied_for_the_maximum_duration() {
+            let escrow = escrow(1000, 100 * DAY);
+            assert_eq!(escrow.voting_power_at_time(&params(), 0), 10_000);
+        }
+
+        #[test]
+        fn decays_linearly() {
+            let escrow = escrow(1000, 100 * DAY);
+            assert_eq!(escrow.voting_power_at_time(&params(), 50 * DAY), 5_000);
+            assert_eq!(escrow.voting_power_at_time(&params(), 75 * DAY), 2_500);
+        }
+
+        #[test]
+        fn is_capped_at_the_maximum_duration() {
+            let escrow = escrow(1000, 200 * DAY);
+            assert_eq!(escrow.voting_power_at_time(&params(), 0), 10_000);
+        }
+
+        #[test]
+        fn is_zero_after_the_lock_ends() {
+            let escrow = escrow(1000, 100 * DAY);
+            assert_eq!(escrow.voting_power_at_time(&params(), 100 * DAY), 0);
+            assert_eq!(escrow.voting_power_at_time(&params(), 101 * DAY), 0);
+        }
+    }
+}
@@ -1,14 +1,22 @@
 pub mod factory;
 pub mod fee;
+pub mod hook;
 pub mod oracle;
+pub mod pair;
 pub mod pool;
 pub mod position;
 pub mod tick;
 pub mod tick_bitmap;
 
 // Non fungible position manager
+pub mod operator_approval;
+pub mod position_lock;
 pub mod position_manager;
 pub mod tokenized_position;
 
 // Swap router
 pub mod swap_router;
+
+// Gauge voting
+pub mod gauge;
+pub mod locked_voter;
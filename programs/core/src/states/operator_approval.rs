@@ -0,0 +1,46 @@
+use anchor_lang::prelude::*;
+
+/// Seed to derive account address and signature
+pub const OPERATOR_APPROVAL_SEED: &str = "oa";
+
+/// Approves an operator to manage all position NFTs of an owner
+///
+/// The operator can decrease liquidity, collect tokens and split every position NFT held by
+/// the owner, including NFTs received after the approval. Deleting the account revokes
+/// the approval. Increasing liquidity needs no approval, so an operator can rebalance a
+/// position with `zap_out` and mint the new range to the owner with `zap_in` or
+/// `mint_tokenized_position`.
+///
+/// Operators can't burn position NFTs, since the token program only lets the owner or a
+/// delegate burn. They can't merge positions or burn the NFT in `zap_out`.
+///
+/// PDA of `[OPERATOR_APPROVAL_SEED, owner, operator]`
+///
+#[account(zero_copy)]
+#[derive(Default)]
+#[repr(packed)]
+pub struct OperatorApprovalState {
+    /// Bump to identify PDA
+    pub bump: u8,
+
+    /// The owner of the position NFTs
+    pub owner: Pubkey,
+
+    /// The address approved to act on the position NFTs
+    pub operator: Pubkey,
+}
+
+/// Emitted when an operator is approved or revoked for all position NFTs of an owner
+#[event]
+pub struct ApprovalForAllEvent {
+    /// The owner of the position NFTs
+    #[index]
+    pub owner: Pubkey,
+
+    /// The operator whose approval changed
+    #[index]
+    pub operator: Pubkey,
+
+    /// Whether the operator is approved
+    pub approved: bool,
+}
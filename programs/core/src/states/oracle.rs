@@ -17,6 +17,10 @@ use anchor_lang::prelude::*;
 /// Seed to derive account address and signature
 pub const OBSERVATION_SEED: &str = "o";
 
+/// The shortest window in seconds of a reference TWAP used to price a pool. Shorter windows
+/// are close to the spot price, which can be moved within a block
+pub const MIN_TWAP_WINDOW: u32 = 600;
+
 /// Returns data about a specific observation index
 ///
 /// PDA of `[OBSERVATION_SEED, token_0, token_1, fee, index]`
@@ -126,6 +130,28 @@ impl ObservationState {
     }
 }
 
+/// Returns the time weighted average tick between two observations, rounded towards
+/// negative infinity
+///
+/// # Arguments
+///
+/// * `tick_cumulative_start` - The tick accumulator of the older observation
+/// * `tick_cumulative_end` - The tick accumulator of the newer observation
+/// * `seconds_elapsed` - Seconds between the two observations. Must be greater than 0
+///
+pub fn time_weighted_average_tick(
+    tick_cumulative_start: i64,
+    tick_cumulative_end: i64,
+    seconds_elapsed: u32,
+) -> i32 {
+    let tick_cumulative_delta = tick_cumulative_end - tick_cumulative_start;
+    let mut tick = tick_cumulative_delta / seconds_elapsed as i64;
+    if tick_cumulative_delta < 0 && tick_cumulative_delta % seconds_elapsed as i64 != 0 {
+        tick -= 1;
+    }
+    tick as i32
+}
+
 /// Returns the block timestamp truncated to 32 bits, i.e. mod 2**32
 ///
 pub fn _block_timestamp() -> u32 {
@@ -145,3 +171,40 @@ pub struct IncreaseObservationCardinalityNext {
     /// The updated value of the next observation cardinality
     pub observation_cardinality_next_new: u16,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    mod time_weighted_average_tick {
+        use super::*;
+
+        #[test]
+        fn constant_tick() {
+            assert_eq!(time_weighted_average_tick(0, 100 * 60, 60), 100);
+            assert_eq!(time_weighted_average_tick(0, -100 * 60, 60), -100);
+        }
+
+        #[test]
+        fn relative_to_start() {
+            assert_eq!(time_weighted_average_tick(1_000, 1_000 + 25 * 10, 10), 25);
+        }
+
+        #[test]
+        fn rounds_positive_ticks_down() {
+            assert_eq!(time_weighted_average_tick(0, 19, 10), 1);
+        }
+
+        #[test]
+        fn rounds_negative_ticks_towards_negative_infinity() {
+            assert_eq!(time_weighted_average_tick(0, -19, 10), -2);
+            assert_eq!(time_weighted_average_tick(0, -20, 10), -2);
+        }
+
+        #[test]
+        fn max_and_min_ticks() {
+            assert_eq!(time_weighted_average_tick(0, 221_818 * 3600, 3600), 221_818);
+            assert_eq!(time_weighted_average_tick(0, -221_818 * 3600, 3600), -221_818);
+        }
+    }
+}
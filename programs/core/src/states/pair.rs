@@ -0,0 +1,102 @@
+use crate::error::ErrorCode;
+use anchor_lang::prelude::*;
+
+/// Seed to derive account address and signature
+pub const PAIR_SEED: &str = "pr";
+
+/// The maximum number of fee tiers that can be registered for a token pair
+pub const MAX_FEES_PER_PAIR: usize = 16;
+
+/// Lists the fee tiers of all pools deployed for a token pair
+///
+/// Pool addresses can be derived from the pair and fee, so indexers and routers can
+/// enumerate the pools of a pair without scanning program accounts. Pools created once
+/// `MAX_FEES_PER_PAIR` pools are registered are left out of the registry.
+///
+/// PDA of `[PAIR_SEED, token_0, token_1]`
+///
+#[account(zero_copy)]
+#[derive(Default)]
+#[repr(packed)]
+pub struct PairState {
+    /// Bump to identify PDA
+    pub bump: u8,
+
+    /// Token pair, where token_0 address < token_1 address
+    pub token_0: Pubkey,
+    pub token_1: Pubkey,
+
+    /// The number of pools deployed for the pair
+    pub pool_count: u8,
+
+    /// Fee amounts of the deployed pools in order of creation. Only the first
+    /// `pool_count` elements are valid
+    pub fees: [u32; MAX_FEES_PER_PAIR],
+}
+
+impl PairState {
+    /// Registers the pool with the given fee
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - The pair account
+    /// * `fee` - Fee amount of the new pool
+    ///
+    pub fn add_fee(&mut self, fee: u32) -> Result<()> {
+        let count = self.pool_count as usize;
+        require!(count < MAX_FEES_PER_PAIR, ErrorCode::PairRegistryFull);
+
+        let mut fees = self.fees;
+        fees[count] = fee;
+        self.fees = fees;
+        self.pool_count += 1;
+        Ok(())
+    }
+
+    /// Whether the registry has no space for more pools
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - The pair account
+    ///
+    pub fn is_full(&self) -> bool {
+        self.pool_count as usize >= MAX_FEES_PER_PAIR
+    }
+
+    /// Returns the fee amounts of all pools deployed for the pair
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - The pair account
+    ///
+    pub fn registered_fees(self) -> Vec<u32> {
+        let fees = self.fees;
+        fees[..self.pool_count as usize].to_vec()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn registers_fees_in_order() {
+        let mut pair = PairState::default();
+        assert!(pair.registered_fees().is_empty());
+
+        pair.add_fee(500).unwrap();
+        pair.add_fee(3000).unwrap();
+        assert_eq!(pair.registered_fees(), vec![500, 3000]);
+    }
+
+    #[test]
+    fn fails_when_full() {
+        let mut pair = PairState::default();
+        for fee in 0..MAX_FEES_PER_PAIR as u32 {
+            pair.add_fee(fee).unwrap();
+        }
+        assert!(pair.is_full());
+        assert!(pair.add_fee(10_000).is_err());
+        assert_eq!(pair.registered_fees().len(), MAX_FEES_PER_PAIR);
+    }
+}
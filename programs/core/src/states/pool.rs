@@ -1,6 +1,8 @@
 use crate::{
+    error::ErrorCode,
     program::CyclosCore,
     states::{
+        hook,
         oracle::{self, OBSERVATION_SEED},
         position::POSITION_SEED,
         tick::TICK_SEED,
@@ -69,9 +71,33 @@ pub struct PoolState {
     pub protocol_fees_token_1: u64,
     // Whether the pool is currently locked to reentrancy
     pub unlocked: bool,
+
+    /// The program invoked by hooks, or the default address if the pool has no hooks
+    pub hook_program: Pubkey,
+
+    /// Bitmask of the hooks enabled for the pool
+    pub hook_flags: u8,
+
+    /// The number of initialized ticks. A pool without initialized ticks has no liquidity
+    /// in any range, so its price can be reinitialized
+    pub initialized_tick_count: u32,
+
+    /// Whether initialized ticks have been counted since pool creation. False for pools
+    /// migrated from an older layout, whose count is unknown
+    pub tick_count_tracked: bool,
 }
 
 impl PoolState {
+    /// Whether the given hook is enabled for the pool
+    ///
+    /// # Arguments
+    /// * `self` - A pool account
+    /// * `flag` - One or more hook flags
+    ///
+    pub fn has_hook(&self, flag: u8) -> bool {
+        self.hook_flags & flag != 0
+    }
+
     /// Returns the observation index after the currently active one in a liquidity pool
     ///
     /// # Arguments
@@ -81,6 +107,30 @@ impl PoolState {
         (self.observation_index + 1) % self.observation_cardinality_next
     }
 
+    /// Returns the next observation account passed to a liquidity change. It is the first
+    /// remaining account, following the hook program if the pool has position hooks
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - The pool of the liquidity change
+    /// * `remaining_accounts` - Remaining accounts passed to the liquidity change
+    ///
+    pub fn next_observation_account<'a, 'info>(
+        self,
+        remaining_accounts: &'a [AccountInfo<'info>],
+    ) -> Result<&'a AccountInfo<'info>> {
+        let remaining_accounts = if self
+            .has_hook(hook::BEFORE_MODIFY_POSITION_FLAG | hook::AFTER_MODIFY_POSITION_FLAG)
+        {
+            hook::split_hook_program(self.hook_program, remaining_accounts)?.1
+        } else {
+            remaining_accounts
+        };
+        Ok(remaining_accounts
+            .first()
+            .ok_or(ErrorCode::MissingObservationAccount)?)
+    }
+
     /// Validates the public key of an observation account
     ///
     /// # Arguments
@@ -96,6 +146,24 @@ impl PoolState {
         } else {
             self.observation_index
         };
+        self.validate_observation_address_at_index(key, bump, index)
+    }
+
+    /// Validates the public key of an observation account at any index of the oracle array
+    ///
+    /// # Arguments
+    ///
+    /// * `self`- The pool to which the account belongs
+    /// * `key` - The address to validated
+    /// * `bump` - The PDA bump for the address
+    /// * `index` - The index of the observation in the oracle array
+    ///
+    pub fn validate_observation_address_at_index(
+        self,
+        key: &Pubkey,
+        bump: u8,
+        index: u16,
+    ) -> Result<()> {
         let seeds = [
             &OBSERVATION_SEED.as_bytes(),
             self.token_0.as_ref(),
@@ -118,23 +186,33 @@ impl PoolState {
     /// * `tick` - The tick from which the address should be derived
     ///
     pub fn validate_tick_address(self, key: &Pubkey, bump: u8, tick: i32) -> Result<()> {
-        assert!(
-            *key == Pubkey::create_program_address(
-                &[
-                    &TICK_SEED.as_bytes(),
-                    self.token_0.as_ref(),
-                    self.token_1.as_ref(),
-                    &self.fee.to_be_bytes(),
-                    &tick.to_be_bytes(),
-                    &[bump],
-                ],
-                &CyclosCore::id(),
-            )
-            .unwrap(),
-        );
+        assert!(self.is_tick_address(key, bump, tick));
         Ok(())
     }
 
+    /// Whether the public key belongs to the tick account of the pool
+    ///
+    /// # Arguments
+    ///
+    /// * `self`- The pool to which the account belongs
+    /// * `key` - The address to check
+    /// * `bump` - The PDA bump for the address
+    /// * `tick` - The tick from which the address should be derived
+    ///
+    pub fn is_tick_address(self, key: &Pubkey, bump: u8, tick: i32) -> bool {
+        Pubkey::create_program_address(
+            &[
+                &TICK_SEED.as_bytes(),
+                self.token_0.as_ref(),
+                self.token_1.as_ref(),
+                &self.fee.to_be_bytes(),
+                &tick.to_be_bytes(),
+                &[bump],
+            ],
+            &CyclosCore::id(),
+        ) == Ok(*key)
+    }
+
     /// Validates the public key of a bitmap account
     ///
     /// # Arguments
@@ -145,23 +223,33 @@ impl PoolState {
     /// * `tick` - The tick from which the address should be derived
     ///
     pub fn validate_bitmap_address(self, key: &Pubkey, bump: u8, word_pos: i16) -> Result<()> {
-        assert!(
-            *key == Pubkey::create_program_address(
-                &[
-                    &BITMAP_SEED.as_bytes(),
-                    self.token_0.as_ref(),
-                    self.token_1.as_ref(),
-                    &self.fee.to_be_bytes(),
-                    &word_pos.to_be_bytes(),
-                    &[bump],
-                ],
-                &CyclosCore::id(),
-            )
-            .unwrap(),
-        );
+        assert!(self.is_bitmap_address(key, bump, word_pos));
         Ok(())
     }
 
+    /// Whether the public key belongs to the bitmap account of the pool
+    ///
+    /// # Arguments
+    ///
+    /// * `self`- The pool to which the account belongs
+    /// * `key` - The address to check
+    /// * `bump` - The PDA bump for the address
+    /// * `word_pos` - The bitmap word from which the address should be derived
+    ///
+    pub fn is_bitmap_address(self, key: &Pubkey, bump: u8, word_pos: i16) -> bool {
+        Pubkey::create_program_address(
+            &[
+                &BITMAP_SEED.as_bytes(),
+                self.token_0.as_ref(),
+                self.token_1.as_ref(),
+                &self.fee.to_be_bytes(),
+                &word_pos.to_be_bytes(),
+                &[bump],
+            ],
+            &CyclosCore::id(),
+        ) == Ok(*key)
+    }
+
     /// Validates the public key of a bitmap account
     ///
     /// # Arguments
@@ -296,6 +384,30 @@ pub struct PoolCreatedAndInitialized {
 
     /// The initial tick of the pool, i.e. log base 1.0001 of the starting price of the pool
     pub tick: i32,
+
+    /// The program invoked by the pool's hooks
+    pub hook_program: Pubkey,
+
+    /// Bitmask of the hooks enabled for the pool
+    pub hook_flags: u8,
+}
+
+/// Emitted when the price of an empty pool is reinitialized
+///
+#[event]
+pub struct PriceReinitialized {
+    /// The pool whose price is reinitialized
+    #[index]
+    pub pool_state: Pubkey,
+
+    /// The previous sqrt price of the pool, as a Q32.32
+    pub sqrt_price_x32_old: u64,
+
+    /// The new sqrt price of the pool, as a Q32.32
+    pub sqrt_price_x32: u64,
+
+    /// The new tick of the pool
+    pub tick: i32,
 }
 
 /// Emitted when the collected protocol fees are withdrawn by the factory owner
@@ -349,6 +461,9 @@ pub struct SwapEvent {
     /// The delta of the token_1 balance of the pool
     pub amount_1: i64,
 
+    /// The sqrt(price) of the pool before the swap, as a Q32.32
+    pub sqrt_price_start_x32: u64,
+
     /// The sqrt(price) of the pool after the swap, as a Q32.32
     pub sqrt_price_x32: u64,
 
@@ -357,4 +472,114 @@ pub struct SwapEvent {
 
     /// The log base 1.0001 of price of the pool after the swap
     pub tick: i32,
+
+    /// The amount left unswapped. Zero unless a partial fill ran out of supplied accounts,
+    /// or the swap reached the price limit
+    pub amount_specified_remaining: i64,
+
+    /// The token_0 fee paid to liquidity providers
+    pub lp_fee_0: u64,
+
+    /// The token_1 fee paid to liquidity providers
+    pub lp_fee_1: u64,
+
+    /// The token_0 fee owed to the protocol
+    pub protocol_fee_0: u64,
+
+    /// The token_1 fee owed to the protocol
+    pub protocol_fee_1: u64,
+
+    /// The number of initialized ticks crossed by the swap
+    pub ticks_crossed: u32,
+}
+
+/// Return data of a swap
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct SwapResult {
+    /// The delta of the token_0 balance of the pool
+    pub amount_0: i64,
+
+    /// The delta of the token_1 balance of the pool
+    pub amount_1: i64,
+
+    /// The amount left unswapped by a partial fill, or at the price limit
+    pub amount_specified_remaining: i64,
+
+    /// The sqrt(price) of the pool after the swap, as a Q32.32
+    pub sqrt_price_x32: u64,
+
+    /// The log base 1.0001 of price of the pool after the swap
+    pub tick: i32,
+}
+
+/// Return data of mints, burns and collects
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct TokenAmounts {
+    /// The amount of token_0 paid, owed or collected
+    pub amount_0: u64,
+
+    /// The amount of token_1 paid, owed or collected
+    pub amount_1: u64,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    mod next_observation_account {
+        use super::*;
+
+        #[test]
+        fn skips_the_hook_program() {
+            let hook_program = Pubkey::new_unique();
+            let observation = Pubkey::new_unique();
+            let owner = Pubkey::default();
+            let (mut lamports_0, mut lamports_1) = (0, 0);
+            let (mut data_0, mut data_1) = (vec![], vec![]);
+            let accounts = [
+                AccountInfo::new(
+                    &hook_program,
+                    false,
+                    false,
+                    &mut lamports_0,
+                    &mut data_0,
+                    &owner,
+                    true,
+                    0,
+                ),
+                AccountInfo::new(
+                    &observation,
+                    false,
+                    true,
+                    &mut lamports_1,
+                    &mut data_1,
+                    &owner,
+                    false,
+                    0,
+                ),
+            ];
+
+            let pool = PoolState::default();
+            assert_eq!(
+                pool.next_observation_account(&accounts).unwrap().key(),
+                hook_program
+            );
+
+            let pool = PoolState {
+                hook_program,
+                hook_flags: hook::AFTER_MODIFY_POSITION_FLAG,
+                ..Default::default()
+            };
+            assert_eq!(
+                pool.next_observation_account(&accounts).unwrap().key(),
+                observation
+            );
+            assert!(pool.next_observation_account(&accounts[..1]).is_err());
+        }
+
+        #[test]
+        fn fails_without_remaining_accounts() {
+            assert!(PoolState::default().next_observation_account(&[]).is_err());
+        }
+    }
 }
@@ -85,6 +85,30 @@ impl PositionState {
 
         Ok(())
     }
+
+    /// Moves the liquidity and tokens owed of a position to the position of another owner
+    /// with the same tick boundaries
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - The position to move from
+    /// * `recipient` - The position to move into. Must have no liquidity
+    ///
+    pub fn transfer_to(&mut self, recipient: &mut PositionState) -> Result<()> {
+        require!(recipient.liquidity == 0, ErrorCode::PositionHasLiquidity);
+
+        recipient.liquidity = self.liquidity;
+        recipient.fee_growth_inside_0_last_x32 = self.fee_growth_inside_0_last_x32;
+        recipient.fee_growth_inside_1_last_x32 = self.fee_growth_inside_1_last_x32;
+        recipient.tokens_owed_0 += self.tokens_owed_0;
+        recipient.tokens_owed_1 += self.tokens_owed_1;
+
+        self.liquidity = 0;
+        self.tokens_owed_0 = 0;
+        self.tokens_owed_1 = 0;
+
+        Ok(())
+    }
 }
 
 /// Emitted when liquidity is minted for a given position
@@ -172,3 +196,28 @@ pub struct CollectEvent {
     /// The amount of token_1 fees collected
     pub amount_1: u64,
 }
+
+/// Emitted when a position is transferred to a new owner
+#[event]
+pub struct TransferPositionEvent {
+    /// The pool of the position
+    #[index]
+    pub pool_state: Pubkey,
+
+    /// The previous owner of the position
+    pub owner: Pubkey,
+
+    /// The new owner of the position
+    pub new_owner: Pubkey,
+
+    /// The lower tick of the position
+    #[index]
+    pub tick_lower: i32,
+
+    /// The upper tick of the position
+    #[index]
+    pub tick_upper: i32,
+
+    /// The liquidity moved to the new owner
+    pub liquidity: u64,
+}
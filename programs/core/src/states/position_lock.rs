@@ -0,0 +1,147 @@
+use crate::error::ErrorCode;
+use anchor_lang::prelude::*;
+
+/// Seed to derive account address and signature
+pub const POSITION_LOCK_SEED: &str = "lk";
+
+/// Escrow holding a position NFT until an unlock time
+///
+/// The lock owner is an approved operator of the escrow, so fees of the position can be
+/// collected while it is locked. Liquidity cannot be increased or decreased until the NFT
+/// is unlocked.
+///
+/// PDA of `[POSITION_LOCK_SEED, mint_address]`
+///
+#[account(zero_copy)]
+#[derive(Default)]
+#[repr(packed)]
+pub struct PositionLockState {
+    /// Bump to identify PDA
+    pub bump: u8,
+
+    /// Mint address of the locked position NFT
+    pub mint: Pubkey,
+
+    /// The address that can collect fees, extend the lock and withdraw the NFT on unlock
+    pub owner: Pubkey,
+
+    /// Unix timestamp from which the NFT can be withdrawn
+    pub unlock_time: i64,
+}
+
+impl PositionLockState {
+    /// Pushes back the unlock time. Locks can only be extended
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - The lock to extend
+    /// * `unlock_time` - The new unlock time, later than the current one
+    ///
+    pub fn extend(&mut self, unlock_time: i64) -> Result<()> {
+        require!(unlock_time > self.unlock_time, ErrorCode::InvalidUnlockTime);
+        self.unlock_time = unlock_time;
+        Ok(())
+    }
+
+    /// Whether the NFT can be withdrawn at the given time
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - The lock
+    /// * `time` - The current unix timestamp
+    ///
+    pub fn is_unlocked(&self, time: i64) -> bool {
+        time >= self.unlock_time
+    }
+}
+
+/// Returns the address of the lock escrow for a position NFT
+///
+/// # Arguments
+///
+/// * `mint` - Mint address of the position NFT
+///
+pub fn position_lock_address(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[POSITION_LOCK_SEED.as_bytes(), mint.as_ref()], &crate::ID).0
+}
+
+/// Emitted when a position NFT is locked
+#[event]
+pub struct PositionLockedEvent {
+    /// The locked position NFT
+    #[index]
+    pub token_id: Pubkey,
+
+    /// The owner of the lock
+    #[index]
+    pub owner: Pubkey,
+
+    /// Unix timestamp from which the NFT can be withdrawn
+    pub unlock_time: i64,
+}
+
+/// Emitted when the unlock time of a position NFT is pushed back
+#[event]
+pub struct LockExtendedEvent {
+    /// The locked position NFT
+    #[index]
+    pub token_id: Pubkey,
+
+    /// The new unlock time
+    pub unlock_time: i64,
+}
+
+/// Emitted when a position NFT is withdrawn from its lock
+#[event]
+pub struct PositionUnlockedEvent {
+    /// The unlocked position NFT
+    #[index]
+    pub token_id: Pubkey,
+
+    /// The owner of the lock, who received the NFT
+    #[index]
+    pub owner: Pubkey,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lock(unlock_time: i64) -> PositionLockState {
+        PositionLockState {
+            unlock_time,
+            ..Default::default()
+        }
+    }
+
+    mod extend {
+        use super::*;
+
+        #[test]
+        fn pushes_back_the_unlock_time() {
+            let mut lock = lock(100);
+            lock.extend(200).unwrap();
+            assert_eq!({ lock.unlock_time }, 200);
+        }
+
+        #[test]
+        fn fails_for_an_earlier_or_same_time() {
+            let mut lock = lock(100);
+            assert!(lock.extend(100).is_err());
+            assert!(lock.extend(50).is_err());
+            assert_eq!({ lock.unlock_time }, 100);
+        }
+    }
+
+    mod is_unlocked {
+        use super::*;
+
+        #[test]
+        fn unlocks_at_the_unlock_time() {
+            let lock = lock(100);
+            assert!(!lock.is_unlocked(99));
+            assert!(lock.is_unlocked(100));
+            assert!(lock.is_unlocked(101));
+        }
+    }
+}
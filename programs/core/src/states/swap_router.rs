@@ -10,3 +10,67 @@ pub struct SwapRouterState {
     /// Cache for exact output swaps
     pub amount_in_cached: u64,
 }
+
+/// Return data of exact input swaps
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct ExactInputResult {
+    /// The amount of the input token paid
+    pub amount_in: u64,
+
+    /// The amount of the output token received
+    pub amount_out: u64,
+
+    /// The sqrt(price) of the last pool after the swap, as a Q32.32
+    pub sqrt_price_x32: u64,
+
+    /// The tick of the last pool after the swap
+    pub tick: i32,
+}
+
+/// Parameters of a hop in a multi-hop swap
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct SwapHop {
+    /// The number of observation, bitmap and tick accounts passed for this hop
+    pub additional_accounts: u8,
+
+    /// The Q32.32 sqrt price limit of the hop. Zero for no limit. Hops after the first
+    /// fail if the limit stops them before their whole input is swapped
+    pub sqrt_price_limit_x32: u64,
+
+    /// The minimum output of the hop, which serves as a slippage check on the
+    /// intermediate pool
+    pub amount_out_minimum: u64,
+}
+
+/// A leg of a split swap, performed in one of several pools for the same token pair
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct SplitLeg {
+    /// The share of the input amount swapped in this leg, in basis points. Must be positive
+    pub share_bps: u16,
+
+    /// The number of bitmap, tick and next observation accounts passed for this leg
+    pub additional_accounts: u8,
+}
+
+/// Emitted by the router when one input is split across several pools
+#[event]
+pub struct SplitSwapEvent {
+    /// The user performing the swap
+    #[index]
+    pub signer: Pubkey,
+
+    /// The pools in which each leg was performed
+    pub pools: Vec<Pubkey>,
+
+    /// The input amount of each leg
+    pub amounts_in: Vec<u64>,
+
+    /// The output amount of each leg
+    pub amounts_out: Vec<u64>,
+
+    /// The total amount of the input token paid
+    pub amount_in: u64,
+
+    /// The total amount of the output token received
+    pub amount_out: u64,
+}
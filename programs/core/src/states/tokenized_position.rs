@@ -1,4 +1,9 @@
+use crate::error::ErrorCode;
+use crate::libraries::fixed_point_32;
+use crate::libraries::full_math::MulDiv;
+use crate::libraries::{liquidity_amounts, tick_math};
 use anchor_lang::prelude::*;
+use mpl_token_metadata::state::MAX_NAME_LENGTH;
 
 /// Position wrapped as an SPL non-fungible token
 ///
@@ -39,6 +44,236 @@ pub struct TokenizedPositionState {
     pub tokens_owed_1: u64,
 }
 
+impl TokenizedPositionState {
+    /// Credits the fees earned by the position since the last action, as of the fee growth
+    /// recorded by the core position
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - The tokenized position
+    /// * `fee_growth_inside_0_last_x32` - The token_0 fee growth of the core position
+    /// * `fee_growth_inside_1_last_x32` - The token_1 fee growth of the core position
+    ///
+    pub fn update_tokens_owed(
+        &mut self,
+        fee_growth_inside_0_last_x32: u64,
+        fee_growth_inside_1_last_x32: u64,
+    ) {
+        let liquidity = self.liquidity;
+        self.tokens_owed_0 += (fee_growth_inside_0_last_x32 - self.fee_growth_inside_0_last_x32)
+            .mul_div_floor(liquidity, fixed_point_32::Q32)
+            .unwrap();
+        self.tokens_owed_1 += (fee_growth_inside_1_last_x32 - self.fee_growth_inside_1_last_x32)
+            .mul_div_floor(liquidity, fixed_point_32::Q32)
+            .unwrap();
+        self.fee_growth_inside_0_last_x32 = fee_growth_inside_0_last_x32;
+        self.fee_growth_inside_1_last_x32 = fee_growth_inside_1_last_x32;
+    }
+
+    /// Returns a fraction of the position liquidity, rounded down
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - The tokenized position
+    /// * `liquidity_bps` - The fraction in basis points, between 1 and 10000. Must not round
+    /// down to zero liquidity
+    ///
+    pub fn liquidity_for_bps(&self, liquidity_bps: u16) -> Result<u64> {
+        require!(
+            liquidity_bps > 0 && liquidity_bps <= 10_000,
+            ErrorCode::InvalidBps
+        );
+        let liquidity = (self.liquidity as u128 * liquidity_bps as u128 / 10_000) as u64;
+        require!(liquidity > 0, ErrorCode::InvalidBps);
+        Ok(liquidity)
+    }
+
+    /// Moves part of the liquidity, and the same share of tokens owed, into a new position
+    /// with the same range. Fees must be up to date in the position being split.
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - The position to split
+    /// * `recipient` - The new position. Its bump and mint are left unchanged
+    /// * `liquidity` - The liquidity to move, less than the liquidity of the position
+    ///
+    pub fn split_into(
+        &mut self,
+        recipient: &mut TokenizedPositionState,
+        liquidity: u64,
+    ) -> Result<()> {
+        let total_liquidity = self.liquidity;
+        require!(
+            liquidity > 0 && liquidity < total_liquidity,
+            ErrorCode::InvalidLiquiditySplit
+        );
+
+        let tokens_owed_0 = self
+            .tokens_owed_0
+            .mul_div_floor(liquidity, total_liquidity)
+            .unwrap();
+        let tokens_owed_1 = self
+            .tokens_owed_1
+            .mul_div_floor(liquidity, total_liquidity)
+            .unwrap();
+
+        recipient.pool_id = self.pool_id;
+        recipient.tick_lower = self.tick_lower;
+        recipient.tick_upper = self.tick_upper;
+        recipient.liquidity = liquidity;
+        recipient.fee_growth_inside_0_last_x32 = self.fee_growth_inside_0_last_x32;
+        recipient.fee_growth_inside_1_last_x32 = self.fee_growth_inside_1_last_x32;
+        recipient.tokens_owed_0 = tokens_owed_0;
+        recipient.tokens_owed_1 = tokens_owed_1;
+
+        self.liquidity -= liquidity;
+        self.tokens_owed_0 -= tokens_owed_0;
+        self.tokens_owed_1 -= tokens_owed_1;
+
+        Ok(())
+    }
+
+    /// Moves the liquidity and tokens owed of another position with the same range into this
+    /// position. Fees must be up to date in both positions.
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - The position to merge into
+    /// * `source` - The position to merge. Left with no liquidity or tokens owed
+    ///
+    pub fn merge(&mut self, source: &mut TokenizedPositionState) -> Result<()> {
+        require!(
+            self.pool_id == source.pool_id
+                && self.tick_lower == source.tick_lower
+                && self.tick_upper == source.tick_upper,
+            ErrorCode::PositionMismatch
+        );
+
+        self.liquidity += source.liquidity;
+        self.tokens_owed_0 += source.tokens_owed_0;
+        self.tokens_owed_1 += source.tokens_owed_1;
+
+        source.liquidity = 0;
+        source.tokens_owed_0 = 0;
+        source.tokens_owed_1 = 0;
+
+        Ok(())
+    }
+
+    /// Values the position at a sqrt price, with the fees it has earned as of the current
+    /// fee growth inside its range
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - The position to value
+    /// * `sqrt_price_x32` - The Q32.32 sqrt price at which liquidity is valued
+    /// * `fee_growth_inside_0_x32` - The current token_0 fee growth inside the position range
+    /// * `fee_growth_inside_1_x32` - The current token_1 fee growth inside the position range
+    ///
+    pub fn value(
+        &self,
+        sqrt_price_x32: u64,
+        fee_growth_inside_0_x32: u64,
+        fee_growth_inside_1_x32: u64,
+    ) -> Result<PositionValue> {
+        let (amount_0, amount_1) = liquidity_amounts::get_amounts_for_liquidity(
+            sqrt_price_x32,
+            tick_math::get_sqrt_ratio_at_tick(self.tick_lower)?,
+            tick_math::get_sqrt_ratio_at_tick(self.tick_upper)?,
+            self.liquidity,
+        );
+
+        let mut position = *self;
+        position.update_tokens_owed(fee_growth_inside_0_x32, fee_growth_inside_1_x32);
+
+        Ok(PositionValue {
+            sqrt_price_x32,
+            amount_0,
+            amount_1,
+            fees_0: position.tokens_owed_0,
+            fees_1: position.tokens_owed_1,
+        })
+    }
+}
+
+/// Returns a descriptive name for a position NFT with the pair, fee tier and tick range,
+/// such as `So11/EPjF 0.05% -10<>10`
+///
+/// Mint addresses are shortened to their first four characters. The name is truncated to
+/// the length allowed by Metaplex.
+///
+/// # Arguments
+///
+/// * `token_0` - The mint address of token_0 of the pool
+/// * `token_1` - The mint address of token_1 of the pool
+/// * `fee` - The fee of the pool, in hundredths of a bip
+/// * `tick_lower` - The lower tick of the position
+/// * `tick_upper` - The upper tick of the position
+///
+pub fn position_name(
+    token_0: &Pubkey,
+    token_1: &Pubkey,
+    fee: u32,
+    tick_lower: i32,
+    tick_upper: i32,
+) -> String {
+    let mut name = format!(
+        "{}/{} {}% {}<>{}",
+        &token_0.to_string()[..4],
+        &token_1.to_string()[..4],
+        fee_percent(fee),
+        tick_lower,
+        tick_upper
+    );
+    name.truncate(MAX_NAME_LENGTH);
+    name
+}
+
+/// Formats a fee in hundredths of a bip as a percentage without trailing zeros
+fn fee_percent(fee: u32) -> String {
+    let whole = fee / 10_000;
+    let fraction = fee % 10_000;
+    if fraction == 0 {
+        whole.to_string()
+    } else {
+        format!("{}.{:04}", whole, fraction)
+            .trim_end_matches('0')
+            .to_string()
+    }
+}
+
+/// Return data of position NFT valuations
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct PositionValue {
+    /// The Q32.32 sqrt price at which liquidity was valued
+    pub sqrt_price_x32: u64,
+
+    /// The amount of token_0 the liquidity of the position is worth
+    pub amount_0: u64,
+
+    /// The amount of token_1 the liquidity of the position is worth
+    pub amount_1: u64,
+
+    /// The uncollected token_0 fees of the position, including tokens owed
+    pub fees_0: u64,
+
+    /// The uncollected token_1 fees of the position, including tokens owed
+    pub fees_1: u64,
+}
+
+/// Return data of liquidity increases and decreases for a position NFT
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct LiquidityChange {
+    /// The change in liquidity
+    pub liquidity: u64,
+
+    /// The amount of token_0 paid for, or accounted for, the change
+    pub amount_0: u64,
+
+    /// The amount of token_1 paid for, or accounted for, the change
+    pub amount_1: u64,
+}
+
 /// Emitted when liquidity is increased for a position NFT.
 /// Also emitted when a token is minted
 #[event]
@@ -94,3 +329,250 @@ pub struct CollectTokenizedEvent {
     /// The amount of token_1 owed to the position that was collected
     pub amount_1: u64,
 }
+
+/// Emitted when a position NFT is split into two positions with the same range
+#[event]
+pub struct SplitTokenizedPositionEvent {
+    /// The ID of the token that was split
+    #[index]
+    pub token_id: Pubkey,
+
+    /// The ID of the token minted for the split off position
+    #[index]
+    pub new_token_id: Pubkey,
+
+    /// The liquidity moved to the new position
+    pub liquidity: u64,
+}
+
+/// Emitted when a position NFT is merged into another position with the same range
+#[event]
+pub struct MergeTokenizedPositionsEvent {
+    /// The ID of the token that received the liquidity
+    #[index]
+    pub token_id: Pubkey,
+
+    /// The ID of the token that was merged and burned
+    #[index]
+    pub source_token_id: Pubkey,
+
+    /// The liquidity moved from the merged position
+    pub liquidity: u64,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn position(liquidity: u64, tokens_owed_0: u64, tokens_owed_1: u64) -> TokenizedPositionState {
+        TokenizedPositionState {
+            pool_id: Pubkey::new_unique(),
+            tick_lower: -10,
+            tick_upper: 10,
+            liquidity,
+            fee_growth_inside_0_last_x32: 5,
+            fee_growth_inside_1_last_x32: 7,
+            tokens_owed_0,
+            tokens_owed_1,
+            ..Default::default()
+        }
+    }
+
+    mod update_tokens_owed {
+        use super::*;
+
+        #[test]
+        fn credits_fees_for_the_liquidity() {
+            let mut position = position(1000, 0, 0);
+            position.update_tokens_owed(5 + fixed_point_32::Q32, 7 + 2 * fixed_point_32::Q32);
+            assert_eq!({ position.tokens_owed_0 }, 1000);
+            assert_eq!({ position.tokens_owed_1 }, 2000);
+            assert_eq!(
+                { position.fee_growth_inside_0_last_x32 },
+                5 + fixed_point_32::Q32
+            );
+            assert_eq!(
+                { position.fee_growth_inside_1_last_x32 },
+                7 + 2 * fixed_point_32::Q32
+            );
+        }
+    }
+
+    mod liquidity_for_bps {
+        use super::*;
+
+        #[test]
+        fn rounds_down() {
+            assert_eq!(position(1000, 0, 0).liquidity_for_bps(3333).unwrap(), 333);
+            assert_eq!(
+                position(1000, 0, 0).liquidity_for_bps(10_000).unwrap(),
+                1000
+            );
+        }
+
+        #[test]
+        fn fails_for_bps_out_of_range() {
+            assert!(position(1000, 0, 0).liquidity_for_bps(0).is_err());
+            assert!(position(1000, 0, 0).liquidity_for_bps(10_001).is_err());
+        }
+
+        #[test]
+        fn fails_if_rounded_to_zero() {
+            assert!(position(99, 0, 0).liquidity_for_bps(100).is_err());
+            assert_eq!(position(100, 0, 0).liquidity_for_bps(100).unwrap(), 1);
+        }
+    }
+
+    mod split_into {
+        use super::*;
+
+        #[test]
+        fn moves_a_proportional_share() {
+            let mut position = position(1000, 101, 50);
+            let mut recipient = TokenizedPositionState::default();
+            position.split_into(&mut recipient, 250).unwrap();
+
+            assert_eq!({ recipient.pool_id }, { position.pool_id });
+            assert_eq!({ recipient.tick_lower }, -10);
+            assert_eq!({ recipient.tick_upper }, 10);
+            assert_eq!({ recipient.fee_growth_inside_0_last_x32 }, 5);
+            assert_eq!({ recipient.fee_growth_inside_1_last_x32 }, 7);
+            assert_eq!({ recipient.liquidity }, 250);
+            assert_eq!({ recipient.tokens_owed_0 }, 25);
+            assert_eq!({ recipient.tokens_owed_1 }, 12);
+
+            assert_eq!({ position.liquidity }, 750);
+            assert_eq!({ position.tokens_owed_0 }, 76);
+            assert_eq!({ position.tokens_owed_1 }, 38);
+        }
+
+        #[test]
+        fn fails_for_zero_or_all_liquidity() {
+            let mut position = position(1000, 0, 0);
+            let mut recipient = TokenizedPositionState::default();
+            assert!(position.split_into(&mut recipient, 0).is_err());
+            assert!(position.split_into(&mut recipient, 1000).is_err());
+            assert_eq!({ position.liquidity }, 1000);
+        }
+    }
+
+    mod merge {
+        use super::*;
+
+        #[test]
+        fn adds_liquidity_and_tokens_owed() {
+            let mut position = position(1000, 10, 20);
+            let mut source = position;
+            source.liquidity = 500;
+            source.tokens_owed_0 = 1;
+            source.tokens_owed_1 = 2;
+            position.merge(&mut source).unwrap();
+
+            assert_eq!({ position.liquidity }, 1500);
+            assert_eq!({ position.tokens_owed_0 }, 11);
+            assert_eq!({ position.tokens_owed_1 }, 22);
+            assert_eq!({ source.liquidity }, 0);
+            assert_eq!({ source.tokens_owed_0 }, 0);
+            assert_eq!({ source.tokens_owed_1 }, 0);
+        }
+
+        #[test]
+        fn fails_for_different_ranges() {
+            let mut position = position(1000, 0, 0);
+            let mut source = position;
+            source.tick_upper = 20;
+            assert!(position.merge(&mut source).is_err());
+
+            let mut source = position;
+            source.pool_id = Pubkey::new_unique();
+            assert!(position.merge(&mut source).is_err());
+        }
+    }
+
+    mod value {
+        use super::*;
+
+        #[test]
+        fn holds_both_tokens_in_range() {
+            let position = position(1_000_000, 0, 0);
+            let value = position.value(fixed_point_32::Q32, 5, 7).unwrap();
+            assert_eq!(value.sqrt_price_x32, fixed_point_32::Q32);
+            assert!(value.amount_0 > 0);
+            assert!(value.amount_1 > 0);
+            assert_eq!(value.fees_0, 0);
+            assert_eq!(value.fees_1, 0);
+        }
+
+        #[test]
+        fn holds_a_single_token_out_of_range() {
+            let position = position(1_000_000, 0, 0);
+
+            let below = tick_math::get_sqrt_ratio_at_tick(-20).unwrap();
+            let value = position.value(below, 5, 7).unwrap();
+            assert!(value.amount_0 > 0);
+            assert_eq!(value.amount_1, 0);
+
+            let above = tick_math::get_sqrt_ratio_at_tick(20).unwrap();
+            let value = position.value(above, 5, 7).unwrap();
+            assert_eq!(value.amount_0, 0);
+            assert!(value.amount_1 > 0);
+        }
+
+        #[test]
+        fn adds_uncollected_fees_to_tokens_owed() {
+            let position = position(1000, 10, 20);
+            let value = position
+                .value(
+                    fixed_point_32::Q32,
+                    5 + fixed_point_32::Q32,
+                    7 + 2 * fixed_point_32::Q32,
+                )
+                .unwrap();
+            assert_eq!(value.fees_0, 1010);
+            assert_eq!(value.fees_1, 2020);
+
+            // The position itself is left unchanged
+            assert_eq!({ position.tokens_owed_0 }, 10);
+            assert_eq!({ position.fee_growth_inside_0_last_x32 }, 5);
+        }
+    }
+
+    mod position_name {
+        use super::*;
+
+        #[test]
+        fn formats_fee_tiers() {
+            assert_eq!(fee_percent(100), "0.01");
+            assert_eq!(fee_percent(500), "0.05");
+            assert_eq!(fee_percent(3000), "0.3");
+            assert_eq!(fee_percent(10_000), "1");
+            assert_eq!(fee_percent(12_345), "1.2345");
+        }
+
+        #[test]
+        fn includes_pair_fee_and_range() {
+            let token_0 = Pubkey::new_unique();
+            let token_1 = Pubkey::new_unique();
+            assert_eq!(
+                position_name(&token_0, &token_1, 500, -10, 10),
+                format!(
+                    "{}/{} 0.05% -10<>10",
+                    &token_0.to_string()[..4],
+                    &token_1.to_string()[..4]
+                )
+            );
+        }
+
+        #[test]
+        fn fits_metaplex_name_length() {
+            let name = position_name(
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                1,
+                -221_818,
+                -221_817,
+            );
+            assert_eq!(name.len(), MAX_NAME_LENGTH);
+        }
+    }
+}
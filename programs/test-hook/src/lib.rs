@@ -0,0 +1,82 @@
+//! Hook program for the cyclos-core tests
+//!
+//! Implements every hook of `cyclos_core::states::hook` as a no-op, so tests can check
+//! which hooks a pool invokes from the instruction logs.
+//!
+use anchor_lang::prelude::*;
+
+declare_id!("HfLXFN8ShpntLc2PWBeBTKW5F4mJpVe6dWbrUDHQ44AB");
+
+#[program]
+pub mod test_hook {
+    use super::*;
+
+    #[allow(unused_variables)]
+    pub fn before_swap_hook(
+        _ctx: Context<SwapHook>,
+        zero_for_one: bool,
+        amount_specified: i64,
+        sqrt_price_limit_x32: u64,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    pub fn after_swap_hook(
+        _ctx: Context<SwapHook>,
+        amount_0: i64,
+        amount_1: i64,
+        sqrt_price_x32: u64,
+        tick: i32,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    pub fn before_modify_position_hook(
+        _ctx: Context<ModifyPositionHook>,
+        tick_lower: i32,
+        tick_upper: i32,
+        liquidity_delta: i64,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    pub fn after_modify_position_hook(
+        _ctx: Context<ModifyPositionHook>,
+        tick_lower: i32,
+        tick_upper: i32,
+        liquidity_delta: i64,
+        amount_0: i64,
+        amount_1: i64,
+    ) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct SwapHook<'info> {
+    /// The address that initiated the swap
+    /// CHECK: Passed without signer privileges
+    pub sender: UncheckedAccount<'info>,
+
+    /// The pool in which the swap is performed
+    /// CHECK: Passed read only by the core program
+    pub pool_state: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ModifyPositionHook<'info> {
+    /// The address that initiated the liquidity change
+    /// CHECK: Passed without signer privileges
+    pub sender: UncheckedAccount<'info>,
+
+    /// The owner of the position
+    /// CHECK: Passed read only by the core program
+    pub owner: UncheckedAccount<'info>,
+
+    /// The pool in which liquidity is changed
+    /// CHECK: Passed read only by the core program
+    pub pool_state: UncheckedAccount<'info>,
+}
@@ -54,6 +54,11 @@ async function main() {
     coreProgram.programId
   );
 
+  const [pairState] = await web3.PublicKey.findProgramAddress(
+    [Buffer.from('pr'), usdtMint.toBuffer(), usdcMint.toBuffer()],
+    coreProgram.programId
+  );
+
   const vaultA0 = await Token.getAssociatedTokenAddress(
     ASSOCIATED_TOKEN_PROGRAM_ID,
     TOKEN_PROGRAM_ID,
@@ -69,7 +74,7 @@ async function main() {
     true
   )
 
-  const tx = coreProgram.transaction.createAndInitPool(new anchor.BN(4294967296), {
+  const tx = coreProgram.transaction.createAndInitPool(new anchor.BN(4294967296), web3.PublicKey.default, 0, {
     accounts: {
       poolCreator: owner,
       token0: usdtMint,
@@ -77,6 +82,7 @@ async function main() {
       feeState,
       poolState: poolAState,
       initialObservationState: initialObservationStateA,
+      pairState,
       vault0: vaultA0,
       vault1: vaultA1,
       systemProgram: SystemProgram.programId,
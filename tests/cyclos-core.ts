@@ -1,6 +1,15 @@
 import * as anchor from '@project-serum/anchor'
 import { Program, web3, BN, ProgramError } from '@project-serum/anchor'
 import * as metaplex from '@metaplex/js'
+import { GokiSDK } from '@gokiprotocol/client'
+import { SolanaProvider } from '@saberhq/solana-contrib'
+import {
+  findEscrowAddress,
+  findGovernorAddress,
+  findLockerAddress,
+  LockerWrapper,
+  TribecaSDK
+} from '@tribecahq/tribeca-sdk'
 import { Token, TOKEN_PROGRAM_ID, ASSOCIATED_TOKEN_PROGRAM_ID } from '@solana/spl-token'
 import {
   Pool,
@@ -21,11 +30,24 @@ chai.use(chaiAsPromised)
 
 import { CyclosCore } from '../target/types/cyclos_core'
 import {
+  COLLECTION_SEED,
+  EPOCH_GAUGE_SEED,
+  EPOCH_SEED,
+  GAUGE_CONTROLLER_SEED,
+  GAUGE_SEED,
+  GAUGE_VOTER_SEED,
+  GAUGE_VOTE_SEED,
   MaxU64,
   MAX_SQRT_RATIO,
   MAX_TICK,
   MIN_SQRT_RATIO,
   MIN_TICK,
+  OPERATOR_APPROVAL_SEED,
+  PAIR_SEED,
+  POSITION_LOCK_SEED,
+  computeUnitsConsumed,
+  returnData,
+  transactionReturnData,
 } from './utils'
 import SolanaTickDataProvider from './SolanaTickDataProvider'
 import { Transaction } from '@solana/web3.js'
@@ -63,6 +85,38 @@ describe('cyclos-core', async () => {
 
   const mintAuthority = new Keypair()
 
+  // Position NFT metadata config
+  const baseUri = 'https://example.com/nft/'
+  const collectionName = 'Cykura Positions'
+
+  // Factory owned collection NFT that position NFTs are verified into
+  const metadataProgramId = metaplex.programs.metadata.MetadataProgram.PUBKEY
+  const [collectionMint] = await PublicKey.findProgramAddress(
+    [COLLECTION_SEED],
+    coreProgram.programId
+  )
+  const collectionTokenAccount = await Token.getAssociatedTokenAddress(
+    ASSOCIATED_TOKEN_PROGRAM_ID,
+    TOKEN_PROGRAM_ID,
+    collectionMint,
+    factoryState,
+    true
+  )
+  const [collectionMetadata] = await PublicKey.findProgramAddress(
+    [Buffer.from('metadata'), metadataProgramId.toBuffer(), collectionMint.toBuffer()],
+    metadataProgramId
+  )
+  const [collectionMasterEdition] = await PublicKey.findProgramAddress(
+    [Buffer.from('metadata'), metadataProgramId.toBuffer(), collectionMint.toBuffer(), Buffer.from('edition')],
+    metadataProgramId
+  )
+
+  // Operator approval of the position owner for notOwner
+  const [notOwnerOperatorApproval] = await PublicKey.findProgramAddress(
+    [OPERATOR_APPROVAL_SEED, owner.toBuffer(), notOwner.publicKey.toBuffer()],
+    coreProgram.programId
+  )
+
   // Tokens constituting the pool
   let token0: Token
   let token1: Token
@@ -90,6 +144,9 @@ describe('cyclos-core', async () => {
   let initialObservationStateB: web3.PublicKey
   let initialObservationBumpB: number
 
+  let pairAState: web3.PublicKey
+  let pairBState: web3.PublicKey
+
   // These accounts will spend tokens to mint the position
   let minterWallet0: web3.PublicKey
   let minterWallet1: web3.PublicKey
@@ -109,6 +166,7 @@ describe('cyclos-core', async () => {
 
   const nftMintAKeypair = new Keypair()
   const nftMintBKeypair = new web3.Keypair()
+  const splitNftMintKeypair = new Keypair()
 
   let tickLowerAState: web3.PublicKey
   let tickLowerAStateBump: number
@@ -136,6 +194,8 @@ describe('cyclos-core', async () => {
   let tokenizedPositionBBump: number
   let positionANftAccount: web3.PublicKey
   let positionBNftAccount: web3.PublicKey
+  let splitNftAccount: web3.PublicKey
+  let splitTokenizedPositionState: web3.PublicKey
   let metadataAccount: web3.PublicKey
   let lastObservationAState: web3.PublicKey
   let nextObservationAState: web3.PublicKey
@@ -438,6 +498,130 @@ describe('cyclos-core', async () => {
     })
   })
 
+  describe('#set_metadata_config', () => {
+    it('fails if caller is not owner', async () => {
+      await expect(coreProgram.rpc.setMetadataConfig(baseUri, collectionName, {
+        accounts: {
+          owner: notOwner.publicKey,
+          factoryState,
+        }, signers: [notOwner]
+      })).to.be.rejectedWith(Error)
+    })
+
+    it('fails if the base URI is too long', async () => {
+      await expect(coreProgram.rpc.setMetadataConfig('a'.repeat(129), collectionName, {
+        accounts: {
+          owner,
+          factoryState,
+        }
+      })).to.be.rejectedWith(Error)
+    })
+
+    it('sets the metadata config and emits an event', async () => {
+      let listener: number
+      let [_event, _slot] = await new Promise((resolve, _reject) => {
+        listener = coreProgram.addEventListener("MetadataConfigChanged", (event, slot) => {
+          assert.equal(event.baseUri, baseUri)
+          assert.equal(event.collectionName, collectionName)
+          resolve([event, slot]);
+        });
+
+        coreProgram.rpc.setMetadataConfig(baseUri, collectionName, {
+          accounts: {
+            owner,
+            factoryState,
+          }
+        })
+      })
+      await coreProgram.removeEventListener(listener)
+
+      const factoryStateData = await coreProgram.account.factoryState.fetch(factoryState)
+      assert.equal(Buffer.from(factoryStateData.baseUri).toString().replace(/\0+$/, ''), baseUri)
+      assert.equal(Buffer.from(factoryStateData.collectionName).toString().replace(/\0+$/, ''), collectionName)
+    })
+
+    it('migration does not reset the config of a factory with the current layout', async () => {
+      await expect(coreProgram.rpc.migrateFactoryState({
+        accounts: {
+          payer: owner,
+          factoryState,
+          systemProgram: SystemProgram.programId,
+        }
+      })).to.be.rejectedWith(Error)
+
+      const factoryStateData = await coreProgram.account.factoryState.fetch(factoryState)
+      assert.equal(Buffer.from(factoryStateData.baseUri).toString().replace(/\0+$/, ''), baseUri)
+    })
+  })
+
+  describe('#create_position_collection', () => {
+    it('fails if caller is not owner', async () => {
+      await expect(coreProgram.rpc.createPositionCollection({
+        accounts: {
+          owner: notOwner.publicKey,
+          factoryState,
+          collectionMint,
+          collectionTokenAccount,
+          collectionMetadata,
+          collectionMasterEdition,
+          rent: web3.SYSVAR_RENT_PUBKEY,
+          metadataProgram: metadataProgramId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        }, signers: [notOwner]
+      })).to.be.rejectedWith(Error)
+    })
+
+    it('creates the collection NFT', async () => {
+      await coreProgram.rpc.createPositionCollection({
+        accounts: {
+          owner,
+          factoryState,
+          collectionMint,
+          collectionTokenAccount,
+          collectionMetadata,
+          collectionMasterEdition,
+          rent: web3.SYSVAR_RENT_PUBKEY,
+          metadataProgram: metadataProgramId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        }
+      })
+
+      const collection = new Token(connection, collectionMint, TOKEN_PROGRAM_ID, mintAuthority)
+      const collectionMintInfo = await collection.getMintInfo()
+      assert.equal(collectionMintInfo.supply.toNumber(), 1)
+      assert.equal(collectionMintInfo.mintAuthority.toString(), collectionMasterEdition.toString())
+
+      const metadata = await Metadata.load(connection, collectionMetadata)
+      assert.equal(metadata.data.mint, collectionMint.toString())
+      assert.equal(metadata.data.updateAuthority, factoryState.toString())
+      assert.equal(metadata.data.data.name, collectionName)
+      assert.equal(metadata.data.data.symbol, 'CYS-POS')
+      assert.equal(metadata.data.data.uri, baseUri + collectionMint.toString())
+    })
+
+    it('fails if the collection already exists', async () => {
+      await expect(coreProgram.rpc.createPositionCollection({
+        accounts: {
+          owner,
+          factoryState,
+          collectionMint,
+          collectionTokenAccount,
+          collectionMetadata,
+          collectionMasterEdition,
+          rent: web3.SYSVAR_RENT_PUBKEY,
+          metadataProgram: metadataProgramId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        }
+      })).to.be.rejectedWith(Error)
+    })
+  })
+
   describe('#enable_fee_amount', () => {
     it('fails if PDA seeds do not match', async () => {
       await expect(coreProgram.rpc.enableFeeAmount(fee + 1, tickSpacing, {
@@ -529,6 +713,7 @@ describe('cyclos-core', async () => {
       assert.equal(feeStateData.bump, feeStateBump)
       assert.equal(feeStateData.fee, fee)
       assert.equal(feeStateData.tickSpacing, tickSpacing)
+      assert.isTrue(feeStateData.enabled)
     })
 
     it('fails if already initialized', async () => {
@@ -554,6 +739,124 @@ describe('cyclos-core', async () => {
     })
   })
 
+  describe('#disable_fee_amount', () => {
+    const disabledFee = 3000
+    const disabledTickSpacing = 60
+    let disabledFeeState: web3.PublicKey
+
+    it('enables a fee amount to disable', async () => {
+      [disabledFeeState] = await PublicKey.findProgramAddress(
+        [FEE_SEED, u32ToSeed(disabledFee)],
+        coreProgram.programId
+      );
+      await coreProgram.rpc.enableFeeAmount(disabledFee, disabledTickSpacing, {
+        accounts: {
+          owner,
+          factoryState,
+          feeState: disabledFeeState,
+          systemProgram: SystemProgram.programId,
+        }
+      })
+    })
+
+    it('fails if caller is not owner', async () => {
+      await expect(coreProgram.rpc.disableFeeAmount({
+        accounts: {
+          owner: notOwner.publicKey,
+          factoryState,
+          feeState: disabledFeeState,
+        }, signers: [notOwner]
+      })).to.be.rejectedWith(Error)
+    })
+
+    it('disables the fee amount and emits an event', async () => {
+      let listener: number
+      let [_event, _slot] = await new Promise((resolve, _reject) => {
+        listener = coreProgram.addEventListener("FeeAmountDisabled", (event, slot) => {
+          assert.equal(event.fee, disabledFee)
+
+          resolve([event, slot]);
+        });
+
+        coreProgram.rpc.disableFeeAmount({
+          accounts: {
+            owner,
+            factoryState,
+            feeState: disabledFeeState,
+          }
+        })
+      });
+      await coreProgram.removeEventListener(listener);
+
+      const feeStateData = await coreProgram.account.feeState.fetch(disabledFeeState)
+      assert.isFalse(feeStateData.enabled)
+      assert.equal(feeStateData.tickSpacing, disabledTickSpacing)
+    })
+
+    it('fails if already disabled', async () => {
+      await expect(coreProgram.rpc.disableFeeAmount({
+        accounts: {
+          owner,
+          factoryState,
+          feeState: disabledFeeState,
+        }
+      })).to.be.rejectedWith(Error)
+    })
+
+    it('migration does not reenable a fee tier with the current layout', async () => {
+      await expect(coreProgram.rpc.migrateFeeState({
+        accounts: {
+          payer: owner,
+          feeState: disabledFeeState,
+          systemProgram: SystemProgram.programId,
+        }
+      })).to.be.rejectedWith(Error)
+
+      const feeStateData = await coreProgram.account.feeState.fetch(disabledFeeState)
+      assert.isFalse(feeStateData.enabled)
+    })
+
+    it('pools cannot be created with a disabled fee amount', async () => {
+      const [disabledPoolState] = await PublicKey.findProgramAddress(
+        [
+          POOL_SEED,
+          token0.publicKey.toBuffer(),
+          token1.publicKey.toBuffer(),
+          u32ToSeed(disabledFee)
+        ],
+        coreProgram.programId
+      );
+      const [disabledPairState] = await PublicKey.findProgramAddress(
+        [PAIR_SEED, token0.publicKey.toBuffer(), token1.publicKey.toBuffer()],
+        coreProgram.programId
+      );
+      const [disabledObservationState] = await PublicKey.findProgramAddress(
+        [
+          OBSERVATION_SEED,
+          token0.publicKey.toBuffer(),
+          token1.publicKey.toBuffer(),
+          u32ToSeed(disabledFee),
+          u16ToSeed(0)
+        ],
+        coreProgram.programId
+      );
+
+      await expect(coreProgram.rpc.createAndInitPool(initialPriceX32, PublicKey.default, 0, {
+        accounts: {
+          poolCreator: owner,
+          token0: token0.publicKey,
+          token1: token1.publicKey,
+          feeState: disabledFeeState,
+          poolState: disabledPoolState,
+          initialObservationState: disabledObservationState,
+          pairState: disabledPairState,
+          systemProgram: SystemProgram.programId,
+          rent: web3.SYSVAR_RENT_PUBKEY,
+        }
+      })).to.be.rejectedWith(Error)
+    })
+  })
+
   describe('#create_and_init_pool', () => {
     it('derive first observation slot address', async () => {
       [initialObservationStateA, initialObservationBumpA] = await PublicKey.findProgramAddress(
@@ -575,12 +878,20 @@ describe('cyclos-core', async () => {
           u16ToSeed(0)
         ],
         coreProgram.programId
+      );
+      [pairAState] = await PublicKey.findProgramAddress(
+        [PAIR_SEED, token0.publicKey.toBuffer(), token1.publicKey.toBuffer()],
+        coreProgram.programId
+      );
+      [pairBState] = await PublicKey.findProgramAddress(
+        [PAIR_SEED, token1.publicKey.toBuffer(), token2.publicKey.toBuffer()],
+        coreProgram.programId
       )
     })
 
     it('fails if tokens are passed in reverse', async () => {
       // Unlike Uniswap, we must pass the tokens by address sort order
-      await expect(coreProgram.rpc.createAndInitPool(initialPriceX32, {
+      await expect(coreProgram.rpc.createAndInitPool(initialPriceX32, PublicKey.default, 0, {
         accounts: {
           poolCreator: owner,
           token0: token1.publicKey,
@@ -588,6 +899,7 @@ describe('cyclos-core', async () => {
           feeState,
           poolState: poolAState,
           initialObservationState: initialObservationStateA,
+          pairState: pairAState,
           systemProgram: SystemProgram.programId,
           rent: web3.SYSVAR_RENT_PUBKEY,
         }
@@ -596,7 +908,7 @@ describe('cyclos-core', async () => {
 
     it('fails if token0 == token1', async () => {
       // Unlike Uniswap, we must pass the tokens by address sort order
-      await expect(coreProgram.rpc.createAndInitPool(initialPriceX32, {
+      await expect(coreProgram.rpc.createAndInitPool(initialPriceX32, PublicKey.default, 0, {
         accounts: {
           poolCreator: owner,
           token0: token0.publicKey,
@@ -604,6 +916,7 @@ describe('cyclos-core', async () => {
           feeState,
           poolState: poolAState,
           initialObservationState: initialObservationStateA,
+          pairState: pairAState,
           systemProgram: SystemProgram.programId,
           rent: web3.SYSVAR_RENT_PUBKEY,
         }
@@ -616,7 +929,7 @@ describe('cyclos-core', async () => {
         coreProgram.programId
       );
 
-      await expect(coreProgram.rpc.createAndInitPool(initialPriceX32, {
+      await expect(coreProgram.rpc.createAndInitPool(initialPriceX32, PublicKey.default, 0, {
         accounts: {
           poolCreator: owner,
           token0: token0.publicKey,
@@ -624,6 +937,7 @@ describe('cyclos-core', async () => {
           feeState: uninitializedFeeState,
           poolState: poolAState,
           initialObservationState: initialObservationStateA,
+          pairState: pairAState,
           systemProgram: SystemProgram.programId,
           rent: web3.SYSVAR_RENT_PUBKEY,
         }
@@ -631,7 +945,7 @@ describe('cyclos-core', async () => {
     })
 
     it('fails if starting price is too low', async () => {
-      await expect(coreProgram.rpc.createAndInitPool(new BN(1), {
+      await expect(coreProgram.rpc.createAndInitPool(new BN(1), PublicKey.default, 0, {
         accounts: {
           poolCreator: owner,
           token0: token0.publicKey,
@@ -639,13 +953,16 @@ describe('cyclos-core', async () => {
           feeState,
           poolState: poolAState,
           initialObservationState: initialObservationStateA,
+          pairState: pairAState,
           systemProgram: SystemProgram.programId,
           rent: web3.SYSVAR_RENT_PUBKEY,
         }
       })).to.be.rejectedWith(Error)
 
       await expect(coreProgram.rpc.createAndInitPool(
-        MIN_SQRT_RATIO.subn(1), {
+        MIN_SQRT_RATIO.subn(1),
+        PublicKey.default,
+        0, {
         accounts: {
           poolCreator: owner,
           token0: token0.publicKey,
@@ -653,6 +970,7 @@ describe('cyclos-core', async () => {
           feeState,
           poolState: poolAState,
           initialObservationState: initialObservationStateA,
+          pairState: pairAState,
           systemProgram: SystemProgram.programId,
           rent: web3.SYSVAR_RENT_PUBKEY,
         }
@@ -661,7 +979,7 @@ describe('cyclos-core', async () => {
     })
 
     it('fails if starting price is too high', async () => {
-      await expect(coreProgram.rpc.createAndInitPool(MAX_SQRT_RATIO, {
+      await expect(coreProgram.rpc.createAndInitPool(MAX_SQRT_RATIO, PublicKey.default, 0, {
         accounts: {
           poolCreator: owner,
           token0: token0.publicKey,
@@ -669,13 +987,16 @@ describe('cyclos-core', async () => {
           feeState,
           poolState: poolAState,
           initialObservationState: initialObservationStateA,
+          pairState: pairAState,
           systemProgram: SystemProgram.programId,
           rent: web3.SYSVAR_RENT_PUBKEY,
         }
       })).to.be.rejectedWith(Error)
 
       await expect(coreProgram.rpc.createAndInitPool(
-        new BN(2).pow(new BN(64)).subn(1), { // u64::MAX
+        new BN(2).pow(new BN(64)).subn(1), // u64::MAX
+        PublicKey.default,
+        0, {
         accounts: {
           poolCreator: owner,
           token0: token0.publicKey,
@@ -683,6 +1004,7 @@ describe('cyclos-core', async () => {
           feeState,
           poolState: poolAState,
           initialObservationState: initialObservationStateA,
+          pairState: pairAState,
           systemProgram: SystemProgram.programId,
           rent: web3.SYSVAR_RENT_PUBKEY,
         }
@@ -704,7 +1026,7 @@ describe('cyclos-core', async () => {
           resolve([event, slot]);
         });
 
-        coreProgram.rpc.createAndInitPool(initialPriceX32, {
+        coreProgram.rpc.createAndInitPool(initialPriceX32, PublicKey.default, 0, {
           accounts: {
             poolCreator: owner,
             token0: token0.publicKey,
@@ -712,6 +1034,7 @@ describe('cyclos-core', async () => {
             feeState,
             poolState: poolAState,
             initialObservationState: initialObservationStateA,
+            pairState: pairAState,
             systemProgram: SystemProgram.programId,
             rent: web3.SYSVAR_RENT_PUBKEY,
           }
@@ -747,11 +1070,18 @@ describe('cyclos-core', async () => {
       assert(observationStateData.initialized)
       assert.approximately(observationStateData.blockTimestamp, Math.floor(Date.now() / 1000), 60)
 
+      // pair registry
+      const pairStateData = await coreProgram.account.pairState.fetch(pairAState)
+      assert((pairStateData.token0).equals(token0.publicKey))
+      assert((pairStateData.token1).equals(token1.publicKey))
+      assert.equal(pairStateData.poolCount, 1)
+      assert.equal(pairStateData.fees[0], fee)
+
       console.log('got pool address', poolAState.toString())
     })
 
     it('fails if already initialized', async () => {
-      await expect(coreProgram.rpc.createAndInitPool(initialPriceX32, {
+      await expect(coreProgram.rpc.createAndInitPool(initialPriceX32, PublicKey.default, 0, {
         accounts: {
           poolCreator: owner,
           token0: token0.publicKey,
@@ -759,6 +1089,7 @@ describe('cyclos-core', async () => {
           feeState,
           poolState: poolAState,
           initialObservationState: initialObservationStateA,
+          pairState: pairAState,
           systemProgram: SystemProgram.programId,
           rent: web3.SYSVAR_RENT_PUBKEY,
         }
@@ -766,6 +1097,63 @@ describe('cyclos-core', async () => {
     })
   })
 
+  describe('#register_pool_pair', () => {
+    it('fails if the pool is already registered', async () => {
+      await expect(coreProgram.rpc.registerPoolPair({
+        accounts: {
+          payer: owner,
+          poolState: poolAState,
+          pairState: pairAState,
+          systemProgram: SystemProgram.programId,
+        }
+      })).to.be.rejectedWith(Error)
+    })
+
+    it('creates a pool whose pair registry address already holds lamports', async () => {
+      let tokenA = await Token.createMint(connection, mintAuthority, mintAuthority.publicKey, null, 8, TOKEN_PROGRAM_ID)
+      let tokenB = await Token.createMint(connection, mintAuthority, mintAuthority.publicKey, null, 8, TOKEN_PROGRAM_ID)
+      if (tokenA.publicKey.toString() > tokenB.publicKey.toString()) {
+        [tokenA, tokenB] = [tokenB, tokenA]
+      }
+      const seeds = [tokenA.publicKey.toBuffer(), tokenB.publicKey.toBuffer(), u32ToSeed(fee)]
+      const [poolState] = await PublicKey.findProgramAddress([POOL_SEED, ...seeds], coreProgram.programId)
+      const [observationState] = await PublicKey.findProgramAddress(
+        [OBSERVATION_SEED, ...seeds, u16ToSeed(0)],
+        coreProgram.programId
+      )
+      const [pairState] = await PublicKey.findProgramAddress(
+        [PAIR_SEED, tokenA.publicKey.toBuffer(), tokenB.publicKey.toBuffer()],
+        coreProgram.programId
+      )
+
+      // anyone can send lamports to the registry address before the first pool is created
+      await anchor.getProvider().send(new web3.Transaction().add(
+        web3.SystemProgram.transfer({
+          fromPubkey: owner,
+          toPubkey: pairState,
+          lamports: 1_000,
+        })
+      ))
+
+      await coreProgram.rpc.createAndInitPool(initialPriceX32, PublicKey.default, 0, {
+        accounts: {
+          poolCreator: owner,
+          token0: tokenA.publicKey,
+          token1: tokenB.publicKey,
+          feeState,
+          poolState,
+          initialObservationState: observationState,
+          pairState,
+          systemProgram: SystemProgram.programId,
+          rent: web3.SYSVAR_RENT_PUBKEY,
+        }
+      })
+      const pairStateData = await coreProgram.account.pairState.fetch(pairState)
+      assert.equal(pairStateData.poolCount, 1)
+      assert.equal(pairStateData.fees[0], fee)
+    })
+  })
+
   describe('#increase_observation_cardinality_next', () => {
     it('fails if bump does not produce a PDA with observation state seeds', async () => {
       const [observationState, _] = await PublicKey.findProgramAddress(
@@ -1316,6 +1704,72 @@ describe('cyclos-core', async () => {
     );
   })
 
+  describe('#reinitialize_price', () => {
+    it('sets a new price for an empty pool and emits an event', async () => {
+      const newPriceX32 = initialPriceX32.muln(2)
+      let listener: number
+      let [_event, _slot] = await new Promise((resolve, _reject) => {
+        listener = coreProgram.addEventListener("PriceReinitialized", (event, slot) => {
+          assert((event.poolState as web3.PublicKey).equals(poolAState))
+          assert((event.sqrtPriceX32Old as BN).eq(initialPriceX32))
+          assert((event.sqrtPriceX32 as BN).eq(newPriceX32))
+
+          resolve([event, slot]);
+        });
+
+        coreProgram.rpc.reinitializePrice(newPriceX32, {
+          accounts: {
+            signer: notOwner.publicKey,
+            poolState: poolAState,
+            lastObservationState: initialObservationStateA,
+          }, signers: [notOwner]
+        })
+      })
+      await coreProgram.removeEventListener(listener)
+
+      let poolStateData = await coreProgram.account.poolState.fetch(poolAState)
+      assert(poolStateData.sqrtPriceX32.eq(newPriceX32))
+      assert.isAbove(poolStateData.tick, initialTick)
+
+      // restore the starting price
+      await coreProgram.rpc.reinitializePrice(initialPriceX32, {
+        accounts: {
+          signer: owner,
+          poolState: poolAState,
+          lastObservationState: initialObservationStateA,
+        }
+      })
+      poolStateData = await coreProgram.account.poolState.fetch(poolAState)
+      assert(poolStateData.sqrtPriceX32.eq(initialPriceX32))
+      assert.equal(poolStateData.tick, initialTick)
+    })
+    it('fails to use a TWAP without the reference accounts', async () => {
+      await expect(coreProgram.rpc.reinitializePrice(new BN(0), {
+        accounts: {
+          signer: owner,
+          poolState: poolAState,
+          lastObservationState: initialObservationStateA,
+        }
+      })).to.be.rejectedWith(Error)
+    })
+
+    it('fails to use a TWAP shorter than the minimum window', async () => {
+      // the only observation of the pool was written moments ago
+      await expect(coreProgram.rpc.reinitializePrice(new BN(0), {
+        accounts: {
+          signer: owner,
+          poolState: poolAState,
+          lastObservationState: initialObservationStateA,
+        },
+        remainingAccounts: [poolAState, initialObservationStateA, initialObservationStateA].map(pubkey => ({
+          pubkey,
+          isSigner: false,
+          isWritable: false,
+        })),
+      })).to.be.rejectedWith(Error)
+    })
+  })
+
   describe('#init_tick_account', () => {
     it('fails if tick is lower than limit', async () => {
       const [invalidLowTickState, invalidLowTickBump] = await PublicKey.findProgramAddress([
@@ -1520,24 +1974,110 @@ describe('cyclos-core', async () => {
     })
   })
 
-  describe('#mint_tokenized_position', () => {
-
-    it('generate observation PDAs', async () => {
-      const {
-        observationIndex,
-        observationCardinalityNext
-      } = await coreProgram.account.poolState.fetch(poolAState)
+  describe('#transfer_position', () => {
+    let ownerPositionState: web3.PublicKey
+    let notOwnerPositionState: web3.PublicKey
 
-      lastObservationAState = (await PublicKey.findProgramAddress(
-        [
-          OBSERVATION_SEED,
-          token0.publicKey.toBuffer(),
-          token1.publicKey.toBuffer(),
-          u32ToSeed(fee),
-          u16ToSeed(observationIndex)
-        ],
-        coreProgram.programId
-      ))[0]
+    it('creates a position owned by the signer', async () => {
+      [ownerPositionState] = await PublicKey.findProgramAddress([
+        POSITION_SEED,
+        token0.publicKey.toBuffer(),
+        token1.publicKey.toBuffer(),
+        u32ToSeed(fee),
+        owner.toBuffer(),
+        u32ToSeed(tickLower),
+        u32ToSeed(tickUpper)
+      ],
+        coreProgram.programId
+      );
+      [notOwnerPositionState] = await PublicKey.findProgramAddress([
+        POSITION_SEED,
+        token0.publicKey.toBuffer(),
+        token1.publicKey.toBuffer(),
+        u32ToSeed(fee),
+        notOwner.publicKey.toBuffer(),
+        u32ToSeed(tickLower),
+        u32ToSeed(tickUpper)
+      ],
+        coreProgram.programId
+      )
+
+      await coreProgram.rpc.initPositionAccount({
+        accounts: {
+          signer: owner,
+          recipient: owner,
+          poolState: poolAState,
+          tickLowerState: tickLowerAState,
+          tickUpperState: tickUpperAState,
+          positionState: ownerPositionState,
+          systemProgram: SystemProgram.programId,
+        }
+      })
+    })
+
+    it('fails if the new owner is the current owner', async () => {
+      await expect(coreProgram.rpc.transferPosition({
+        accounts: {
+          owner,
+          newOwner: owner,
+          poolState: poolAState,
+          tickLowerState: tickLowerAState,
+          tickUpperState: tickUpperAState,
+          positionState: ownerPositionState,
+          newPositionState: ownerPositionState,
+          systemProgram: SystemProgram.programId,
+        }
+      })).to.be.rejectedWith(Error)
+    })
+
+    it('creates the position of the new owner', async () => {
+      // anyone can send lamports to the position address of the new owner
+      await anchor.getProvider().send(new web3.Transaction().add(
+        web3.SystemProgram.transfer({
+          fromPubkey: owner,
+          toPubkey: notOwnerPositionState,
+          lamports: 1_000,
+        })
+      ))
+
+      await coreProgram.rpc.transferPosition({
+        accounts: {
+          owner,
+          newOwner: notOwner.publicKey,
+          poolState: poolAState,
+          tickLowerState: tickLowerAState,
+          tickUpperState: tickUpperAState,
+          positionState: ownerPositionState,
+          newPositionState: notOwnerPositionState,
+          systemProgram: SystemProgram.programId,
+        }
+      })
+
+      const newPositionData = await coreProgram.account.positionState.fetch(notOwnerPositionState)
+      assert(newPositionData.liquidity.eqn(0))
+      const positionData = await coreProgram.account.positionState.fetch(ownerPositionState)
+      assert(positionData.liquidity.eqn(0))
+    })
+  })
+
+  describe('#mint_tokenized_position', () => {
+
+    it('generate observation PDAs', async () => {
+      const {
+        observationIndex,
+        observationCardinalityNext
+      } = await coreProgram.account.poolState.fetch(poolAState)
+
+      lastObservationAState = (await PublicKey.findProgramAddress(
+        [
+          OBSERVATION_SEED,
+          token0.publicKey.toBuffer(),
+          token1.publicKey.toBuffer(),
+          u32ToSeed(fee),
+          u16ToSeed(observationIndex)
+        ],
+        coreProgram.programId
+      ))[0]
 
       nextObservationAState = (await PublicKey.findProgramAddress(
         [
@@ -1556,10 +2096,14 @@ describe('cyclos-core', async () => {
       // connection.slot
       const deadline = new BN(Date.now() / 1000 - 10_000)
 
-      await expect(coreProgram.rpc.mintTokenizedPosition(amount0Desired,
+      await expect(coreProgram.rpc.mintTokenizedPosition(tickLower,
+        tickUpper,
+        amount0Desired,
         amount1Desired,
         amount0Minimum,
         amount1Minimum,
+        new BN(0),
+        new BN(0),
         deadline, {
         accounts: {
           minter: owner,
@@ -1600,10 +2144,14 @@ describe('cyclos-core', async () => {
 
       console.log('word upper', wordPosUpper)
       console.log('word upper bytes', u16ToSeed(wordPosUpper))
-      await coreProgram.rpc.mintTokenizedPosition(amount0Desired,
+      await coreProgram.rpc.mintTokenizedPosition(tickLower,
+        tickUpper,
+        amount0Desired,
         amount1Desired,
         amount0Minimum,
         amount1Minimum,
+        new BN(0),
+        new BN(0),
         deadline, {
         accounts: {
           minter: owner,
@@ -1757,6 +2305,72 @@ describe('cyclos-core', async () => {
     notOwner
   )
 
+  describe('#zap_in', () => {
+    const nftMintZapKeypair = new Keypair()
+
+    it('fails if minted liquidity is below the minimum', async () => {
+      const deadline = new BN(Date.now() / 1000 + 10_000)
+      const positionZapNftAccount = await Token.getAssociatedTokenAddress(
+        ASSOCIATED_TOKEN_PROGRAM_ID,
+        TOKEN_PROGRAM_ID,
+        nftMintZapKeypair.publicKey,
+        owner,
+      )
+      const [tokenizedPositionZapState] = await PublicKey.findProgramAddress([
+        POSITION_SEED,
+        nftMintZapKeypair.publicKey.toBuffer()
+      ],
+        coreProgram.programId
+      )
+      const { observationIndex } = await coreProgram.account.poolState.fetch(poolAState)
+      const [lastObservation] = await PublicKey.findProgramAddress(
+        [
+          OBSERVATION_SEED,
+          token0.publicKey.toBuffer(),
+          token1.publicKey.toBuffer(),
+          u32ToSeed(fee),
+          u16ToSeed(observationIndex)
+        ],
+        coreProgram.programId
+      )
+
+      await expect(coreProgram.rpc.zapIn(
+        tickLower,
+        tickUpper,
+        new BN(1_000),
+        true,
+        new BN('18446744073709551615'), // u64::MAX
+        0,
+        deadline, {
+        accounts: {
+          minter: owner,
+          recipient: owner,
+          factoryState,
+          nftMint: nftMintZapKeypair.publicKey,
+          nftAccount: positionZapNftAccount,
+          poolState: poolAState,
+          corePositionState: corePositionAState,
+          tickLowerState: tickLowerAState,
+          tickUpperState: tickUpperAState,
+          bitmapLowerState: bitmapLowerAState,
+          bitmapUpperState: bitmapUpperAState,
+          tokenAccount0: minterWallet0,
+          tokenAccount1: minterWallet1,
+          vault0: vaultA0,
+          vault1: vaultA1,
+          lastObservationState: lastObservation,
+          tokenizedPositionState: tokenizedPositionZapState,
+          coreProgram: coreProgram.programId,
+          systemProgram: SystemProgram.programId,
+          rent: web3.SYSVAR_RENT_PUBKEY,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID
+        },
+        signers: [nftMintZapKeypair],
+      })).to.be.rejectedWith(Error)
+    })
+  })
+
   describe('#add_metaplex_metadata', () => {
     it('Add metadata to a generated position', async () => {
       await coreProgram.rpc.addMetaplexMetadata({
@@ -1765,11 +2379,14 @@ describe('cyclos-core', async () => {
           factoryState,
           nftMint: nftMintAKeypair.publicKey,
           tokenizedPositionState: tokenizedPositionAState,
+          poolState: poolAState,
           metadataAccount,
+          collectionMint,
+          collectionMetadata,
+          collectionMasterEdition,
           systemProgram: SystemProgram.programId,
-          rent: web3.SYSVAR_RENT_PUBKEY,
           tokenProgram: TOKEN_PROGRAM_ID,
-          metadataProgram: metaplex.programs.metadata.MetadataProgram.PUBKEY,
+          metadataProgram: metadataProgramId,
         }
       })
 
@@ -1778,9 +2395,12 @@ describe('cyclos-core', async () => {
       const metadata = await Metadata.load(connection, metadataAccount)
       assert.equal(metadata.data.mint, nftMint.publicKey.toString())
       assert.equal(metadata.data.updateAuthority, factoryState.toString())
-      assert.equal(metadata.data.data.name, 'Cyclos Positions NFT-V1')
+      assert.equal(
+        metadata.data.data.name,
+        `${token0.publicKey.toString().slice(0, 4)}/${token1.publicKey.toString().slice(0, 4)} 0.05% ${tickLower}<>${tickUpper}`
+      )
       assert.equal(metadata.data.data.symbol, 'CYS-POS')
-      assert.equal(metadata.data.data.uri, 'https://asia-south1-cyclos-finance.cloudfunctions.net/nft?mint=' + nftMint.publicKey.toString())
+      assert.equal(metadata.data.data.uri, baseUri + nftMint.publicKey.toString())
       assert.deepEqual(metadata.data.data.creators, [{
         address: factoryState.toString(),
         // @ts-ignore
@@ -1790,6 +2410,8 @@ describe('cyclos-core', async () => {
       assert.equal(metadata.data.data.sellerFeeBasisPoints, 0)
       // @ts-ignore
       assert.equal(metadata.data.isMutable, 0)
+      assert.equal(metadata.data.collection.key, collectionMint.toString())
+      assert.isOk(metadata.data.collection.verified)
     })
 
     it('fails if metadata is already set', async () => {
@@ -1799,17 +2421,68 @@ describe('cyclos-core', async () => {
           factoryState,
           nftMint: nftMintAKeypair.publicKey,
           tokenizedPositionState: tokenizedPositionAState,
+          poolState: poolAState,
           metadataAccount,
+          collectionMint,
+          collectionMetadata,
+          collectionMasterEdition,
           systemProgram: SystemProgram.programId,
-          rent: web3.SYSVAR_RENT_PUBKEY,
           tokenProgram: TOKEN_PROGRAM_ID,
-          metadataProgram: metaplex.programs.metadata.MetadataProgram.PUBKEY,
+          metadataProgram: metadataProgramId,
+        }
+      })).to.be.rejectedWith(Error)
+    })
+  })
+
+  describe('#verify_position_collection', () => {
+    it('fails if the mint is not a tokenized position', async () => {
+      await expect(coreProgram.rpc.verifyPositionCollection({
+        accounts: {
+          payer: owner,
+          factoryState,
+          nftMint: collectionMint,
+          tokenizedPositionState: tokenizedPositionAState,
+          metadataAccount: collectionMetadata,
+          collectionMint,
+          collectionMetadata,
+          collectionMasterEdition,
+          metadataProgram: metadataProgramId,
         }
       })).to.be.rejectedWith(Error)
     })
+
+    it('verifies the collection of a position NFT', async () => {
+      await coreProgram.rpc.verifyPositionCollection({
+        accounts: {
+          payer: owner,
+          factoryState,
+          nftMint: nftMintAKeypair.publicKey,
+          tokenizedPositionState: tokenizedPositionAState,
+          metadataAccount,
+          collectionMint,
+          collectionMetadata,
+          collectionMasterEdition,
+          metadataProgram: metadataProgramId,
+        }
+      })
+
+      const metadata = await Metadata.load(connection, metadataAccount)
+      assert.equal(metadata.data.collection.key, collectionMint.toString())
+      assert.isOk(metadata.data.collection.verified)
+    })
   })
 
   describe('#increase_liquidity', () => {
+    it('price cannot be reinitialized once the pool has initialized ticks', async () => {
+      await expect(coreProgram.rpc.reinitializePrice(initialPriceX32.muln(2), {
+        accounts: {
+          signer: owner,
+          poolState: poolAState,
+          lastObservationState: lastObservationAState,
+        }
+      })).to.be.rejectedWith(Error)
+    })
+
     it('fails if past deadline', async () => {
       const deadline = new BN(Date.now() / 1000 - 100_000)
       await expect(coreProgram.rpc.increaseLiquidity(
@@ -1817,6 +2490,47 @@ describe('cyclos-core', async () => {
         amount1Desired,
         amount0Minimum,
         amount1Minimum,
+        new BN(0),
+        new BN(0),
+        deadline, {
+        accounts: {
+          payer: owner,
+          factoryState,
+          poolState: poolAState,
+          corePositionState: corePositionAState,
+          tickLowerState: tickLowerAState,
+          tickUpperState: tickUpperAState,
+          bitmapLowerState: bitmapLowerAState,
+          bitmapUpperState: bitmapUpperAState,
+          tokenAccount0: minterWallet0,
+          tokenAccount1: minterWallet1,
+          vault0: vaultA0,
+          vault1: vaultA1,
+          lastObservationState: lastObservationAState,
+          tokenizedPositionState: tokenizedPositionAState,
+          nftAccount: positionANftAccount,
+          coreProgram: coreProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        remainingAccounts: [{
+          pubkey: nextObservationAState,
+          isSigner: false,
+          isWritable: true
+        }],
+      }
+      )).to.be.rejectedWith(Error)
+    })
+
+    it('fails if the pool price is outside the bounds', async () => {
+      const deadline = new BN(Date.now() / 1000 + 10_000)
+      const { sqrtPriceX32 } = await coreProgram.account.poolState.fetch(poolAState)
+      await expect(coreProgram.rpc.increaseLiquidity(
+        amount0Desired,
+        amount1Desired,
+        amount0Minimum,
+        amount1Minimum,
+        (sqrtPriceX32 as BN).addn(1),
+        new BN(0),
         deadline, {
         accounts: {
           payer: owner,
@@ -1833,6 +2547,7 @@ describe('cyclos-core', async () => {
           vault1: vaultA1,
           lastObservationState: lastObservationAState,
           tokenizedPositionState: tokenizedPositionAState,
+          nftAccount: positionANftAccount,
           coreProgram: coreProgram.programId,
           tokenProgram: TOKEN_PROGRAM_ID,
         },
@@ -1889,12 +2604,16 @@ describe('cyclos-core', async () => {
 
     it('Add token 1 to the position', async () => {
       const deadline = new BN(Date.now() / 1000 + 10_000)
+      const { amount: vault0Before } = await token0.getAccountInfo(vaultA0)
+      const { amount: vault1Before } = await token1.getAccountInfo(vaultA1)
 
-      await coreProgram.rpc.increaseLiquidity(
+      const signature = await coreProgram.rpc.increaseLiquidity(
         amount0Desired,
         amount1Desired,
         amount0Minimum,
         amount1Minimum,
+        new BN(0),
+        new BN(0),
         deadline, {
         accounts: {
           payer: owner,
@@ -1911,6 +2630,7 @@ describe('cyclos-core', async () => {
           vault1: vaultA1,
           lastObservationState: lastObservationAState,
           tokenizedPositionState: tokenizedPositionAState,
+          nftAccount: positionANftAccount,
           coreProgram: coreProgram.programId,
           tokenProgram: TOKEN_PROGRAM_ID,
         },
@@ -1919,8 +2639,20 @@ describe('cyclos-core', async () => {
           isSigner: false,
           isWritable: true
         }],
+        options: { commitment: 'confirmed' },
       })
 
+      // the returned amounts are the amounts transferred to the vaults
+      const change = coreProgram.coder.types.decode(
+        'LiquidityChange',
+        await transactionReturnData(connection, signature)
+      )
+      const { amount: vault0After } = await token0.getAccountInfo(vaultA0)
+      const { amount: vault1After } = await token1.getAccountInfo(vaultA1)
+      assert((change.liquidity as BN).gtn(0))
+      assert((change.amount0 as BN).eq(vault0After.sub(vault0Before)))
+      assert((change.amount1 as BN).eq(vault1After.sub(vault1Before)))
+
       // let listener: number
       // let [_event, _slot] = await new Promise((resolve, _reject) => {
       //   listener = coreProgram.addEventListener("IncreaseLiquidityEvent", (event, slot) => {
@@ -1953,6 +2685,7 @@ describe('cyclos-core', async () => {
       //       lastObservationState: latestObservationAState,
       //       nextObservationState: nextObservationAState,
       //       tokenizedPositionState: tokenizedPositionAState,
+      //       nftAccount: positionANftAccount,
       //       coreProgram: coreProgram.programId,
       //       tokenProgram: TOKEN_PROGRAM_ID,
       //     },
@@ -2025,6 +2758,8 @@ describe('cyclos-core', async () => {
         liquidity,
         new BN(0),
         amount1Desired,
+        new BN(0),
+        new BN(0),
         deadline, {
         accounts: {
           ownerOrDelegate: owner,
@@ -2055,6 +2790,8 @@ describe('cyclos-core', async () => {
         liquidity,
         new BN(0),
         amount1Desired,
+        new BN(0),
+        new BN(0),
         deadline, {
         accounts: {
           ownerOrDelegate: notOwner,
@@ -2085,6 +2822,8 @@ describe('cyclos-core', async () => {
         liquidity,
         new BN(0),
         new BN(1_000_000), // 999_999 available
+        new BN(0),
+        new BN(0),
         deadline, {
         accounts: {
           ownerOrDelegate: owner,
@@ -2109,6 +2848,40 @@ describe('cyclos-core', async () => {
       )).to.be.rejectedWith(Error)
     })
 
+    it('fails if the liquidity fraction is out of range', async () => {
+      const deadline = new BN(Date.now() / 1000 + 10_000)
+      for (const liquidityBps of [0, 10_001]) {
+        await expect(coreProgram.rpc.decreaseLiquidityBps(
+          liquidityBps,
+          new BN(0),
+          new BN(0),
+          new BN(0),
+          new BN(0),
+          deadline, {
+          accounts: {
+            ownerOrDelegate: owner,
+            nftAccount: positionANftAccount,
+            tokenizedPositionState: tokenizedPositionAState,
+            factoryState,
+            poolState: poolAState,
+            corePositionState: corePositionAState,
+            tickLowerState: tickLowerAState,
+            tickUpperState: tickUpperAState,
+            bitmapLowerState: bitmapLowerAState,
+            bitmapUpperState: bitmapUpperAState,
+            lastObservationState: lastObservationAState,
+            coreProgram: coreProgram.programId
+          },
+          remainingAccounts: [{
+            pubkey: nextObservationAState,
+            isSigner: false,
+            isWritable: true
+          }],
+        }
+        )).to.be.rejectedWith(Error)
+      }
+    })
+
     it('generate a temporary NFT account for testing', async () => {
       temporaryNftHolder = await nftMint.createAssociatedTokenAccount(mintAuthority.publicKey)
     })
@@ -2132,6 +2905,8 @@ describe('cyclos-core', async () => {
         liquidity,
         new BN(0),
         amount1Desired,
+        new BN(0),
+        new BN(0),
         deadline, {
         accounts: {
           ownerOrDelegate: owner,
@@ -2169,6 +2944,7 @@ describe('cyclos-core', async () => {
       const deadline = new BN(Date.now() / 1000 + 10_000)
 
       let listener: number
+      let signature: Promise<string>
       let [_event, _slot] = await new Promise((resolve, _reject) => {
         listener = coreProgram.addEventListener("DecreaseLiquidityEvent", (event, slot) => {
           assert((event.tokenId as web3.PublicKey).equals(nftMintAKeypair.publicKey))
@@ -2179,10 +2955,12 @@ describe('cyclos-core', async () => {
           resolve([event, slot]);
         });
 
-        coreProgram.rpc.decreaseLiquidity(
+        signature = coreProgram.rpc.decreaseLiquidity(
           liquidity,
           new BN(0),
           amount1Desired,
+          new BN(0),
+          new BN(0),
           deadline, {
           accounts: {
             ownerOrDelegate: owner,
@@ -2203,10 +2981,19 @@ describe('cyclos-core', async () => {
             isSigner: false,
             isWritable: true
           }],
+          options: { commitment: 'confirmed' },
         }
         )
       })
       await coreProgram.removeEventListener(listener)
+
+      const change = coreProgram.coder.types.decode(
+        'LiquidityChange',
+        await transactionReturnData(connection, await signature)
+      )
+      assert((change.liquidity as BN).eq(liquidity))
+      assert((change.amount0 as BN).eqn(0))
+      assert((change.amount1 as BN).eq(amount1Desired))
       const tokenizedPositionData = await coreProgram.account.tokenizedPositionState.fetch(tokenizedPositionAState)
       assert(tokenizedPositionData.tokensOwed0.eqn(0))
       assert(tokenizedPositionData.tokensOwed1.eqn(999999))
@@ -2230,6 +3017,8 @@ describe('cyclos-core', async () => {
         new BN(1_000),
         new BN(0),
         new BN(0),
+        new BN(0),
+        new BN(0),
         deadline, {
         accounts: {
           ownerOrDelegate: mintAuthority.publicKey,
@@ -2280,6 +3069,8 @@ describe('cyclos-core', async () => {
           new BN(1_000_000),
           new BN(0),
           new BN(0),
+          new BN(0),
+          new BN(0),
           deadline, {
           accounts: {
             ownerOrDelegate: mintAuthority.publicKey,
@@ -2323,6 +3114,8 @@ describe('cyclos-core', async () => {
         new BN(1_000_000),
         new BN(0),
         new BN(0),
+        new BN(0),
+        new BN(0),
         deadline, {
         accounts: {
           ownerOrDelegate: mintAuthority.publicKey,
@@ -2642,214 +3435,1919 @@ describe('cyclos-core', async () => {
     })
   })
 
-  describe('#exact_input_single', () => {
-    // before swapping, current tick = 10 and price = 4297115210
-    // active ticks are 0 and 10
-    // entire liquidity is in token_1
+  describe('#approve_operator', () => {
+    it('approves an operator for all positions of the owner', async () => {
+      let listener: number
+      let [_event, _slot] = await new Promise((resolve, _reject) => {
+        listener = coreProgram.addEventListener("ApprovalForAllEvent", (event, slot) => {
+          assert((event.owner as web3.PublicKey).equals(owner))
+          assert((event.operator as web3.PublicKey).equals(notOwner.publicKey))
+          assert.isTrue(event.approved)
+          resolve([event, slot]);
+        });
+
+        coreProgram.rpc.approveOperator({
+          accounts: {
+            owner,
+            operator: notOwner.publicKey,
+            operatorApproval: notOwnerOperatorApproval,
+            systemProgram: SystemProgram.programId,
+          }
+        })
+      })
+      await coreProgram.removeEventListener(listener)
+
+      const approvalData = await coreProgram.account.operatorApprovalState.fetch(notOwnerOperatorApproval)
+      assert(approvalData.owner.equals(owner))
+      assert(approvalData.operator.equals(notOwner.publicKey))
+    })
+
+    it('collects tokens as the operator', async () => {
+      const recipientWallet1Before = await token1.getAccountInfo(feeRecipientWallet1)
+      await coreProgram.rpc.collectFromTokenized(new BN(0), new BN(10), {
+        accounts: {
+          ownerOrDelegate: notOwner.publicKey,
+          nftAccount: positionANftAccount,
+          tokenizedPositionState: tokenizedPositionAState,
+          factoryState,
+          poolState: poolAState,
+          corePositionState: corePositionAState,
+          tickLowerState: tickLowerAState,
+          tickUpperState: tickUpperAState,
+          bitmapLowerState: bitmapLowerAState,
+          bitmapUpperState: bitmapUpperAState,
+          lastObservationState: lastObservationAState,
+          coreProgram: coreProgram.programId,
+          vault0: vaultA0,
+          vault1: vaultA1,
+          recipientWallet0: feeRecipientWallet0,
+          recipientWallet1: feeRecipientWallet1,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        remainingAccounts: [{
+          pubkey: notOwnerOperatorApproval,
+          isSigner: false,
+          isWritable: false
+        }, {
+          pubkey: nextObservationAState,
+          isSigner: false,
+          isWritable: true
+        }],
+        signers: [notOwner],
+      })
+      const recipientWallet1After = await token1.getAccountInfo(feeRecipientWallet1)
+      assert(recipientWallet1After.amount.eq(recipientWallet1Before.amount.addn(10)))
+    })
+
+    it('fails to collect as the operator without the approval account', async () => {
+      await expect(coreProgram.rpc.collectFromTokenized(new BN(0), new BN(10), {
+        accounts: {
+          ownerOrDelegate: notOwner.publicKey,
+          nftAccount: positionANftAccount,
+          tokenizedPositionState: tokenizedPositionAState,
+          factoryState,
+          poolState: poolAState,
+          corePositionState: corePositionAState,
+          tickLowerState: tickLowerAState,
+          tickUpperState: tickUpperAState,
+          bitmapLowerState: bitmapLowerAState,
+          bitmapUpperState: bitmapUpperAState,
+          lastObservationState: lastObservationAState,
+          coreProgram: coreProgram.programId,
+          vault0: vaultA0,
+          vault1: vaultA1,
+          recipientWallet0: feeRecipientWallet0,
+          recipientWallet1: feeRecipientWallet1,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        remainingAccounts: [{
+          pubkey: nextObservationAState,
+          isSigner: false,
+          isWritable: true
+        }],
+        signers: [notOwner],
+      })).to.be.rejectedWith(Error)
+    })
+
+    it('rebalances the position as the operator', async () => {
+      const deadline = new BN(Date.now() / 1000 + 10_000)
+      const operatorWallet0 = await token0.createAssociatedTokenAccount(notOwner.publicKey)
+      const operatorWallet1 = await token1.createAssociatedTokenAccount(notOwner.publicKey)
+      const { liquidity: liquidityBefore } = await coreProgram.account.tokenizedPositionState.fetch(tokenizedPositionAState)
+      const remainingAccounts = [{
+        pubkey: notOwnerOperatorApproval,
+        isSigner: false,
+        isWritable: false
+      }, {
+        pubkey: nextObservationAState,
+        isSigner: false,
+        isWritable: true
+      }]
+
+      // withdraw half of the position to the operator
+      await coreProgram.rpc.decreaseAndCollect(
+        (liquidityBefore as BN).divn(2),
+        new BN(0),
+        new BN(0),
+        new BN(0),
+        new BN(0),
+        deadline, {
+        accounts: {
+          ownerOrDelegate: notOwner.publicKey,
+          nftAccount: positionANftAccount,
+          tokenizedPositionState: tokenizedPositionAState,
+          factoryState,
+          poolState: poolAState,
+          corePositionState: corePositionAState,
+          tickLowerState: tickLowerAState,
+          tickUpperState: tickUpperAState,
+          bitmapLowerState: bitmapLowerAState,
+          bitmapUpperState: bitmapUpperAState,
+          lastObservationState: lastObservationAState,
+          coreProgram: coreProgram.programId,
+          vault0: vaultA0,
+          vault1: vaultA1,
+          recipientWallet0: operatorWallet0,
+          recipientWallet1: operatorWallet1,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        remainingAccounts,
+        signers: [notOwner],
+      })
+      const { amount: collected0 } = await token0.getAccountInfo(operatorWallet0)
+      const { amount: collected1 } = await token1.getAccountInfo(operatorWallet1)
+      assert(collected0.gtn(0) || collected1.gtn(0))
+
+      // add the withdrawn tokens back, paid by the operator
+      await coreProgram.rpc.increaseLiquidity(
+        collected0,
+        collected1,
+        new BN(0),
+        new BN(0),
+        new BN(0),
+        new BN(0),
+        deadline, {
+        accounts: {
+          payer: notOwner.publicKey,
+          factoryState,
+          poolState: poolAState,
+          corePositionState: corePositionAState,
+          tickLowerState: tickLowerAState,
+          tickUpperState: tickUpperAState,
+          bitmapLowerState: bitmapLowerAState,
+          bitmapUpperState: bitmapUpperAState,
+          tokenAccount0: operatorWallet0,
+          tokenAccount1: operatorWallet1,
+          vault0: vaultA0,
+          vault1: vaultA1,
+          lastObservationState: lastObservationAState,
+          tokenizedPositionState: tokenizedPositionAState,
+          nftAccount: positionANftAccount,
+          coreProgram: coreProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        remainingAccounts: [{
+          pubkey: nextObservationAState,
+          isSigner: false,
+          isWritable: true
+        }],
+        signers: [notOwner],
+      })
+      const { liquidity: liquidityAfter } = await coreProgram.account.tokenizedPositionState.fetch(tokenizedPositionAState)
+      assert((liquidityAfter as BN).gt((liquidityBefore as BN).divn(2)))
+    })
+
+    it('fails to collect as the operator once revoked', async () => {
+      await coreProgram.rpc.revokeOperator({
+        accounts: {
+          owner,
+          operator: notOwner.publicKey,
+          operatorApproval: notOwnerOperatorApproval,
+        }
+      })
+      assert.isNull(await connection.getAccountInfo(notOwnerOperatorApproval))
+
+      await expect(coreProgram.rpc.collectFromTokenized(new BN(0), new BN(10), {
+        accounts: {
+          ownerOrDelegate: notOwner.publicKey,
+          nftAccount: positionANftAccount,
+          tokenizedPositionState: tokenizedPositionAState,
+          factoryState,
+          poolState: poolAState,
+          corePositionState: corePositionAState,
+          tickLowerState: tickLowerAState,
+          tickUpperState: tickUpperAState,
+          bitmapLowerState: bitmapLowerAState,
+          bitmapUpperState: bitmapUpperAState,
+          lastObservationState: lastObservationAState,
+          coreProgram: coreProgram.programId,
+          vault0: vaultA0,
+          vault1: vaultA1,
+          recipientWallet0: feeRecipientWallet0,
+          recipientWallet1: feeRecipientWallet1,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        remainingAccounts: [{
+          pubkey: notOwnerOperatorApproval,
+          isSigner: false,
+          isWritable: false
+        }, {
+          pubkey: nextObservationAState,
+          isSigner: false,
+          isWritable: true
+        }],
+        signers: [notOwner],
+      })).to.be.rejectedWith(Error)
+    })
+  })
+
+  describe('#split_tokenized_position', () => {
+    it('derive the accounts of the split position', async () => {
+      splitNftAccount = await Token.getAssociatedTokenAddress(
+        ASSOCIATED_TOKEN_PROGRAM_ID,
+        TOKEN_PROGRAM_ID,
+        splitNftMintKeypair.publicKey,
+        owner,
+      );
+      [splitTokenizedPositionState] = await PublicKey.findProgramAddress([
+        POSITION_SEED,
+        splitNftMintKeypair.publicKey.toBuffer()
+      ],
+        coreProgram.programId
+      )
+    })
+
+    it('fails to split all of the liquidity', async () => {
+      const { liquidity } = await coreProgram.account.tokenizedPositionState.fetch(tokenizedPositionAState)
+      await expect(coreProgram.rpc.splitTokenizedPosition(liquidity, {
+        accounts: {
+          ownerOrDelegate: owner,
+          nftAccount: positionANftAccount,
+          tokenizedPositionState: tokenizedPositionAState,
+          factoryState,
+          poolState: poolAState,
+          corePositionState: corePositionAState,
+          recipient: owner,
+          newNftMint: splitNftMintKeypair.publicKey,
+          newNftAccount: splitNftAccount,
+          newTokenizedPositionState: splitTokenizedPositionState,
+          systemProgram: SystemProgram.programId,
+          rent: web3.SYSVAR_RENT_PUBKEY,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID
+        },
+        signers: [splitNftMintKeypair],
+      })).to.be.rejectedWith(Error)
+    })
+
+    it('fails to split into a wallet other than the owner', async () => {
+      const { liquidity } = await coreProgram.account.tokenizedPositionState.fetch(tokenizedPositionAState)
+      const notOwnerNftAccount = await Token.getAssociatedTokenAddress(
+        ASSOCIATED_TOKEN_PROGRAM_ID,
+        TOKEN_PROGRAM_ID,
+        splitNftMintKeypair.publicKey,
+        notOwner.publicKey,
+      )
+      await expect(coreProgram.rpc.splitTokenizedPosition((liquidity as BN).divn(4), {
+        accounts: {
+          ownerOrDelegate: owner,
+          nftAccount: positionANftAccount,
+          tokenizedPositionState: tokenizedPositionAState,
+          factoryState,
+          poolState: poolAState,
+          corePositionState: corePositionAState,
+          recipient: notOwner.publicKey,
+          newNftMint: splitNftMintKeypair.publicKey,
+          newNftAccount: notOwnerNftAccount,
+          newTokenizedPositionState: splitTokenizedPositionState,
+          systemProgram: SystemProgram.programId,
+          rent: web3.SYSVAR_RENT_PUBKEY,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID
+        },
+        signers: [splitNftMintKeypair],
+      })).to.be.rejectedWith(Error)
+    })
+
+    it('splits part of the liquidity into a new position', async () => {
+      const { liquidity } = await coreProgram.account.tokenizedPositionState.fetch(tokenizedPositionAState)
+      const splitLiquidity = (liquidity as BN).divn(4)
+      await coreProgram.rpc.splitTokenizedPosition(splitLiquidity, {
+        accounts: {
+          ownerOrDelegate: owner,
+          nftAccount: positionANftAccount,
+          tokenizedPositionState: tokenizedPositionAState,
+          factoryState,
+          poolState: poolAState,
+          corePositionState: corePositionAState,
+          recipient: owner,
+          newNftMint: splitNftMintKeypair.publicKey,
+          newNftAccount: splitNftAccount,
+          newTokenizedPositionState: splitTokenizedPositionState,
+          systemProgram: SystemProgram.programId,
+          rent: web3.SYSVAR_RENT_PUBKEY,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID
+        },
+        signers: [splitNftMintKeypair],
+      })
+
+      const positionData = await coreProgram.account.tokenizedPositionState.fetch(tokenizedPositionAState)
+      const splitPositionData = await coreProgram.account.tokenizedPositionState.fetch(splitTokenizedPositionState)
+      assert(splitPositionData.liquidity.eq(splitLiquidity))
+      assert(positionData.liquidity.add(splitPositionData.liquidity).eq(liquidity))
+      assert.equal(splitPositionData.tickLower, positionData.tickLower)
+      assert.equal(splitPositionData.tickUpper, positionData.tickUpper)
+      assert(splitPositionData.poolId.equals(poolAState))
+
+      const splitNftAccountInfo = await connection.getTokenAccountBalance(splitNftAccount)
+      assert.equal(splitNftAccountInfo.value.amount, '1')
+    })
+  })
+
+  describe('#merge_tokenized_positions', () => {
+    it('fails if the rent goes to another account than the NFT owner', async () => {
+      await expect(coreProgram.rpc.mergeTokenizedPositions({
+        accounts: {
+          ownerOrDelegate: owner,
+          owner: notOwner.publicKey,
+          nftAccount: positionANftAccount,
+          tokenizedPositionState: tokenizedPositionAState,
+          sourceNftMint: splitNftMintKeypair.publicKey,
+          sourceNftAccount: splitNftAccount,
+          sourceTokenizedPositionState: splitTokenizedPositionState,
+          factoryState,
+          poolState: poolAState,
+          corePositionState: corePositionAState,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        }
+      })).to.be.rejectedWith(Error)
+    })
+
+    it('merges the split position back', async () => {
+      const positionDataBefore = await coreProgram.account.tokenizedPositionState.fetch(tokenizedPositionAState)
+      const splitPositionData = await coreProgram.account.tokenizedPositionState.fetch(splitTokenizedPositionState)
+
+      await coreProgram.rpc.mergeTokenizedPositions({
+        accounts: {
+          ownerOrDelegate: owner,
+          owner,
+          nftAccount: positionANftAccount,
+          tokenizedPositionState: tokenizedPositionAState,
+          sourceNftMint: splitNftMintKeypair.publicKey,
+          sourceNftAccount: splitNftAccount,
+          sourceTokenizedPositionState: splitTokenizedPositionState,
+          factoryState,
+          poolState: poolAState,
+          corePositionState: corePositionAState,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        }
+      })
+
+      const positionData = await coreProgram.account.tokenizedPositionState.fetch(tokenizedPositionAState)
+      assert(positionData.liquidity.eq(positionDataBefore.liquidity.add(splitPositionData.liquidity)))
+      assert.isNull(await connection.getAccountInfo(splitTokenizedPositionState))
+
+      const splitNftAccountInfo = await connection.getTokenAccountBalance(splitNftAccount)
+      assert.equal(splitNftAccountInfo.value.amount, '0')
+    })
+
+    it('fails to merge a position into itself', async () => {
+      await expect(coreProgram.rpc.mergeTokenizedPositions({
+        accounts: {
+          ownerOrDelegate: owner,
+          owner,
+          nftAccount: positionANftAccount,
+          tokenizedPositionState: tokenizedPositionAState,
+          sourceNftMint: nftMintAKeypair.publicKey,
+          sourceNftAccount: positionANftAccount,
+          sourceTokenizedPositionState: tokenizedPositionAState,
+          factoryState,
+          poolState: poolAState,
+          corePositionState: corePositionAState,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        }
+      })).to.be.rejectedWith(Error)
+    })
+  })
+
+  describe('#collect_from_tokenized_batch', () => {
+    const batchAccounts = () => ({
+      ownerOrDelegate: owner,
+      factoryState,
+      poolState: poolAState,
+      lastObservationState: lastObservationAState,
+      vault0: vaultA0,
+      vault1: vaultA1,
+      recipientWallet0: feeRecipientWallet0,
+      recipientWallet1: feeRecipientWallet1,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    })
+    const positionAAccounts = () => [
+      tokenizedPositionAState,
+      positionANftAccount,
+      corePositionAState,
+      tickLowerAState,
+      tickUpperAState,
+      bitmapLowerAState,
+      bitmapUpperAState,
+    ].map(pubkey => ({ pubkey, isSigner: false, isWritable: true }))
+
+    it('fails if a position tuple is incomplete', async () => {
+      await expect(coreProgram.rpc.collectFromTokenizedBatch(false, {
+        accounts: batchAccounts(),
+        remainingAccounts: positionAAccounts().slice(0, 6),
+      })).to.be.rejectedWith(Error)
+    })
+
+    it('fails if the operator approval flag does not match the accounts', async () => {
+      await expect(coreProgram.rpc.collectFromTokenizedBatch(true, {
+        accounts: batchAccounts(),
+        remainingAccounts: positionAAccounts(),
+      })).to.be.rejectedWith(Error)
+    })
+
+    it('fails if the vaults are not the pool vaults', async () => {
+      await expect(coreProgram.rpc.collectFromTokenizedBatch(false, {
+        accounts: { ...batchAccounts(), vault1: vaultB1 },
+        remainingAccounts: positionAAccounts(),
+      })).to.be.rejectedWith(Error)
+    })
+
+    it('collects all tokens owed to the positions', async () => {
+      const recipientWallet1Before = await token1.getAccountInfo(feeRecipientWallet1)
+
+      await coreProgram.rpc.collectFromTokenizedBatch(false, {
+        accounts: batchAccounts(),
+        remainingAccounts: positionAAccounts(),
+      })
+
+      const tokenizedPositionData = await coreProgram.account.tokenizedPositionState.fetch(tokenizedPositionAState)
+      assert(tokenizedPositionData.tokensOwed0.eqn(0))
+      assert(tokenizedPositionData.tokensOwed1.eqn(0))
+
+      const recipientWallet1After = await token1.getAccountInfo(feeRecipientWallet1)
+      assert(recipientWallet1After.amount.gt(recipientWallet1Before.amount))
+    })
+  })
+
+  describe('#value_tokenized_position', () => {
+    const valueAccounts = () => ({
+      tokenizedPositionState: tokenizedPositionAState,
+      poolState: poolAState,
+      tickLowerState: tickLowerAState,
+      tickUpperState: tickUpperAState,
+    })
+
+    it('fails if ticks do not belong to the position', async () => {
+      await expect(coreProgram.simulate.valueTokenizedPosition(null, {
+        accounts: {
+          ...valueAccounts(),
+          tickLowerState: tickUpperAState,
+        }
+      })).to.be.rejectedWith(Error)
+    })
+
+    it('values the position at the spot price', async () => {
+      const { raw: logs } = await coreProgram.simulate.valueTokenizedPosition(null, {
+        accounts: valueAccounts(),
+      })
+      const value = coreProgram.coder.types.decode(
+        'PositionValue',
+        returnData(logs, coreProgram.programId.toString())
+      )
+
+      const poolStateData = await coreProgram.account.poolState.fetch(poolAState)
+      const tokenizedPositionData = await coreProgram.account.tokenizedPositionState.fetch(tokenizedPositionAState)
+      assert(value.sqrtPriceX32.eq(poolStateData.sqrtPriceX32))
+      assert(value.amount0.gtn(0))
+      assert(value.amount1.gtn(0))
+      assert(value.fees0.gte(tokenizedPositionData.tokensOwed0))
+      assert(value.fees1.gte(tokenizedPositionData.tokensOwed1))
+    })
+
+    const twapAccounts = async () => {
+      const [olderObservation] = await PublicKey.findProgramAddress(
+        [
+          OBSERVATION_SEED,
+          token0.publicKey.toBuffer(),
+          token1.publicKey.toBuffer(),
+          u32ToSeed(fee),
+          u16ToSeed(0)
+        ],
+        coreProgram.programId
+      )
+      return [olderObservation, lastObservationAState].map(pubkey => ({
+        pubkey,
+        isSigner: false,
+        isWritable: false,
+      }))
+    }
+
+    it('fails to value at the TWAP without observation accounts', async () => {
+      await expect(coreProgram.simulate.valueTokenizedPosition(1, {
+        accounts: valueAccounts(),
+      })).to.be.rejectedWith(Error)
+    })
+
+    it('fails to value at the TWAP with a zero window', async () => {
+      await expect(coreProgram.simulate.valueTokenizedPosition(0, {
+        accounts: valueAccounts(),
+        remainingAccounts: await twapAccounts(),
+      })).to.be.rejectedWith(Error)
+    })
+
+    it('fails to value at the TWAP if the observation is younger than the window', async () => {
+      // the first observation of the pool is younger than a day
+      await expect(coreProgram.simulate.valueTokenizedPosition(86_400, {
+        accounts: valueAccounts(),
+        remainingAccounts: await twapAccounts(),
+      })).to.be.rejectedWith(Error)
+    })
+
+    it('values the position at the pool TWAP', async () => {
+      const { raw: logs } = await coreProgram.simulate.valueTokenizedPosition(1, {
+        accounts: valueAccounts(),
+        remainingAccounts: await twapAccounts(),
+      })
+      const value = coreProgram.coder.types.decode(
+        'PositionValue',
+        returnData(logs, coreProgram.programId.toString())
+      )
+      assert(value.sqrtPriceX32.gtn(0))
+    })
+  })
+
+  describe('#lock_position', () => {
+    let positionALock: web3.PublicKey
+    let escrowNftAccount: web3.PublicKey
+    let lockOperatorApproval: web3.PublicKey
+    let unlockTime: number
+
+    const collectAccounts = () => ({
+      ownerOrDelegate: owner,
+      nftAccount: escrowNftAccount,
+      tokenizedPositionState: tokenizedPositionAState,
+      factoryState,
+      poolState: poolAState,
+      corePositionState: corePositionAState,
+      tickLowerState: tickLowerAState,
+      tickUpperState: tickUpperAState,
+      bitmapLowerState: bitmapLowerAState,
+      bitmapUpperState: bitmapUpperAState,
+      lastObservationState: lastObservationAState,
+      coreProgram: coreProgram.programId,
+      vault0: vaultA0,
+      vault1: vaultA1,
+      recipientWallet0: feeRecipientWallet0,
+      recipientWallet1: feeRecipientWallet1,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    })
+
+    const unlockAccounts = () => ({
+      owner,
+      nftMint: nftMintAKeypair.publicKey,
+      positionLockState: positionALock,
+      escrowNftAccount,
+      nftAccount: positionANftAccount,
+      operatorApproval: lockOperatorApproval,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    })
+
+    it('fails if the unlock time has passed', async () => {
+      [positionALock] = await PublicKey.findProgramAddress(
+        [POSITION_LOCK_SEED, nftMintAKeypair.publicKey.toBuffer()],
+        coreProgram.programId
+      )
+      escrowNftAccount = await Token.getAssociatedTokenAddress(
+        ASSOCIATED_TOKEN_PROGRAM_ID,
+        TOKEN_PROGRAM_ID,
+        nftMintAKeypair.publicKey,
+        positionALock,
+        true
+      );
+      [lockOperatorApproval] = await PublicKey.findProgramAddress(
+        [OPERATOR_APPROVAL_SEED, positionALock.toBuffer(), owner.toBuffer()],
+        coreProgram.programId
+      )
+
+      await expect(coreProgram.rpc.lockPosition(new BN(Math.floor(Date.now() / 1000) - 10), {
+        accounts: {
+          owner,
+          nftMint: nftMintAKeypair.publicKey,
+          tokenizedPositionState: tokenizedPositionAState,
+          nftAccount: positionANftAccount,
+          positionLockState: positionALock,
+          escrowNftAccount,
+          operatorApproval: lockOperatorApproval,
+          rent: web3.SYSVAR_RENT_PUBKEY,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        }
+      })).to.be.rejectedWith(Error)
+    })
+
+    it('locks the position NFT in escrow', async () => {
+      unlockTime = Math.floor(Date.now() / 1000) + 5
+      let listener: number
+      let [_event, _slot] = await new Promise((resolve, _reject) => {
+        listener = coreProgram.addEventListener("PositionLockedEvent", (event, slot) => {
+          assert((event.tokenId as web3.PublicKey).equals(nftMintAKeypair.publicKey))
+          assert((event.owner as web3.PublicKey).equals(owner))
+          assert((event.unlockTime as BN).eqn(unlockTime))
+          resolve([event, slot]);
+        });
+
+        coreProgram.rpc.lockPosition(new BN(unlockTime), {
+          accounts: {
+            owner,
+            nftMint: nftMintAKeypair.publicKey,
+            tokenizedPositionState: tokenizedPositionAState,
+            nftAccount: positionANftAccount,
+            positionLockState: positionALock,
+            escrowNftAccount,
+            operatorApproval: lockOperatorApproval,
+            rent: web3.SYSVAR_RENT_PUBKEY,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+            systemProgram: SystemProgram.programId,
+          }
+        })
+      })
+      await coreProgram.removeEventListener(listener)
+
+      const nftMint = new Token(connection, nftMintAKeypair.publicKey, TOKEN_PROGRAM_ID, mintAuthority)
+      assert.equal((await nftMint.getAccountInfo(escrowNftAccount)).amount.toNumber(), 1)
+      assert.equal((await nftMint.getAccountInfo(positionANftAccount)).amount.toNumber(), 0)
+
+      const positionLockData = await coreProgram.account.positionLockState.fetch(positionALock)
+      assert(positionLockData.owner.equals(owner))
+      assert(positionLockData.unlockTime.eqn(unlockTime))
+    })
+
+    it('collects fees of the locked position', async () => {
+      await coreProgram.rpc.collectFromTokenized(new BN(10), new BN(10), {
+        accounts: collectAccounts(),
+        remainingAccounts: [{
+          pubkey: lockOperatorApproval,
+          isSigner: false,
+          isWritable: false
+        }, {
+          pubkey: nextObservationAState,
+          isSigner: false,
+          isWritable: true
+        }],
+      })
+    })
+
+    it('fails to decrease liquidity of the locked position', async () => {
+      await expect(coreProgram.rpc.decreaseLiquidity(
+        new BN(1),
+        new BN(0),
+        new BN(0),
+        new BN(0),
+        new BN(0),
+        new BN(Date.now() / 1000 + 10_000), {
+        accounts: {
+          ownerOrDelegate: owner,
+          nftAccount: escrowNftAccount,
+          tokenizedPositionState: tokenizedPositionAState,
+          factoryState,
+          poolState: poolAState,
+          corePositionState: corePositionAState,
+          tickLowerState: tickLowerAState,
+          tickUpperState: tickUpperAState,
+          bitmapLowerState: bitmapLowerAState,
+          bitmapUpperState: bitmapUpperAState,
+          lastObservationState: lastObservationAState,
+          coreProgram: coreProgram.programId
+        },
+        remainingAccounts: [{
+          pubkey: lockOperatorApproval,
+          isSigner: false,
+          isWritable: false
+        }, {
+          pubkey: nextObservationAState,
+          isSigner: false,
+          isWritable: true
+        }],
+      })).to.be.rejectedWith(Error)
+    })
+
+    it('fails to increase liquidity of the locked position', async () => {
+      await expect(coreProgram.rpc.increaseLiquidity(
+        new BN(1_000),
+        new BN(1_000),
+        new BN(0),
+        new BN(0),
+        new BN(0),
+        new BN(0),
+        new BN(Date.now() / 1000 + 10_000), {
+        accounts: {
+          payer: owner,
+          factoryState,
+          poolState: poolAState,
+          corePositionState: corePositionAState,
+          tickLowerState: tickLowerAState,
+          tickUpperState: tickUpperAState,
+          bitmapLowerState: bitmapLowerAState,
+          bitmapUpperState: bitmapUpperAState,
+          tokenAccount0: minterWallet0,
+          tokenAccount1: minterWallet1,
+          vault0: vaultA0,
+          vault1: vaultA1,
+          lastObservationState: lastObservationAState,
+          tokenizedPositionState: tokenizedPositionAState,
+          nftAccount: escrowNftAccount,
+          coreProgram: coreProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        remainingAccounts: [{
+          pubkey: nextObservationAState,
+          isSigner: false,
+          isWritable: true
+        }],
+      })).to.be.rejectedWith(Error)
+    })
+
+    it('extends the lock', async () => {
+      await expect(coreProgram.rpc.extendLock(new BN(unlockTime), {
+        accounts: {
+          owner,
+          positionLockState: positionALock,
+        }
+      })).to.be.rejectedWith(Error)
+
+      unlockTime += 1
+      await coreProgram.rpc.extendLock(new BN(unlockTime), {
+        accounts: {
+          owner,
+          positionLockState: positionALock,
+        }
+      })
+      const positionLockData = await coreProgram.account.positionLockState.fetch(positionALock)
+      assert(positionLockData.unlockTime.eqn(unlockTime))
+    })
+
+    it('fails to unlock before the unlock time', async () => {
+      await expect(coreProgram.rpc.unlockPosition({
+        accounts: unlockAccounts(),
+      })).to.be.rejectedWith(Error)
+    })
+
+    it('unlocks the position NFT after the unlock time', async () => {
+      await new Promise(r => setTimeout(r, (unlockTime + 2) * 1000 - Date.now()))
+
+      await coreProgram.rpc.unlockPosition({
+        accounts: unlockAccounts(),
+      })
+
+      const nftMint = new Token(connection, nftMintAKeypair.publicKey, TOKEN_PROGRAM_ID, mintAuthority)
+      assert.equal((await nftMint.getAccountInfo(positionANftAccount)).amount.toNumber(), 1)
+      assert.isNull(await connection.getAccountInfo(positionALock))
+      assert.isNull(await connection.getAccountInfo(escrowNftAccount))
+      assert.isNull(await connection.getAccountInfo(lockOperatorApproval))
+    })
+  })
+
+  describe('#zap_out', () => {
+    const deadline = new BN(Date.now() / 1000 + 10_000)
+
+    const zapOutAccounts = () => ({
+      nftMint: nftMintAKeypair.publicKey,
+      nftAccount: positionANftAccount,
+      tokenizedPositionState: tokenizedPositionAState,
+      factoryState,
+      poolState: poolAState,
+      corePositionState: corePositionAState,
+      tickLowerState: tickLowerAState,
+      tickUpperState: tickUpperAState,
+      bitmapLowerState: bitmapLowerAState,
+      bitmapUpperState: bitmapUpperAState,
+      lastObservationState: lastObservationAState,
+      vault0: vaultA0,
+      vault1: vaultA1,
+      tokenAccount0: minterWallet0,
+      tokenAccount1: minterWallet1,
+      coreProgram: coreProgram.programId,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    })
+
+    it('fails if not called by the owner', async () => {
+      await expect(coreProgram.rpc.zapOut(
+        new BN(0),
+        true,
+        new BN(0),
+        0,
+        false,
+        deadline, {
+        accounts: {
+          ownerOrDelegate: notOwner,
+          ...zapOutAccounts(),
+        },
+      })).to.be.rejectedWith(Error)
+    })
+
+    it('fails to burn the NFT of a position with liquidity', async () => {
+      const { liquidity } = await coreProgram.account.tokenizedPositionState.fetch(tokenizedPositionAState)
+      assert((liquidity as BN).gtn(0))
+
+      await expect(coreProgram.rpc.zapOut(
+        new BN(0),
+        true,
+        new BN(0),
+        0,
+        true,
+        deadline, {
+        accounts: {
+          ownerOrDelegate: owner,
+          ...zapOutAccounts(),
+        },
+        remainingAccounts: [{
+          pubkey: nextObservationAState,
+          isSigner: false,
+          isWritable: true
+        }],
+      })).to.be.rejectedWith(Error)
+    })
+  })
+
+  describe('#exact_input_single', () => {
+    // before swapping, current tick = 10 and price = 4297115210
+    // active ticks are 0 and 10
+    // entire liquidity is in token_1
+
+    const deadline = new BN(Date.now() / 1000 + 1_000_000)
+
+    it('fails if limit price is greater than current pool price', async () => {
+      const amountIn = new BN(100_000)
+      const amountOutMinimum = new BN(0)
+      const sqrtPriceLimitX32 = new BN(4297115220)
+
+      await expect(coreProgram.rpc.exactInputSingle(
+        deadline,
+        // true,
+        amountIn,
+        amountOutMinimum,
+        sqrtPriceLimitX32,
+        false,
+        {
+          accounts: {
+            signer: owner,
+            factoryState,
+            poolState: poolAState,
+            inputTokenAccount: minterWallet0,
+            outputTokenAccount: minterWallet1,
+            inputVault: vaultA0,
+            outputVault: vaultA1,
+            lastObservationState: lastObservationAState,
+            // nextObservationState: nextObservationAState,
+            coreProgram: coreProgram.programId,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          }, remainingAccounts: [{
+            pubkey: nextObservationAState,
+            isSigner: false,
+            isWritable: true
+          },{
+            pubkey: bitmapLowerAState,
+            isSigner: false,
+            isWritable: true
+          },
+          // price moves downwards in zero for one swap
+          {
+            pubkey: tickUpperAState,
+            isSigner: false,
+            isWritable: true
+          }, {
+            pubkey: tickLowerAState,
+            isSigner: false,
+            isWritable: true
+          }]
+        }
+      )).to.be.rejectedWith(Error)
+    })
+
+    it('swap upto a limit price for a zero to one swap', async () => {
+      const amountIn = new BN(100_000)
+      const amountOutMinimum = new BN(0)
+      const sqrtPriceLimitX32 = new BN(4297115200) // current price is 4297115210
+
+      const tickDataProvider = new SolanaTickDataProvider(coreProgram, {
+        token0: token0.publicKey,
+        token1: token1.publicKey,
+        fee,
+      })
+
+      const { tick: currentTick, sqrtPriceX32: currentSqrtPriceX32, liquidity: currentLiquidity } = await coreProgram.account.poolState.fetch(poolAState)
+      await tickDataProvider.eagerLoadCache(currentTick, tickSpacing)
+      // output is one tick behind actual (8 instead of 9)
+      uniPoolA = new Pool(
+        uniToken0,
+        uniToken1,
+        fee,
+        JSBI.BigInt(currentSqrtPriceX32),
+        JSBI.BigInt(currentLiquidity),
+        currentTick,
+        tickDataProvider
+      )
+
+      const [expectedAmountOut, expectedNewPool, bitmapAndTickAccounts] = await uniPoolA.getOutputAmount(
+        CurrencyAmount.fromRawAmount(uniToken0, amountIn.toNumber()),
+        JSBI.BigInt(sqrtPriceLimitX32),
+      )
+      assert.equal(expectedNewPool.sqrtRatioX32.toString(), sqrtPriceLimitX32.toString())
+
+      let listener: number
+      const swapEvent = new Promise<any>((resolve, _reject) => {
+        listener = coreProgram.addEventListener("SwapEvent", (event, _slot) => {
+          resolve(event)
+        })
+      })
+
+      const signature = await coreProgram.rpc.exactInputSingle(
+        deadline,
+        amountIn,
+        amountOutMinimum,
+        sqrtPriceLimitX32,
+        false,
+        {
+          accounts: {
+            signer: owner,
+            factoryState,
+            poolState: poolAState,
+            inputTokenAccount: minterWallet0,
+            outputTokenAccount: minterWallet1,
+            inputVault: vaultA0,
+            outputVault: vaultA1,
+            lastObservationState: lastObservationAState,
+            // nextObservationState: nextObservationAState,
+            coreProgram: coreProgram.programId,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          },
+          remainingAccounts: [
+            ...bitmapAndTickAccounts,
+            {
+              pubkey: nextObservationAState,
+              isSigner: false,
+              isWritable: true
+            },
+          ],
+          options: { commitment: 'confirmed' },
+        }
+      )
+      let poolStateData = await coreProgram.account.poolState.fetch(poolAState)
+      assert(poolStateData.sqrtPriceX32.eq(sqrtPriceLimitX32))
+
+      const event = await swapEvent
+      await coreProgram.removeEventListener(listener)
+      assert((event.sqrtPriceStartX32 as BN).eq(currentSqrtPriceX32))
+      assert((event.sqrtPriceX32 as BN).eq(sqrtPriceLimitX32))
+      assert((event.lpFee0 as BN).gtn(0))
+      assert((event.lpFee1 as BN).eqn(0))
+      assert((event.protocolFee1 as BN).eqn(0))
+      assert.equal(event.ticksCrossed, 0)
+
+      const result = coreProgram.coder.types.decode(
+        'ExactInputResult',
+        await transactionReturnData(connection, signature)
+      )
+      assert((result.amountIn as BN).eq(event.amount0))
+      assert((result.amountOut as BN).eq((event.amount1 as BN).neg()))
+      assert((result.sqrtPriceX32 as BN).eq(sqrtPriceLimitX32))
+      assert.equal(result.tick, poolStateData.tick)
+
+      console.log('tick after swap', poolStateData.tick, 'price', poolStateData.sqrtPriceX32.toString())
+      uniPoolA = expectedNewPool
+    })
+
+    it('benchmarks compute units used by a swap across bitmap words', async () => {
+      const { observationIndex, observationCardinalityNext } = await coreProgram.account.poolState.fetch(poolAState)
+      const [lastObservation] = await PublicKey.findProgramAddress(
+        [
+          OBSERVATION_SEED,
+          token0.publicKey.toBuffer(),
+          token1.publicKey.toBuffer(),
+          u32ToSeed(fee),
+          u16ToSeed(observationIndex)
+        ],
+        coreProgram.programId
+      )
+      const [nextObservation] = await PublicKey.findProgramAddress(
+        [
+          OBSERVATION_SEED,
+          token0.publicKey.toBuffer(),
+          token1.publicKey.toBuffer(),
+          u32ToSeed(fee),
+          u16ToSeed((observationIndex + 1) % observationCardinalityNext)
+        ],
+        coreProgram.programId
+      )
+
+      const amountIn = new BN(100_000)
+      const [_expectedAmountOut, _expectedNewPool, bitmapAndTickAccounts] = await uniPoolA.getOutputAmount(
+        CurrencyAmount.fromRawAmount(uniToken0, amountIn.toNumber())
+      )
+
+      const { raw: logs } = await coreProgram.simulate.exactInputSingle(
+        deadline,
+        amountIn,
+        new BN(0),
+        new BN(0),
+        false,
+        {
+          accounts: {
+            signer: owner,
+            factoryState,
+            poolState: poolAState,
+            inputTokenAccount: minterWallet0,
+            outputTokenAccount: minterWallet1,
+            inputVault: vaultA0,
+            outputVault: vaultA1,
+            lastObservationState: lastObservation,
+            coreProgram: coreProgram.programId,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          },
+          remainingAccounts: [
+            ...bitmapAndTickAccounts,
+            {
+              pubkey: nextObservation,
+              isSigner: false,
+              isWritable: true
+            },
+          ],
+        }
+      )
+      const computeUnits = computeUnitsConsumed(logs as string[], coreProgram.programId.toString())
+      assert.isAbove(computeUnits, 0)
+      assert.isBelow(computeUnits, 200_000)
+    })
+
+    it('fills nothing in partial fill mode if no bitmap accounts are supplied', async () => {
+      const { observationIndex } = await coreProgram.account.poolState.fetch(poolAState)
+      const [lastObservation] = await PublicKey.findProgramAddress(
+        [
+          OBSERVATION_SEED,
+          token0.publicKey.toBuffer(),
+          token1.publicKey.toBuffer(),
+          u32ToSeed(fee),
+          u16ToSeed(observationIndex)
+        ],
+        coreProgram.programId
+      )
+      const amountIn = new BN(100_000)
+      const accounts = {
+        signer: owner,
+        factoryState,
+        poolState: poolAState,
+        inputTokenAccount: minterWallet0,
+        outputTokenAccount: minterWallet1,
+        inputVault: vaultA0,
+        outputVault: vaultA1,
+        lastObservationState: lastObservation,
+        coreProgram: coreProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      }
+
+      await expect(coreProgram.simulate.exactInputSingle(
+        deadline,
+        amountIn,
+        new BN(0),
+        new BN(0),
+        false,
+        { accounts }
+      )).to.be.rejectedWith(Error)
+
+      const { events } = await coreProgram.simulate.exactInputSingle(
+        deadline,
+        amountIn,
+        new BN(0),
+        new BN(0),
+        true,
+        { accounts }
+      )
+      const swapEvent = events.find(event => event.name == 'SwapEvent')
+      assert((swapEvent.data.amountSpecifiedRemaining as BN).eq(amountIn))
+      assert((swapEvent.data.amount0 as BN).eqn(0))
+      assert((swapEvent.data.amount1 as BN).eqn(0))
+    })
+
+    it('swaps to a target price and charges only the consumed input', async () => {
+      const { observationIndex, observationCardinalityNext, sqrtPriceX32 } = await coreProgram.account.poolState.fetch(poolAState)
+      const [lastObservation] = await PublicKey.findProgramAddress(
+        [
+          OBSERVATION_SEED,
+          token0.publicKey.toBuffer(),
+          token1.publicKey.toBuffer(),
+          u32ToSeed(fee),
+          u16ToSeed(observationIndex)
+        ],
+        coreProgram.programId
+      )
+      const [nextObservation] = await PublicKey.findProgramAddress(
+        [
+          OBSERVATION_SEED,
+          token0.publicKey.toBuffer(),
+          token1.publicKey.toBuffer(),
+          u32ToSeed(fee),
+          u16ToSeed((observationIndex + 1) % observationCardinalityNext)
+        ],
+        coreProgram.programId
+      )
+      const maxAmountIn = new BN(100_000)
+      const [_expectedAmountOut, _expectedNewPool, bitmapAndTickAccounts] = await uniPoolA.getOutputAmount(
+        CurrencyAmount.fromRawAmount(uniToken0, maxAmountIn.toNumber())
+      )
+      const accounts = {
+        signer: owner,
+        factoryState,
+        poolState: poolAState,
+        inputTokenAccount: minterWallet0,
+        outputTokenAccount: minterWallet1,
+        inputVault: vaultA0,
+        outputVault: vaultA1,
+        lastObservationState: lastObservation,
+        coreProgram: coreProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      }
+      const remainingAccounts = [
+        ...bitmapAndTickAccounts,
+        {
+          pubkey: nextObservation,
+          isSigner: false,
+          isWritable: true
+        },
+      ]
+
+      // price moves downwards in zero for one swap
+      await expect(coreProgram.simulate.swapToPrice(
+        deadline,
+        (sqrtPriceX32 as BN).addn(1000),
+        maxAmountIn,
+        { accounts, remainingAccounts }
+      )).to.be.rejectedWith(Error)
+
+      const targetSqrtPriceX32 = (sqrtPriceX32 as BN).subn(1000)
+      const { events } = await coreProgram.simulate.swapToPrice(
+        deadline,
+        targetSqrtPriceX32,
+        maxAmountIn,
+        { accounts, remainingAccounts }
+      )
+      const swapEvent = events.find(event => event.name == 'SwapEvent')
+      assert((swapEvent.data.sqrtPriceX32 as BN).gte(targetSqrtPriceX32))
+      assert((swapEvent.data.amount0 as BN).lte(maxAmountIn))
+    })
+
+    it('performs a zero for one swap without a limit price', async () => {
+      let poolStateDataBefore = await coreProgram.account.poolState.fetch(poolAState)
+      console.log('pool price', poolStateDataBefore.sqrtPriceX32.toNumber())
+      console.log('pool tick', poolStateDataBefore.tick)
+
+      const {
+        observationIndex,
+        observationCardinalityNext
+      } = await coreProgram.account.poolState.fetch(poolAState)
+
+      lastObservationAState = (await PublicKey.findProgramAddress(
+        [
+          OBSERVATION_SEED,
+          token0.publicKey.toBuffer(),
+          token1.publicKey.toBuffer(),
+          u32ToSeed(fee),
+          u16ToSeed(observationIndex)
+        ],
+        coreProgram.programId
+      ))[0]
+
+      nextObservationAState = (await PublicKey.findProgramAddress(
+        [
+          OBSERVATION_SEED,
+          token0.publicKey.toBuffer(),
+          token1.publicKey.toBuffer(),
+          u32ToSeed(fee),
+          u16ToSeed((observationIndex + 1) % observationCardinalityNext)
+        ],
+        coreProgram.programId
+      ))[0]
+
+      const amountIn = new BN(100_000)
+      const amountOutMinimum = new BN(0)
+      const sqrtPriceLimitX32 = new BN(0)
+
+      console.log('pool tick', uniPoolA.tickCurrent, 'price', uniPoolA.sqrtRatioX32.toString())
+      const [expectedAmountOut, expectedNewPool, bitmapAndTickAccounts] = await uniPoolA.getOutputAmount(
+        CurrencyAmount.fromRawAmount(uniToken0, amountIn.toNumber())
+      )
+      console.log('expected pool', expectedNewPool)
+
+      await coreProgram.rpc.exactInputSingle(
+        deadline,
+        // true,
+        amountIn,
+        amountOutMinimum,
+        sqrtPriceLimitX32,
+        false,
+        {
+          accounts: {
+            signer: owner,
+            factoryState,
+            poolState: poolAState,
+            inputTokenAccount: minterWallet0,
+            outputTokenAccount: minterWallet1,
+            inputVault: vaultA0,
+            outputVault: vaultA1,
+            lastObservationState: lastObservationAState,
+            // nextObservationState: nextObservationAState,
+            coreProgram: coreProgram.programId,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          },
+          remainingAccounts: [
+            ...bitmapAndTickAccounts,
+            {
+              pubkey: nextObservationAState,
+              isSigner: false,
+              isWritable: true
+            },
+          ],
+        }
+      )
+      const poolStateDataAfter = await coreProgram.account.poolState.fetch(poolAState)
+      console.log('pool price after', poolStateDataAfter.sqrtPriceX32.toNumber())
+      console.log('pool tick after', poolStateDataAfter.tick)
+
+      uniPoolA = expectedNewPool
+    })
+  })
+
+  describe('#exact_input', () => {
+
+    const deadline = new BN(Date.now() / 1000 + 10_000)
+    it('performs a single pool swap', async () => {
+      const poolStateDataBefore = await coreProgram.account.poolState.fetch(poolAState)
+      console.log('pool price', poolStateDataBefore.sqrtPriceX32.toNumber())
+      console.log('pool tick', poolStateDataBefore.tick)
+
+      const {
+        observationIndex,
+        observationCardinalityNext
+      } = await coreProgram.account.poolState.fetch(poolAState)
+
+      lastObservationAState = (await PublicKey.findProgramAddress(
+        [
+          OBSERVATION_SEED,
+          token0.publicKey.toBuffer(),
+          token1.publicKey.toBuffer(),
+          u32ToSeed(fee),
+          u16ToSeed(observationIndex)
+        ],
+        coreProgram.programId
+      ))[0]
+
+      nextObservationAState = (await PublicKey.findProgramAddress(
+        [
+          OBSERVATION_SEED,
+          token0.publicKey.toBuffer(),
+          token1.publicKey.toBuffer(),
+          u32ToSeed(fee),
+          u16ToSeed((observationIndex + 1) % observationCardinalityNext)
+        ],
+        coreProgram.programId
+      ))[0]
+
+      const amountIn = new BN(100_000)
+      const amountOutMinimum = new BN(0)
+      const [expectedAmountOut, expectedNewPool, swapAccounts] = await uniPoolA.getOutputAmount(
+        CurrencyAmount.fromRawAmount(uniToken0, amountIn.toNumber())
+      )
+      console.log('expected pool', expectedNewPool)
+      const { amount: outputBefore } = await token1.getAccountInfo(minterWallet1)
+
+      const signature = await coreProgram.rpc.exactInput(
+        deadline,
+        amountIn,
+        amountOutMinimum,
+        [{ additionalAccounts: 2, sqrtPriceLimitX32: new BN(0), amountOutMinimum: new BN(0) }],
+        {
+          accounts: {
+            signer: owner,
+            factoryState,
+            inputTokenAccount: minterWallet0,
+            coreProgram: coreProgram.programId,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          }, remainingAccounts: [{
+            pubkey: poolAState,
+            isSigner: false,
+            isWritable: true
+          }, {
+            pubkey: minterWallet1, // outputTokenAccount
+            isSigner: false,
+            isWritable: true
+          }, {
+            pubkey: vaultA0, // input vault
+            isSigner: false,
+            isWritable: true
+          }, {
+            pubkey: vaultA1, // output vault
+            isSigner: false,
+            isWritable: true
+          }, {
+            pubkey: lastObservationAState,
+            isSigner: false,
+            isWritable: true
+          },
+          ...swapAccounts,
+          {
+            pubkey: nextObservationAState,
+            isSigner: false,
+            isWritable: true
+          },
+          ],
+          options: { commitment: 'confirmed' },
+        }
+      )
+
+      const poolStateDataAfter = await coreProgram.account.poolState.fetch(poolAState)
+      console.log('pool price after', poolStateDataAfter.sqrtPriceX32.toNumber())
+      console.log('pool tick after', poolStateDataAfter.tick)
+
+      const result = coreProgram.coder.types.decode(
+        'ExactInputResult',
+        await transactionReturnData(connection, signature)
+      )
+      const { amount: outputAfter } = await token1.getAccountInfo(minterWallet1)
+      assert((result.amountIn as BN).eq(amountIn))
+      assert((result.amountOut as BN).eq(outputAfter.sub(outputBefore)))
+      assert((result.sqrtPriceX32 as BN).eq(poolStateDataAfter.sqrtPriceX32))
+      assert.equal(result.tick, poolStateDataAfter.tick)
+    })
+
+    it('fails if a hop returns less than its minimum output', async () => {
+      const {
+        observationIndex,
+        observationCardinalityNext
+      } = await coreProgram.account.poolState.fetch(poolAState)
+
+      lastObservationAState = (await PublicKey.findProgramAddress(
+        [
+          OBSERVATION_SEED,
+          token0.publicKey.toBuffer(),
+          token1.publicKey.toBuffer(),
+          u32ToSeed(fee),
+          u16ToSeed(observationIndex)
+        ],
+        coreProgram.programId
+      ))[0]
+
+      nextObservationAState = (await PublicKey.findProgramAddress(
+        [
+          OBSERVATION_SEED,
+          token0.publicKey.toBuffer(),
+          token1.publicKey.toBuffer(),
+          u32ToSeed(fee),
+          u16ToSeed((observationIndex + 1) % observationCardinalityNext)
+        ],
+        coreProgram.programId
+      ))[0]
+
+      await expect(coreProgram.rpc.exactInput(
+        deadline,
+        new BN(100_000),
+        new BN(0),
+        [{ additionalAccounts: 2, sqrtPriceLimitX32: new BN(0), amountOutMinimum: new BN(100_000) }],
+        {
+          accounts: {
+            signer: owner,
+            factoryState,
+            inputTokenAccount: minterWallet0,
+            coreProgram: coreProgram.programId,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          }, remainingAccounts: [{
+            pubkey: poolAState,
+            isSigner: false,
+            isWritable: true
+          }, {
+            pubkey: minterWallet1, // outputTokenAccount
+            isSigner: false,
+            isWritable: true
+          }, {
+            pubkey: vaultA0, // input vault
+            isSigner: false,
+            isWritable: true
+          }, {
+            pubkey: vaultA1, // output vault
+            isSigner: false,
+            isWritable: true
+          }, {
+            pubkey: lastObservationAState,
+            isSigner: false,
+            isWritable: true
+          }, {
+            pubkey: bitmapLowerAState,
+            isSigner: false,
+            isWritable: true
+          }, {
+            pubkey: nextObservationAState,
+            isSigner: false,
+            isWritable: true
+          }]
+        }
+      )).to.be.rejectedWith(Error)
+    })
+
+    it('creates a second liquidity pool', async () => {
+      await coreProgram.rpc.createAndInitPool(initialPriceX32, PublicKey.default, 0, {
+        accounts: {
+          poolCreator: owner,
+          token0: token1.publicKey,
+          token1: token2.publicKey,
+          feeState,
+          poolState: poolBState,
+          initialObservationState: initialObservationStateB,
+          pairState: pairBState,
+          systemProgram: SystemProgram.programId,
+          rent: web3.SYSVAR_RENT_PUBKEY,
+        }
+      })
+      console.log('second pool created')
+
+      const {
+        observationIndex,
+        observationCardinalityNext
+      } = await coreProgram.account.poolState.fetch(poolBState)
+
+      latestObservationBState = (await PublicKey.findProgramAddress(
+        [
+          OBSERVATION_SEED,
+          token1.publicKey.toBuffer(),
+          token2.publicKey.toBuffer(),
+          u32ToSeed(fee),
+          u16ToSeed(observationIndex)
+        ],
+        coreProgram.programId
+      ))[0]
+
+      nextObservationBState = (await PublicKey.findProgramAddress(
+        [
+          OBSERVATION_SEED,
+          token1.publicKey.toBuffer(),
+          token2.publicKey.toBuffer(),
+          u32ToSeed(fee),
+          u16ToSeed((observationIndex + 1) % observationCardinalityNext)
+        ],
+        coreProgram.programId
+      ))[0]
+
+      // create tick and bitmap accounts
+      // can't combine with createTokenizedPosition due to size limit
+
+      const tx = new web3.Transaction()
+      tx.recentBlockhash = (await connection.getRecentBlockhash()).blockhash
+      tx.instructions = [
+        coreProgram.instruction.initTickAccount(tickLower, {
+          accounts: {
+            signer: owner,
+            poolState: poolBState,
+            tickState: tickLowerBState,
+            systemProgram: SystemProgram.programId,
+          }
+        }),
+        coreProgram.instruction.initTickAccount(tickUpper, {
+          accounts: {
+            signer: owner,
+            poolState: poolBState,
+            tickState: tickUpperBState,
+            systemProgram: SystemProgram.programId,
+          }
+        }),
+        coreProgram.instruction.initBitmapAccount(wordPosLower, {
+          accounts: {
+            signer: owner,
+            poolState: poolBState,
+            bitmapState: bitmapLowerBState,
+            systemProgram: SystemProgram.programId,
+          }
+        }),
+        coreProgram.instruction.initPositionAccount({
+          accounts: {
+            signer: owner,
+            recipient: factoryState,
+            poolState: poolBState,
+            tickLowerState: tickLowerBState,
+            tickUpperState: tickUpperBState,
+            positionState: corePositionBState,
+            systemProgram: SystemProgram.programId,
+          }
+        })
+      ]
+      await anchor.getProvider().send(tx)
+
+      console.log('creating tokenized position')
+      await coreProgram.rpc.mintTokenizedPosition(tickLower,
+        tickUpper,
+        amount0Desired,
+        amount1Desired,
+        new BN(0),
+        new BN(0),
+        new BN(0),
+        new BN(0),
+        deadline, {
+        accounts: {
+          minter: owner,
+          recipient: owner,
+          factoryState,
+          nftMint: nftMintBKeypair.publicKey,
+          nftAccount: positionBNftAccount,
+          poolState: poolBState,
+          corePositionState: corePositionBState,
+          tickLowerState: tickLowerBState,
+          tickUpperState: tickUpperBState,
+          bitmapLowerState: bitmapLowerBState,
+          bitmapUpperState: bitmapUpperBState,
+          tokenAccount0: minterWallet1,
+          tokenAccount1: minterWallet2,
+          vault0: vaultB1,
+          vault1: vaultB2,
+          lastObservationState: latestObservationBState,
+          tokenizedPositionState: tokenizedPositionBState,
+
+          coreProgram: coreProgram.programId,
+          systemProgram: SystemProgram.programId,
+          rent: web3.SYSVAR_RENT_PUBKEY,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID
+        },
+        remainingAccounts: [{
+          pubkey: nextObservationBState,
+          isSigner: false,
+          isWritable: true
+        }],
+        signers: [nftMintBKeypair],
+      })
+    })
+
+    it('perform a two pool swap', async () => {
+      const poolStateDataBefore = await coreProgram.account.poolState.fetch(poolAState)
+      console.log('pool price', poolStateDataBefore.sqrtPriceX32.toNumber())
+      console.log('pool tick', poolStateDataBefore.tick)
+
+      const {
+        observationIndex: observationAIndex,
+        observationCardinalityNext: observationCardinalityANext
+      } = await coreProgram.account.poolState.fetch(poolAState)
+
+      lastObservationAState = (await PublicKey.findProgramAddress(
+        [
+          OBSERVATION_SEED,
+          token0.publicKey.toBuffer(),
+          token1.publicKey.toBuffer(),
+          u32ToSeed(fee),
+          u16ToSeed(observationAIndex)
+        ],
+        coreProgram.programId
+      ))[0]
+
+      nextObservationAState = (await PublicKey.findProgramAddress(
+        [
+          OBSERVATION_SEED,
+          token0.publicKey.toBuffer(),
+          token1.publicKey.toBuffer(),
+          u32ToSeed(fee),
+          u16ToSeed((observationAIndex + 1) % observationCardinalityANext)
+        ],
+        coreProgram.programId
+      ))[0]
+
+      const {
+        observationIndex: observationBIndex,
+        observationCardinalityNext: observationCardinalityBNext
+      } = await coreProgram.account.poolState.fetch(poolBState)
+
+      latestObservationBState = (await PublicKey.findProgramAddress(
+        [
+          OBSERVATION_SEED,
+          token1.publicKey.toBuffer(),
+          token2.publicKey.toBuffer(),
+          u32ToSeed(fee),
+          u16ToSeed(observationBIndex)
+        ],
+        coreProgram.programId
+      ))[0]
+
+      nextObservationBState = (await PublicKey.findProgramAddress(
+        [
+          OBSERVATION_SEED,
+          token1.publicKey.toBuffer(),
+          token2.publicKey.toBuffer(),
+          u32ToSeed(fee),
+          u16ToSeed((observationBIndex + 1) % observationCardinalityBNext)
+        ],
+        coreProgram.programId
+      ))[0]
+
+      let vaultBalanceA0 = await token0.getAccountInfo(vaultA0)
+      let vaultBalanceA1 = await token1.getAccountInfo(vaultA1)
+      let vaultBalanceB1 = await token1.getAccountInfo(vaultB1)
+      let vaultBalanceB2 = await token2.getAccountInfo(vaultB2)
+      console.log(
+        'vault balances before',
+        vaultBalanceA0.amount.toNumber(),
+        vaultBalanceA1.amount.toNumber(),
+        vaultBalanceB1.amount.toNumber(),
+        vaultBalanceB2.amount.toNumber()
+      )
+      let token2AccountInfo = await token2.getAccountInfo(minterWallet2)
+      console.log('token 2 balance before', token2AccountInfo.amount.toNumber())
+
+      console.log('pool B address', poolBState.toString())
+
+      const amountIn = new BN(100_000)
+      const amountOutMinimum = new BN(0)
+      await coreProgram.rpc.exactInput(
+        deadline,
+        amountIn,
+        amountOutMinimum,
+        [
+          { additionalAccounts: 2, sqrtPriceLimitX32: new BN(0), amountOutMinimum: new BN(0) },
+          { additionalAccounts: 2, sqrtPriceLimitX32: new BN(0), amountOutMinimum: new BN(0) },
+        ],
+        {
+          accounts: {
+            signer: owner,
+            factoryState,
+            inputTokenAccount: minterWallet0,
+            coreProgram: coreProgram.programId,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          }, remainingAccounts: [{
+            pubkey: poolAState,
+            isSigner: false,
+            isWritable: true
+          },{
+            pubkey: minterWallet1, // outputTokenAccount
+            isSigner: false,
+            isWritable: true
+          },{
+            pubkey: vaultA0, // input vault
+            isSigner: false,
+            isWritable: true
+          },{
+            pubkey: vaultA1, // output vault
+            isSigner: false,
+            isWritable: true
+          },{
+            pubkey: lastObservationAState,
+            isSigner: false,
+            isWritable: true
+          },
+          {
+            pubkey: bitmapLowerAState,
+            isSigner: false,
+            isWritable: true
+          },
+          {
+            pubkey: nextObservationAState,
+            isSigner: false,
+            isWritable: true
+          },
+          // second pool
+          {
+            pubkey: poolBState,
+            isSigner: false,
+            isWritable: true
+          },{
+            pubkey: minterWallet2, // outputTokenAccount
+            isSigner: false,
+            isWritable: true
+          },{
+            pubkey: vaultB1, // input vault
+            isSigner: false,
+            isWritable: true
+          },{
+            pubkey: vaultB2, // output vault
+            isSigner: false,
+            isWritable: true
+          },{
+            pubkey: latestObservationBState,
+            isSigner: false,
+            isWritable: true
+          },
+            {
+            pubkey: bitmapLowerBState,
+            isSigner: false,
+            isWritable: true
+          }, {
+            pubkey: tickUpperBState,
+            isSigner: false,
+            isWritable: true
+          },
+          {
+            pubkey: nextObservationBState,
+            isSigner: false,
+            isWritable: true
+          },
+        ]
+        }
+      )
+      vaultBalanceA0 = await token0.getAccountInfo(vaultA0)
+      vaultBalanceA1 = await token1.getAccountInfo(vaultA1)
+      vaultBalanceB1 = await token1.getAccountInfo(vaultB1)
+      vaultBalanceB2 = await token2.getAccountInfo(vaultB2)
+      console.log(
+        'vault balances after',
+        vaultBalanceA0.amount.toNumber(),
+        vaultBalanceA1.amount.toNumber(),
+        vaultBalanceB1.amount.toNumber(),
+        vaultBalanceB2.amount.toNumber()
+      )
+
+      const poolStateDataAfter = await coreProgram.account.poolState.fetch(poolAState)
+      console.log('pool A price after', poolStateDataAfter.sqrtPriceX32.toNumber())
+      console.log('pool A tick after', poolStateDataAfter.tick)
+
+      token2AccountInfo = await token2.getAccountInfo(minterWallet2)
+      console.log('token 2 balance after', token2AccountInfo.amount.toNumber())
+    })
+
+    it('fails if the accounts of a hop are missing', async () => {
+      await expect(coreProgram.rpc.exactInput(
+        deadline,
+        new BN(100_000),
+        new BN(0),
+        [{ additionalAccounts: 0, sqrtPriceLimitX32: new BN(0), amountOutMinimum: new BN(0) }],
+        {
+          accounts: {
+            signer: owner,
+            factoryState,
+            inputTokenAccount: minterWallet0,
+            coreProgram: coreProgram.programId,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          }, remainingAccounts: [{
+            pubkey: poolAState,
+            isSigner: false,
+            isWritable: true
+          }]
+        }
+      )).to.be.rejectedWith(Error)
+    })
+
+    it('fails without hops', async () => {
+      await expect(coreProgram.rpc.exactInput(
+        deadline,
+        new BN(100_000),
+        new BN(0),
+        [],
+        {
+          accounts: {
+            signer: owner,
+            factoryState,
+            inputTokenAccount: minterWallet0,
+            coreProgram: coreProgram.programId,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          }
+        }
+      )).to.be.rejectedWith(Error)
+    })
+
+    it('fails if the price limit of an intermediate hop leaves input unswapped', async () => {
+      const observationStates = async (pool: web3.PublicKey, tokenA: Token, tokenB: Token) => {
+        const { observationIndex, observationCardinalityNext } = await coreProgram.account.poolState.fetch(pool)
+        const [last] = await PublicKey.findProgramAddress(
+          [
+            OBSERVATION_SEED,
+            tokenA.publicKey.toBuffer(),
+            tokenB.publicKey.toBuffer(),
+            u32ToSeed(fee),
+            u16ToSeed(observationIndex)
+          ],
+          coreProgram.programId
+        )
+        const [next] = await PublicKey.findProgramAddress(
+          [
+            OBSERVATION_SEED,
+            tokenA.publicKey.toBuffer(),
+            tokenB.publicKey.toBuffer(),
+            u32ToSeed(fee),
+            u16ToSeed((observationIndex + 1) % observationCardinalityNext)
+          ],
+          coreProgram.programId
+        )
+        return [last, next]
+      }
+      const [lastObservationA, nextObservationA] = await observationStates(poolAState, token0, token1)
+      const [lastObservationB, nextObservationB] = await observationStates(poolBState, token1, token2)
+
+      // token_1 is token_0 of pool B, so the second hop lowers its price. A limit just below
+      // the current price stops the hop after a fraction of its input
+      const { sqrtPriceX32: poolBPrice } = await coreProgram.account.poolState.fetch(poolBState)
+      const { amount: token1Before } = await token1.getAccountInfo(minterWallet1)
+
+      await expect(coreProgram.rpc.exactInput(
+        deadline,
+        new BN(100_000),
+        new BN(0),
+        [
+          { additionalAccounts: 2, sqrtPriceLimitX32: new BN(0), amountOutMinimum: new BN(0) },
+          { additionalAccounts: 3, sqrtPriceLimitX32: poolBPrice.subn(1), amountOutMinimum: new BN(0) },
+        ],
+        {
+          accounts: {
+            signer: owner,
+            factoryState,
+            inputTokenAccount: minterWallet0,
+            coreProgram: coreProgram.programId,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          }, remainingAccounts: [
+            { pubkey: poolAState, isSigner: false, isWritable: true },
+            { pubkey: minterWallet1, isSigner: false, isWritable: true }, // outputTokenAccount
+            { pubkey: vaultA0, isSigner: false, isWritable: true }, // input vault
+            { pubkey: vaultA1, isSigner: false, isWritable: true }, // output vault
+            { pubkey: lastObservationA, isSigner: false, isWritable: true },
+            { pubkey: bitmapLowerAState, isSigner: false, isWritable: true },
+            { pubkey: nextObservationA, isSigner: false, isWritable: true },
+            // second pool
+            { pubkey: poolBState, isSigner: false, isWritable: true },
+            { pubkey: minterWallet2, isSigner: false, isWritable: true }, // outputTokenAccount
+            { pubkey: vaultB1, isSigner: false, isWritable: true }, // input vault
+            { pubkey: vaultB2, isSigner: false, isWritable: true }, // output vault
+            { pubkey: lastObservationB, isSigner: false, isWritable: true },
+            { pubkey: bitmapLowerBState, isSigner: false, isWritable: true },
+            { pubkey: tickUpperBState, isSigner: false, isWritable: true },
+            { pubkey: nextObservationB, isSigner: false, isWritable: true },
+          ]
+        }
+      )).to.be.rejectedWith(Error)
 
-    const deadline = new BN(Date.now() / 1000 + 1_000_000)
+      // no intermediate tokens are left behind
+      const { amount: token1After } = await token1.getAccountInfo(minterWallet1)
+      assert(token1After.eq(token1Before))
+    })
+  })
 
-    it('fails if limit price is greater than current pool price', async () => {
-      const amountIn = new BN(100_000)
-      const amountOutMinimum = new BN(0)
-      const sqrtPriceLimitX32 = new BN(4297115220)
+  describe('#exact_input_split', () => {
+    const deadline = new BN(Date.now() / 1000 + 10_000)
 
-      await expect(coreProgram.rpc.exactInputSingle(
+    it('fails if leg shares do not add up to 10000 bps', async () => {
+      await expect(coreProgram.rpc.exactInputSplit(
         deadline,
-        // true,
-        amountIn,
-        amountOutMinimum,
-        sqrtPriceLimitX32,
+        new BN(100_000),
+        new BN(0),
+        [{ shareBps: 5000, additionalAccounts: 0 }, { shareBps: 4000, additionalAccounts: 0 }],
         {
           accounts: {
             signer: owner,
             factoryState,
-            poolState: poolAState,
             inputTokenAccount: minterWallet0,
             outputTokenAccount: minterWallet1,
-            inputVault: vaultA0,
-            outputVault: vaultA1,
-            lastObservationState: lastObservationAState,
-            // nextObservationState: nextObservationAState,
             coreProgram: coreProgram.programId,
             tokenProgram: TOKEN_PROGRAM_ID,
-          }, remainingAccounts: [{
-            pubkey: nextObservationAState,
-            isSigner: false,
-            isWritable: true
-          },{
-            pubkey: bitmapLowerAState,
-            isSigner: false,
-            isWritable: true
-          },
-          // price moves downwards in zero for one swap
-          {
-            pubkey: tickUpperAState,
-            isSigner: false,
-            isWritable: true
-          }, {
-            pubkey: tickLowerAState,
-            isSigner: false,
-            isWritable: true
-          }]
+          }
         }
       )).to.be.rejectedWith(Error)
     })
 
-    it('swap upto a limit price for a zero to one swap', async () => {
-      const amountIn = new BN(100_000)
-      const amountOutMinimum = new BN(0)
-      const sqrtPriceLimitX32 = new BN(4297115200) // current price is 4297115210
-
-      const tickDataProvider = new SolanaTickDataProvider(coreProgram, {
-        token0: token0.publicKey,
-        token1: token1.publicKey,
-        fee,
-      })
-
-      const { tick: currentTick, sqrtPriceX32: currentSqrtPriceX32, liquidity: currentLiquidity } = await coreProgram.account.poolState.fetch(poolAState)
-      await tickDataProvider.eagerLoadCache(currentTick, tickSpacing)
-      // output is one tick behind actual (8 instead of 9)
-      uniPoolA = new Pool(
-        uniToken0,
-        uniToken1,
-        fee,
-        JSBI.BigInt(currentSqrtPriceX32),
-        JSBI.BigInt(currentLiquidity),
-        currentTick,
-        tickDataProvider
-      )
-
-      const [expectedAmountOut, expectedNewPool, bitmapAndTickAccounts] = await uniPoolA.getOutputAmount(
-        CurrencyAmount.fromRawAmount(uniToken0, amountIn.toNumber()),
-        JSBI.BigInt(sqrtPriceLimitX32),
-      )
-      assert.equal(expectedNewPool.sqrtRatioX32.toString(), sqrtPriceLimitX32.toString())
-
-      await coreProgram.rpc.exactInputSingle(
+    it('fails if a leg has a zero share', async () => {
+      await expect(coreProgram.rpc.exactInputSplit(
         deadline,
-        amountIn,
-        amountOutMinimum,
-        sqrtPriceLimitX32,
+        new BN(100_000),
+        new BN(0),
+        [{ shareBps: 10_000, additionalAccounts: 0 }, { shareBps: 0, additionalAccounts: 0 }],
         {
           accounts: {
             signer: owner,
             factoryState,
-            poolState: poolAState,
             inputTokenAccount: minterWallet0,
             outputTokenAccount: minterWallet1,
-            inputVault: vaultA0,
-            outputVault: vaultA1,
-            lastObservationState: lastObservationAState,
-            // nextObservationState: nextObservationAState,
             coreProgram: coreProgram.programId,
             tokenProgram: TOKEN_PROGRAM_ID,
-          },
-          remainingAccounts: [
-            ...bitmapAndTickAccounts,
-            {
-              pubkey: nextObservationAState,
-              isSigner: false,
-              isWritable: true
-            },
-          ],
+          }
         }
-      )
-      let poolStateData = await coreProgram.account.poolState.fetch(poolAState)
-      assert(poolStateData.sqrtPriceX32.eq(sqrtPriceLimitX32))
-
-      console.log('tick after swap', poolStateData.tick, 'price', poolStateData.sqrtPriceX32.toString())
-      uniPoolA = expectedNewPool
+      )).to.be.rejectedWith(Error)
     })
 
-    it('performs a zero for one swap without a limit price', async () => {
-      let poolStateDataBefore = await coreProgram.account.poolState.fetch(poolAState)
-      console.log('pool price', poolStateDataBefore.sqrtPriceX32.toNumber())
-      console.log('pool tick', poolStateDataBefore.tick)
-
-      const {
-        observationIndex,
-        observationCardinalityNext
-      } = await coreProgram.account.poolState.fetch(poolAState)
-
-      lastObservationAState = (await PublicKey.findProgramAddress(
-        [
-          OBSERVATION_SEED,
-          token0.publicKey.toBuffer(),
-          token1.publicKey.toBuffer(),
-          u32ToSeed(fee),
-          u16ToSeed(observationIndex)
-        ],
-        coreProgram.programId
-      ))[0]
-
-      nextObservationAState = (await PublicKey.findProgramAddress(
-        [
-          OBSERVATION_SEED,
-          token0.publicKey.toBuffer(),
-          token1.publicKey.toBuffer(),
-          u32ToSeed(fee),
-          u16ToSeed((observationIndex + 1) % observationCardinalityNext)
-        ],
-        coreProgram.programId
-      ))[0]
-
-      const amountIn = new BN(100_000)
-      const amountOutMinimum = new BN(0)
-      const sqrtPriceLimitX32 = new BN(0)
-
-      console.log('pool tick', uniPoolA.tickCurrent, 'price', uniPoolA.sqrtRatioX32.toString())
-      const [expectedAmountOut, expectedNewPool, bitmapAndTickAccounts] = await uniPoolA.getOutputAmount(
-        CurrencyAmount.fromRawAmount(uniToken0, amountIn.toNumber())
-      )
-      console.log('expected pool', expectedNewPool)
-
-      await coreProgram.rpc.exactInputSingle(
+    it('fails if the accounts of a leg are missing', async () => {
+      await expect(coreProgram.rpc.exactInputSplit(
         deadline,
-        // true,
-        amountIn,
-        amountOutMinimum,
-        sqrtPriceLimitX32,
+        new BN(100_000),
+        new BN(0),
+        [{ shareBps: 10_000, additionalAccounts: 0 }],
         {
           accounts: {
             signer: owner,
             factoryState,
-            poolState: poolAState,
             inputTokenAccount: minterWallet0,
             outputTokenAccount: minterWallet1,
-            inputVault: vaultA0,
-            outputVault: vaultA1,
-            lastObservationState: lastObservationAState,
-            // nextObservationState: nextObservationAState,
             coreProgram: coreProgram.programId,
             tokenProgram: TOKEN_PROGRAM_ID,
-          },
-          remainingAccounts: [
-            ...bitmapAndTickAccounts,
-            {
-              pubkey: nextObservationAState,
-              isSigner: false,
-              isWritable: true
-            },
-          ],
+          }, remainingAccounts: [{
+            pubkey: poolAState,
+            isSigner: false,
+            isWritable: true
+          }]
         }
-      )
-      const poolStateDataAfter = await coreProgram.account.poolState.fetch(poolAState)
-      console.log('pool price after', poolStateDataAfter.sqrtPriceX32.toNumber())
-      console.log('pool tick after', poolStateDataAfter.tick)
-
-      uniPoolA = expectedNewPool
+      )).to.be.rejectedWith(Error)
     })
-  })
-
-  describe('#exact_input', () => {
-
-    const deadline = new BN(Date.now() / 1000 + 10_000)
-    it('performs a single pool swap', async () => {
-      const poolStateDataBefore = await coreProgram.account.poolState.fetch(poolAState)
-      console.log('pool price', poolStateDataBefore.sqrtPriceX32.toNumber())
-      console.log('pool tick', poolStateDataBefore.tick)
 
+    it('swaps the whole input in a single leg', async () => {
       const {
         observationIndex,
         observationCardinalityNext
@@ -2878,149 +5376,233 @@ describe('cyclos-core', async () => {
       ))[0]
 
       const amountIn = new BN(100_000)
-      const amountOutMinimum = new BN(0)
-      const [expectedAmountOut, expectedNewPool, swapAccounts] = await uniPoolA.getOutputAmount(
-        CurrencyAmount.fromRawAmount(uniToken0, amountIn.toNumber())
-      )
-      console.log('expected pool', expectedNewPool)
+      const token1Before = await token1.getAccountInfo(minterWallet1)
 
-      await coreProgram.rpc.exactInput(
-        deadline,
-        amountIn,
-        amountOutMinimum,
-        Buffer.from([2]),
-        {
-          accounts: {
-            signer: owner,
-            factoryState,
-            inputTokenAccount: minterWallet0,
-            coreProgram: coreProgram.programId,
-            tokenProgram: TOKEN_PROGRAM_ID,
-          }, remainingAccounts: [{
-            pubkey: poolAState,
-            isSigner: false,
-            isWritable: true
-          }, {
-            pubkey: minterWallet1, // outputTokenAccount
-            isSigner: false,
-            isWritable: true
-          }, {
-            pubkey: vaultA0, // input vault
-            isSigner: false,
-            isWritable: true
-          }, {
-            pubkey: vaultA1, // output vault
-            isSigner: false,
-            isWritable: true
-          }, {
-            pubkey: lastObservationAState,
-            isSigner: false,
-            isWritable: true
-          },
-          ...swapAccounts,
-          {
-            pubkey: nextObservationAState,
-            isSigner: false,
-            isWritable: true
-          },
-          ]
-        }
-      )
+      let listener: number
+      let [_event, _slot] = await new Promise((resolve, _reject) => {
+        listener = coreProgram.addEventListener("SplitSwapEvent", (event, slot) => {
+          assert((event.amountIn as BN).eq(amountIn))
+          assert((event.amountsIn[0] as BN).eq(amountIn))
+          assert((event.pools[0] as PublicKey).equals(poolAState))
+          resolve([event, slot]);
+        });
 
-      const poolStateDataAfter = await coreProgram.account.poolState.fetch(poolAState)
-      console.log('pool price after', poolStateDataAfter.sqrtPriceX32.toNumber())
-      console.log('pool tick after', poolStateDataAfter.tick)
-    })
+        coreProgram.rpc.exactInputSplit(
+          deadline,
+          amountIn,
+          new BN(0),
+          [{ shareBps: 10_000, additionalAccounts: 2 }],
+          {
+            accounts: {
+              signer: owner,
+              factoryState,
+              inputTokenAccount: minterWallet0,
+              outputTokenAccount: minterWallet1,
+              coreProgram: coreProgram.programId,
+              tokenProgram: TOKEN_PROGRAM_ID,
+            }, remainingAccounts: [{
+              pubkey: poolAState,
+              isSigner: false,
+              isWritable: true
+            }, {
+              pubkey: vaultA0, // input vault
+              isSigner: false,
+              isWritable: true
+            }, {
+              pubkey: vaultA1, // output vault
+              isSigner: false,
+              isWritable: true
+            }, {
+              pubkey: lastObservationAState,
+              isSigner: false,
+              isWritable: true
+            }, {
+              pubkey: bitmapLowerAState,
+              isSigner: false,
+              isWritable: true
+            }, {
+              pubkey: nextObservationAState,
+              isSigner: false,
+              isWritable: true
+            }]
+          }
+        )
+      });
+      await coreProgram.removeEventListener(listener);
 
-    it('creates a second liquidity pool', async () => {
-      await coreProgram.rpc.createAndInitPool(initialPriceX32, {
-        accounts: {
-          poolCreator: owner,
-          token0: token1.publicKey,
-          token1: token2.publicKey,
-          feeState,
-          poolState: poolBState,
-          initialObservationState: initialObservationStateB,
-          systemProgram: SystemProgram.programId,
-          rent: web3.SYSVAR_RENT_PUBKEY,
-        }
-      })
-      console.log('second pool created')
+      const token1After = await token1.getAccountInfo(minterWallet1)
+      assert(token1After.amount.gt(token1Before.amount))
+    })
+  })
 
+  describe('Completely close position and deallocate ticks', () => {
+    it('update observation accounts', async () => {
       const {
         observationIndex,
         observationCardinalityNext
-      } = await coreProgram.account.poolState.fetch(poolBState)
+      } = await coreProgram.account.poolState.fetch(poolAState)
 
-      latestObservationBState = (await PublicKey.findProgramAddress(
-        [
-          OBSERVATION_SEED,
-          token1.publicKey.toBuffer(),
-          token2.publicKey.toBuffer(),
-          u32ToSeed(fee),
-          u16ToSeed(observationIndex)
-        ],
-        coreProgram.programId
-      ))[0]
+      const { blockTimestamp: lastBlockTime } = await coreProgram.account.observationState.fetch(lastObservationAState)
 
-      nextObservationBState = (await PublicKey.findProgramAddress(
-        [
-          OBSERVATION_SEED,
-          token1.publicKey.toBuffer(),
-          token2.publicKey.toBuffer(),
-          u32ToSeed(fee),
-          u16ToSeed((observationIndex + 1) % observationCardinalityNext)
-        ],
-        coreProgram.programId
-      ))[0]
+      const slot = await connection.getSlot()
+      const blockTimestamp = await connection.getBlockTime(slot)
 
-      // create tick and bitmap accounts
-      // can't combine with createTokenizedPosition due to size limit
+      // If current observation account will expire in 3 seconds, we sleep for this time
+      // before recalculating the observation states
+      if (Math.floor(lastBlockTime / 14) == Math.floor(blockTimestamp / 14) && lastBlockTime % 14 >= 11) {
+        await new Promise(r => setTimeout(r, 3000))
+      }
+      if (Math.floor(lastBlockTime / 14) > Math.floor(blockTimestamp / 14)) {
+        lastObservationAState = (await PublicKey.findProgramAddress(
+          [
+            OBSERVATION_SEED,
+            token0.publicKey.toBuffer(),
+            token1.publicKey.toBuffer(),
+            u32ToSeed(fee),
+            u16ToSeed(observationIndex)
+          ],
+          coreProgram.programId
+        ))[0]
 
-      const tx = new web3.Transaction()
-      tx.recentBlockhash = (await connection.getRecentBlockhash()).blockhash
+        nextObservationAState = (await PublicKey.findProgramAddress(
+          [
+            OBSERVATION_SEED,
+            token0.publicKey.toBuffer(),
+            token1.publicKey.toBuffer(),
+            u32ToSeed(fee),
+            u16ToSeed((observationIndex + 1) % observationCardinalityNext)
+          ],
+          coreProgram.programId
+        ))[0]
+      }
+    })
+
+    it('burn entire of the position liquidity as owner', async () => {
+      const { liquidity } = await coreProgram.account.tokenizedPositionState.fetch(tokenizedPositionAState)
+      console.log('liquidity in position', liquidity)
+      const deadline = new BN(Date.now() / 1000 + 10_000)
+
+      const tx = new Transaction()
       tx.instructions = [
-        coreProgram.instruction.initTickAccount(tickLower, {
-          accounts: {
-            signer: owner,
-            poolState: poolBState,
-            tickState: tickLowerBState,
-            systemProgram: SystemProgram.programId,
-          }
-        }),
-        coreProgram.instruction.initTickAccount(tickUpper, {
+        coreProgram.instruction.decreaseLiquidity(
+          liquidity,
+          new BN(0),
+          new BN(0),
+          new BN(0),
+          new BN(0),
+          deadline, {
           accounts: {
-            signer: owner,
-            poolState: poolBState,
-            tickState: tickUpperBState,
-            systemProgram: SystemProgram.programId,
-          }
-        }),
-        coreProgram.instruction.initBitmapAccount(wordPosLower, {
+            ownerOrDelegate: owner,
+            nftAccount: positionANftAccount,
+            tokenizedPositionState: tokenizedPositionAState,
+            factoryState,
+            poolState: poolAState,
+            corePositionState: corePositionAState,
+            tickLowerState: tickLowerAState,
+            tickUpperState: tickUpperAState,
+            bitmapLowerState: bitmapLowerAState,
+            bitmapUpperState: bitmapUpperAState,
+            lastObservationState: lastObservationAState,
+            coreProgram: coreProgram.programId
+          },
+          remainingAccounts: [{
+            pubkey: nextObservationAState,
+            isSigner: false,
+            isWritable: true
+          }],
+        }
+        ),
+        coreProgram.instruction.closeTickAccount({
           accounts: {
-            signer: owner,
-            poolState: poolBState,
-            bitmapState: bitmapLowerBState,
-            systemProgram: SystemProgram.programId,
+            recipient: owner,
+            tickState: tickLowerAState,
           }
         }),
-        coreProgram.instruction.initPositionAccount({
+        coreProgram.instruction.closeTickAccount({
           accounts: {
-            signer: owner,
-            recipient: factoryState,
-            poolState: poolBState,
-            tickLowerState: tickLowerBState,
-            tickUpperState: tickUpperBState,
-            positionState: corePositionBState,
-            systemProgram: SystemProgram.programId,
+            recipient: owner,
+            tickState: tickUpperAState,
           }
         })
       ]
+      tx.recentBlockhash = (await connection.getRecentBlockhash()).blockhash
       await anchor.getProvider().send(tx)
+    })
+  })
 
-      console.log('creating tokenized position')
-      await coreProgram.rpc.mintTokenizedPosition(amount0Desired,
-        amount1Desired,
+  describe('#mint_tokenized_position with uninitialized accounts', () => {
+    const newTickLower = 20
+    const newTickUpper = 30
+    const nftMintNewKeypair = new Keypair()
+    let newTickLowerState: web3.PublicKey
+    let newTickUpperState: web3.PublicKey
+    let newCorePositionState: web3.PublicKey
+    let newTokenizedPositionState: web3.PublicKey
+    let newPositionNftAccount: web3.PublicKey
+
+    it('creates missing tick and position accounts when minting', async () => {
+      [newTickLowerState] = await PublicKey.findProgramAddress([
+        TICK_SEED,
+        token0.publicKey.toBuffer(),
+        token1.publicKey.toBuffer(),
+        u32ToSeed(fee),
+        u32ToSeed(newTickLower)
+      ],
+        coreProgram.programId
+      )
+      [newTickUpperState] = await PublicKey.findProgramAddress([
+        TICK_SEED,
+        token0.publicKey.toBuffer(),
+        token1.publicKey.toBuffer(),
+        u32ToSeed(fee),
+        u32ToSeed(newTickUpper)
+      ],
+        coreProgram.programId
+      )
+      [newCorePositionState] = await PublicKey.findProgramAddress([
+        POSITION_SEED,
+        token0.publicKey.toBuffer(),
+        token1.publicKey.toBuffer(),
+        u32ToSeed(fee),
+        factoryState.toBuffer(),
+        u32ToSeed(newTickLower),
+        u32ToSeed(newTickUpper)
+      ],
+        coreProgram.programId
+      )
+      [newTokenizedPositionState] = await PublicKey.findProgramAddress([
+        POSITION_SEED,
+        nftMintNewKeypair.publicKey.toBuffer()
+      ],
+        coreProgram.programId
+      )
+      newPositionNftAccount = await Token.getAssociatedTokenAddress(
+        ASSOCIATED_TOKEN_PROGRAM_ID,
+        TOKEN_PROGRAM_ID,
+        nftMintNewKeypair.publicKey,
+        owner,
+      )
+      const { observationIndex } = await coreProgram.account.poolState.fetch(poolAState)
+      const [lastObservation] = await PublicKey.findProgramAddress(
+        [
+          OBSERVATION_SEED,
+          token0.publicKey.toBuffer(),
+          token1.publicKey.toBuffer(),
+          u32ToSeed(fee),
+          u16ToSeed(observationIndex)
+        ],
+        coreProgram.programId
+      )
+      assert.isNull(await connection.getAccountInfo(newTickLowerState))
+
+      const deadline = new BN(Date.now() / 1000 + 10_000)
+      // the range is above the current price and only needs token_0
+      await coreProgram.rpc.mintTokenizedPosition(newTickLower,
+        newTickUpper,
+        new BN(1_000),
+        new BN(0),
+        new BN(0),
+        new BN(0),
         new BN(0),
         new BN(0),
         deadline, {
@@ -3028,306 +5610,907 @@ describe('cyclos-core', async () => {
           minter: owner,
           recipient: owner,
           factoryState,
-          nftMint: nftMintBKeypair.publicKey,
-          nftAccount: positionBNftAccount,
-          poolState: poolBState,
-          corePositionState: corePositionBState,
-          tickLowerState: tickLowerBState,
-          tickUpperState: tickUpperBState,
-          bitmapLowerState: bitmapLowerBState,
-          bitmapUpperState: bitmapUpperBState,
-          tokenAccount0: minterWallet1,
-          tokenAccount1: minterWallet2,
-          vault0: vaultB1,
-          vault1: vaultB2,
-          lastObservationState: latestObservationBState,
-          tokenizedPositionState: tokenizedPositionBState,
-
+          nftMint: nftMintNewKeypair.publicKey,
+          nftAccount: newPositionNftAccount,
+          poolState: poolAState,
+          corePositionState: newCorePositionState,
+          tickLowerState: newTickLowerState,
+          tickUpperState: newTickUpperState,
+          bitmapLowerState: bitmapLowerAState,
+          bitmapUpperState: bitmapLowerAState,
+          tokenAccount0: minterWallet0,
+          tokenAccount1: minterWallet1,
+          vault0: vaultA0,
+          vault1: vaultA1,
+          lastObservationState: lastObservation,
+          tokenizedPositionState: newTokenizedPositionState,
           coreProgram: coreProgram.programId,
           systemProgram: SystemProgram.programId,
           rent: web3.SYSVAR_RENT_PUBKEY,
           tokenProgram: TOKEN_PROGRAM_ID,
-          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID
+        },
+        signers: [nftMintNewKeypair],
+      })
+
+      const tickLowerData = await coreProgram.account.tickState.fetch(newTickLowerState)
+      assert.equal(tickLowerData.tick, newTickLower)
+      const tickUpperData = await coreProgram.account.tickState.fetch(newTickUpperState)
+      assert.equal(tickUpperData.tick, newTickUpper)
+      const { liquidity } = await coreProgram.account.tokenizedPositionState.fetch(newTokenizedPositionState)
+      assert((liquidity as BN).gtn(0))
+    })
+
+    it('creates missing tick and position accounts when minting core liquidity', async () => {
+      const coreTickLower = 40
+      const coreTickUpper = 50
+      const [coreTickLowerState] = await PublicKey.findProgramAddress([
+        TICK_SEED,
+        token0.publicKey.toBuffer(),
+        token1.publicKey.toBuffer(),
+        u32ToSeed(fee),
+        u32ToSeed(coreTickLower)
+      ],
+        coreProgram.programId
+      )
+      const [coreTickUpperState] = await PublicKey.findProgramAddress([
+        TICK_SEED,
+        token0.publicKey.toBuffer(),
+        token1.publicKey.toBuffer(),
+        u32ToSeed(fee),
+        u32ToSeed(coreTickUpper)
+      ],
+        coreProgram.programId
+      )
+      const [corePositionState] = await PublicKey.findProgramAddress([
+        POSITION_SEED,
+        token0.publicKey.toBuffer(),
+        token1.publicKey.toBuffer(),
+        u32ToSeed(fee),
+        owner.toBuffer(),
+        u32ToSeed(coreTickLower),
+        u32ToSeed(coreTickUpper)
+      ],
+        coreProgram.programId
+      )
+      const { observationIndex } = await coreProgram.account.poolState.fetch(poolAState)
+      const [lastObservation] = await PublicKey.findProgramAddress(
+        [
+          OBSERVATION_SEED,
+          token0.publicKey.toBuffer(),
+          token1.publicKey.toBuffer(),
+          u32ToSeed(fee),
+          u16ToSeed(observationIndex)
+        ],
+        coreProgram.programId
+      )
+      assert.isNull(await connection.getAccountInfo(coreTickLowerState))
+      assert.isNull(await connection.getAccountInfo(corePositionState))
+
+      // the range is above the current price and only needs token_0
+      await coreProgram.rpc.mint(coreTickLower, coreTickUpper, new BN(1_000), {
+        accounts: {
+          minter: owner,
+          tokenAccount0: minterWallet0,
+          tokenAccount1: minterWallet1,
+          vault0: vaultA0,
+          vault1: vaultA1,
+          recipient: owner,
+          poolState: poolAState,
+          tickLowerState: coreTickLowerState,
+          tickUpperState: coreTickUpperState,
+          bitmapLowerState: bitmapLowerAState,
+          bitmapUpperState: bitmapLowerAState,
+          positionState: corePositionState,
+          lastObservationState: lastObservation,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          callbackHandler: coreProgram.programId,
+          systemProgram: SystemProgram.programId,
         },
-        remainingAccounts: [{
-          pubkey: nextObservationBState,
-          isSigner: false,
-          isWritable: true
-        }],
-        signers: [nftMintBKeypair],
       })
-    })
 
-    it('perform a two pool swap', async () => {
-      const poolStateDataBefore = await coreProgram.account.poolState.fetch(poolAState)
-      console.log('pool price', poolStateDataBefore.sqrtPriceX32.toNumber())
-      console.log('pool tick', poolStateDataBefore.tick)
+      const tickLowerData = await coreProgram.account.tickState.fetch(coreTickLowerState)
+      assert.equal(tickLowerData.tick, coreTickLower)
+      const { liquidity } = await coreProgram.account.positionState.fetch(corePositionState)
+      assert((liquidity as BN).eqn(1_000))
+    })
 
+    it('closes the position account when zapping out with burn', async () => {
+      const { liquidity } = await coreProgram.account.tokenizedPositionState.fetch(newTokenizedPositionState)
       const {
-        observationIndex: observationAIndex,
-        observationCardinalityNext: observationCardinalityANext
+        observationIndex,
+        observationCardinalityNext
       } = await coreProgram.account.poolState.fetch(poolAState)
-
-      lastObservationAState = (await PublicKey.findProgramAddress(
+      const [lastObservation] = await PublicKey.findProgramAddress(
         [
           OBSERVATION_SEED,
           token0.publicKey.toBuffer(),
           token1.publicKey.toBuffer(),
           u32ToSeed(fee),
-          u16ToSeed(observationAIndex)
+          u16ToSeed(observationIndex)
         ],
         coreProgram.programId
-      ))[0]
-
-      nextObservationAState = (await PublicKey.findProgramAddress(
+      )
+      const [nextObservation] = await PublicKey.findProgramAddress(
         [
           OBSERVATION_SEED,
           token0.publicKey.toBuffer(),
           token1.publicKey.toBuffer(),
           u32ToSeed(fee),
-          u16ToSeed((observationAIndex + 1) % observationCardinalityANext)
+          u16ToSeed((observationIndex + 1) % observationCardinalityNext)
         ],
         coreProgram.programId
-      ))[0]
+      )
+      const rent = await connection.getBalance(newTokenizedPositionState)
+      const balanceBefore = await connection.getBalance(owner)
 
-      const {
-        observationIndex: observationBIndex,
-        observationCardinalityNext: observationCardinalityBNext
-      } = await coreProgram.account.poolState.fetch(poolBState)
+      // the range is above the current price, so only token_0 is withdrawn and nothing is swapped
+      const deadline = new BN(Date.now() / 1000 + 10_000)
+      await coreProgram.rpc.zapOut(
+        liquidity,
+        false,
+        new BN(0),
+        0,
+        true,
+        deadline, {
+        accounts: {
+          ownerOrDelegate: owner,
+          nftMint: nftMintNewKeypair.publicKey,
+          nftAccount: newPositionNftAccount,
+          tokenizedPositionState: newTokenizedPositionState,
+          factoryState,
+          poolState: poolAState,
+          corePositionState: newCorePositionState,
+          tickLowerState: newTickLowerState,
+          tickUpperState: newTickUpperState,
+          bitmapLowerState: bitmapLowerAState,
+          bitmapUpperState: bitmapLowerAState,
+          lastObservationState: lastObservation,
+          vault0: vaultA0,
+          vault1: vaultA1,
+          tokenAccount0: minterWallet0,
+          tokenAccount1: minterWallet1,
+          coreProgram: coreProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        remainingAccounts: [{
+          pubkey: nextObservation,
+          isSigner: false,
+          isWritable: true
+        }],
+      })
 
-      latestObservationBState = (await PublicKey.findProgramAddress(
-        [
-          OBSERVATION_SEED,
-          token1.publicKey.toBuffer(),
-          token2.publicKey.toBuffer(),
-          u32ToSeed(fee),
-          u16ToSeed(observationBIndex)
-        ],
-        coreProgram.programId
-      ))[0]
+      assert.isNull(await connection.getAccountInfo(newTokenizedPositionState))
+      const { supply } = await new Token(
+        connection,
+        nftMintNewKeypair.publicKey,
+        TOKEN_PROGRAM_ID,
+        mintAuthority
+      ).getMintInfo()
+      assert(supply.eqn(0))
+      // the signer gets back the rent, less the transaction fee
+      assert.isAbove(await connection.getBalance(owner), balanceBefore + rent - 10_000)
+    })
+  })
 
-      nextObservationBState = (await PublicKey.findProgramAddress(
-        [
-          OBSERVATION_SEED,
-          token1.publicKey.toBuffer(),
-          token2.publicKey.toBuffer(),
-          u32ToSeed(fee),
-          u16ToSeed((observationBIndex + 1) % observationCardinalityBNext)
-        ],
+  describe('#migrate_pool_state', () => {
+    it('fails for a pool with the current layout', async () => {
+      await expect(coreProgram.rpc.migratePoolState({
+        accounts: {
+          payer: owner,
+          poolState: poolAState,
+          systemProgram: SystemProgram.programId,
+        }
+      })).to.be.rejectedWith(Error)
+    })
+
+    it('fails for an account that is not a pool', async () => {
+      await expect(coreProgram.rpc.migratePoolState({
+        accounts: {
+          payer: owner,
+          poolState: feeState,
+          systemProgram: SystemProgram.programId,
+        }
+      })).to.be.rejectedWith(Error)
+    })
+  })
+
+  describe('#hooks', () => {
+    // hook flags, see states/hook.rs
+    const BEFORE_SWAP_FLAG = 1 << 0
+    const AFTER_SWAP_FLAG = 1 << 1
+    const BEFORE_MODIFY_POSITION_FLAG = 1 << 2
+    const AFTER_MODIFY_POSITION_FLAG = 1 << 3
+
+    // implements every hook as a no-op
+    const hookProgram = anchor.workspace.TestHook.programId as web3.PublicKey
+
+    // the memo program fails for accounts that did not sign, so it rejects every hook
+    const rejectingHookProgram = new PublicKey('MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr')
+
+    const hookTickLower = -100
+    const hookTickUpper = 100
+    const deadline = new BN(Date.now() / 1000 + 1_000_000)
+
+    type HookPool = {
+      token0: Token,
+      token1: Token,
+      poolState: web3.PublicKey,
+      vault0: web3.PublicKey,
+      vault1: web3.PublicKey,
+      wallet0: web3.PublicKey,
+      wallet1: web3.PublicKey,
+      observationState: web3.PublicKey,
+      tickLowerState: web3.PublicKey,
+      tickUpperState: web3.PublicKey,
+      bitmapLowerState: web3.PublicKey,
+      bitmapUpperState: web3.PublicKey,
+      corePositionState: web3.PublicKey,
+    }
+    let hookedPool: HookPool
+    let rejectingPool: HookPool
+
+    // creates a pool at tick 0 for a new token pair
+    const createHookPool = async (hookProgram: web3.PublicKey, hookFlags: number): Promise<HookPool> => {
+      let tokenA = await Token.createMint(connection, mintAuthority, mintAuthority.publicKey, null, 8, TOKEN_PROGRAM_ID)
+      let tokenB = await Token.createMint(connection, mintAuthority, mintAuthority.publicKey, null, 8, TOKEN_PROGRAM_ID)
+      if (tokenA.publicKey.toString() > tokenB.publicKey.toString()) {
+        [tokenA, tokenB] = [tokenB, tokenA]
+      }
+      const seeds = [tokenA.publicKey.toBuffer(), tokenB.publicKey.toBuffer(), u32ToSeed(fee)]
+      const [poolState] = await PublicKey.findProgramAddress([POOL_SEED, ...seeds], coreProgram.programId)
+      const [observationState] = await PublicKey.findProgramAddress(
+        [OBSERVATION_SEED, ...seeds, u16ToSeed(0)],
         coreProgram.programId
-      ))[0]
+      )
+      const [pairState] = await PublicKey.findProgramAddress(
+        [PAIR_SEED, tokenA.publicKey.toBuffer(), tokenB.publicKey.toBuffer()],
+        coreProgram.programId
+      )
+      const [tickLowerState] = await PublicKey.findProgramAddress(
+        [TICK_SEED, ...seeds, u32ToSeed(hookTickLower)],
+        coreProgram.programId
+      )
+      const [tickUpperState] = await PublicKey.findProgramAddress(
+        [TICK_SEED, ...seeds, u32ToSeed(hookTickUpper)],
+        coreProgram.programId
+      )
+      const [bitmapLowerState] = await PublicKey.findProgramAddress(
+        [BITMAP_SEED, ...seeds, u16ToSeed((hookTickLower / tickSpacing) >> 8)],
+        coreProgram.programId
+      )
+      const [bitmapUpperState] = await PublicKey.findProgramAddress(
+        [BITMAP_SEED, ...seeds, u16ToSeed((hookTickUpper / tickSpacing) >> 8)],
+        coreProgram.programId
+      )
+      const [corePositionState] = await PublicKey.findProgramAddress(
+        [POSITION_SEED, ...seeds, factoryState.toBuffer(), u32ToSeed(hookTickLower), u32ToSeed(hookTickUpper)],
+        coreProgram.programId
+      )
 
-      let vaultBalanceA0 = await token0.getAccountInfo(vaultA0)
-      let vaultBalanceA1 = await token1.getAccountInfo(vaultA1)
-      let vaultBalanceB1 = await token1.getAccountInfo(vaultB1)
-      let vaultBalanceB2 = await token2.getAccountInfo(vaultB2)
-      console.log(
-        'vault balances before',
-        vaultBalanceA0.amount.toNumber(),
-        vaultBalanceA1.amount.toNumber(),
-        vaultBalanceB1.amount.toNumber(),
-        vaultBalanceB2.amount.toNumber()
+      await coreProgram.rpc.createAndInitPool(new BN(1).shln(32), hookProgram, hookFlags, {
+        accounts: {
+          poolCreator: owner,
+          token0: tokenA.publicKey,
+          token1: tokenB.publicKey,
+          feeState,
+          poolState,
+          initialObservationState: observationState,
+          pairState,
+          systemProgram: SystemProgram.programId,
+          rent: web3.SYSVAR_RENT_PUBKEY,
+        }
+      })
+
+      const vault0 = await Token.getAssociatedTokenAddress(
+        ASSOCIATED_TOKEN_PROGRAM_ID,
+        TOKEN_PROGRAM_ID,
+        tokenA.publicKey,
+        poolState,
+        true
       )
-      let token2AccountInfo = await token2.getAccountInfo(minterWallet2)
-      console.log('token 2 balance before', token2AccountInfo.amount.toNumber())
+      const vault1 = await Token.getAssociatedTokenAddress(
+        ASSOCIATED_TOKEN_PROGRAM_ID,
+        TOKEN_PROGRAM_ID,
+        tokenB.publicKey,
+        poolState,
+        true
+      )
+      const createVaultsTx = new Transaction()
+      createVaultsTx.instructions = [
+        Token.createAssociatedTokenAccountInstruction(
+          ASSOCIATED_TOKEN_PROGRAM_ID,
+          TOKEN_PROGRAM_ID,
+          tokenA.publicKey,
+          vault0,
+          poolState,
+          owner
+        ),
+        Token.createAssociatedTokenAccountInstruction(
+          ASSOCIATED_TOKEN_PROGRAM_ID,
+          TOKEN_PROGRAM_ID,
+          tokenB.publicKey,
+          vault1,
+          poolState,
+          owner
+        ),
+      ]
+      await anchor.getProvider().send(createVaultsTx)
+
+      const wallet0 = await tokenA.createAssociatedTokenAccount(owner)
+      const wallet1 = await tokenB.createAssociatedTokenAccount(owner)
+      await tokenA.mintTo(wallet0, mintAuthority, [], 100_000_000)
+      await tokenB.mintTo(wallet1, mintAuthority, [], 100_000_000)
+
+      return {
+        token0: tokenA,
+        token1: tokenB,
+        poolState,
+        vault0,
+        vault1,
+        wallet0,
+        wallet1,
+        observationState,
+        tickLowerState,
+        tickUpperState,
+        bitmapLowerState,
+        bitmapUpperState,
+        corePositionState,
+      }
+    }
 
-      console.log('pool B address', poolBState.toString())
+    const mintHookPosition = async (pool: HookPool, remainingAccounts: web3.AccountMeta[]) => {
+      const nftMintKeypair = new Keypair()
+      const [tokenizedPositionState] = await PublicKey.findProgramAddress(
+        [POSITION_SEED, nftMintKeypair.publicKey.toBuffer()],
+        coreProgram.programId
+      )
+      const nftAccount = await Token.getAssociatedTokenAddress(
+        ASSOCIATED_TOKEN_PROGRAM_ID,
+        TOKEN_PROGRAM_ID,
+        nftMintKeypair.publicKey,
+        owner,
+      )
+      const signature = await coreProgram.rpc.mintTokenizedPosition(
+        hookTickLower,
+        hookTickUpper,
+        new BN(1_000_000),
+        new BN(1_000_000),
+        new BN(0),
+        new BN(0),
+        new BN(0),
+        new BN(0),
+        deadline, {
+        accounts: {
+          minter: owner,
+          recipient: owner,
+          factoryState,
+          nftMint: nftMintKeypair.publicKey,
+          nftAccount,
+          poolState: pool.poolState,
+          corePositionState: pool.corePositionState,
+          tickLowerState: pool.tickLowerState,
+          tickUpperState: pool.tickUpperState,
+          bitmapLowerState: pool.bitmapLowerState,
+          bitmapUpperState: pool.bitmapUpperState,
+          tokenAccount0: pool.wallet0,
+          tokenAccount1: pool.wallet1,
+          vault0: pool.vault0,
+          vault1: pool.vault1,
+          lastObservationState: pool.observationState,
+          tokenizedPositionState,
+          coreProgram: coreProgram.programId,
+          systemProgram: SystemProgram.programId,
+          rent: web3.SYSVAR_RENT_PUBKEY,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID
+        },
+        remainingAccounts,
+        signers: [nftMintKeypair],
+        options: { commitment: 'confirmed' },
+      })
+      const { meta } = await connection.getTransaction(signature, { commitment: 'confirmed' })
+      return meta.logMessages
+    }
 
-      const amountIn = new BN(100_000)
-      const amountOutMinimum = new BN(0)
-      await coreProgram.rpc.exactInput(
-        deadline,
-        amountIn,
-        amountOutMinimum,
-        Buffer.from([2, 2]),
-        {
-          accounts: {
-            signer: owner,
-            factoryState,
-            inputTokenAccount: minterWallet0,
-            coreProgram: coreProgram.programId,
-            tokenProgram: TOKEN_PROGRAM_ID,
-          }, remainingAccounts: [{
-            pubkey: poolAState,
-            isSigner: false,
-            isWritable: true
-          },{
-            pubkey: minterWallet1, // outputTokenAccount
-            isSigner: false,
-            isWritable: true
-          },{
-            pubkey: vaultA0, // input vault
-            isSigner: false,
-            isWritable: true
-          },{
-            pubkey: vaultA1, // output vault
-            isSigner: false,
-            isWritable: true
-          },{
-            pubkey: lastObservationAState,
-            isSigner: false,
-            isWritable: true
-          },
-          {
-            pubkey: bitmapLowerAState,
-            isSigner: false,
-            isWritable: true
-          },
-          {
-            pubkey: nextObservationAState,
-            isSigner: false,
-            isWritable: true
-          },
-          // second pool
-          {
-            pubkey: poolBState,
-            isSigner: false,
-            isWritable: true
-          },{
-            pubkey: minterWallet2, // outputTokenAccount
-            isSigner: false,
-            isWritable: true
-          },{
-            pubkey: vaultB1, // input vault
-            isSigner: false,
-            isWritable: true
-          },{
-            pubkey: vaultB2, // output vault
-            isSigner: false,
-            isWritable: true
-          },{
-            pubkey: latestObservationBState,
-            isSigner: false,
-            isWritable: true
-          },
-            {
-            pubkey: bitmapLowerBState,
-            isSigner: false,
-            isWritable: true
-          }, {
-            pubkey: tickUpperBState,
-            isSigner: false,
-            isWritable: true
-          },
-          {
-            pubkey: nextObservationBState,
-            isSigner: false,
-            isWritable: true
-          },
-        ]
+    // swaps token_1 for token_0 within the initialized range, which stays in bitmap word 0
+    const swapHookPool = (pool: HookPool, remainingAccounts: web3.AccountMeta[]) => coreProgram.simulate.exactInputSingle(
+      deadline,
+      new BN(1_000),
+      new BN(0),
+      new BN(0),
+      false,
+      {
+        accounts: {
+          signer: owner,
+          factoryState,
+          poolState: pool.poolState,
+          inputTokenAccount: pool.wallet1,
+          outputTokenAccount: pool.wallet0,
+          inputVault: pool.vault1,
+          outputVault: pool.vault0,
+          lastObservationState: pool.observationState,
+          coreProgram: coreProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        remainingAccounts,
+      }
+    )
+
+    const programMeta = (pubkey: web3.PublicKey) => ({ pubkey, isSigner: false, isWritable: false })
+    const swapAccounts = (pool: HookPool) => [
+      { pubkey: pool.bitmapUpperState, isSigner: false, isWritable: true },
+      { pubkey: pool.observationState, isSigner: false, isWritable: true },
+    ]
+
+    it('fails to create a pool with hook flags but no hook program', async () => {
+      await expect(createHookPool(PublicKey.default, BEFORE_SWAP_FLAG)).to.be.rejectedWith(Error)
+    })
+
+    it('fails to create a pool with unknown hook flags', async () => {
+      await expect(createHookPool(hookProgram, 1 << 4)).to.be.rejectedWith(Error)
+    })
+
+    it('creates pools with hooks', async () => {
+      hookedPool = await createHookPool(
+        hookProgram,
+        BEFORE_SWAP_FLAG | AFTER_SWAP_FLAG | AFTER_MODIFY_POSITION_FLAG
+      )
+      rejectingPool = await createHookPool(rejectingHookProgram, BEFORE_MODIFY_POSITION_FLAG)
+
+      const poolStateData = await coreProgram.account.poolState.fetch(hookedPool.poolState)
+      assert(poolStateData.hookProgram.equals(hookProgram))
+      assert.equal(poolStateData.hookFlags, BEFORE_SWAP_FLAG | AFTER_SWAP_FLAG | AFTER_MODIFY_POSITION_FLAG)
+    })
+
+    it('fails to mint if the hook program is missing', async () => {
+      await expect(mintHookPosition(hookedPool, [])).to.be.rejectedWith(Error)
+    })
+
+    it('fails to mint with another hook program', async () => {
+      await expect(mintHookPosition(hookedPool, [programMeta(rejectingHookProgram)]))
+        .to.be.rejectedWith(Error)
+    })
+
+    it('fails to mint if the hook program rejects', async () => {
+      await expect(mintHookPosition(rejectingPool, [programMeta(rejectingHookProgram)]))
+        .to.be.rejectedWith(Error)
+    })
+
+    it('invokes only the enabled liquidity hooks, after the liquidity change', async () => {
+      const logs = await mintHookPosition(hookedPool, [programMeta(hookProgram)])
+      const mintIndex = logs.indexOf('Program log: Instruction: MintCallback')
+      const afterIndex = logs.indexOf('Program log: Instruction: AfterModifyPositionHook')
+      assert.isAbove(mintIndex, -1)
+      assert.isAbove(afterIndex, mintIndex)
+      assert.notInclude(logs, 'Program log: Instruction: BeforeModifyPositionHook')
+    })
+
+    it('fails to swap if the hook program is missing', async () => {
+      await expect(swapHookPool(hookedPool, swapAccounts(hookedPool))).to.be.rejectedWith(Error)
+    })
+
+    it('fails to swap with another hook program', async () => {
+      await expect(swapHookPool(hookedPool, [
+        programMeta(rejectingHookProgram),
+        ...swapAccounts(hookedPool),
+      ])).to.be.rejectedWith(Error)
+    })
+
+    it('invokes swap hooks before and after the swap', async () => {
+      const { raw: logs } = await swapHookPool(hookedPool, [
+        programMeta(hookProgram),
+        ...swapAccounts(hookedPool),
+      ])
+      const beforeIndex = logs.indexOf('Program log: Instruction: BeforeSwapHook')
+      const callbackIndex = logs.indexOf('Program log: Instruction: SwapCallback')
+      const afterIndex = logs.indexOf('Program log: Instruction: AfterSwapHook')
+      assert.isAbove(beforeIndex, -1)
+      assert.isAbove(callbackIndex, beforeIndex)
+      assert.isAbove(afterIndex, callbackIndex)
+    })
+  })
+
+  describe('#swap bitmap validation', () => {
+    const deadline = new BN(Date.now() / 1000 + 1_000_000)
+
+    type EmptyPool = {
+      poolState: web3.PublicKey,
+      vault0: web3.PublicKey,
+      vault1: web3.PublicKey,
+      wallet0: web3.PublicKey,
+      wallet1: web3.PublicKey,
+      observationState: web3.PublicKey,
+      bitmapState: web3.PublicKey,
+    }
+
+    // creates a pool without liquidity at tick 0 for a new token pair
+    const createEmptyPool = async (): Promise<EmptyPool> => {
+      let tokenA = await Token.createMint(connection, mintAuthority, mintAuthority.publicKey, null, 8, TOKEN_PROGRAM_ID)
+      let tokenB = await Token.createMint(connection, mintAuthority, mintAuthority.publicKey, null, 8, TOKEN_PROGRAM_ID)
+      if (tokenA.publicKey.toString() > tokenB.publicKey.toString()) {
+        [tokenA, tokenB] = [tokenB, tokenA]
+      }
+      const seeds = [tokenA.publicKey.toBuffer(), tokenB.publicKey.toBuffer(), u32ToSeed(fee)]
+      const [poolState] = await PublicKey.findProgramAddress([POOL_SEED, ...seeds], coreProgram.programId)
+      const [observationState] = await PublicKey.findProgramAddress(
+        [OBSERVATION_SEED, ...seeds, u16ToSeed(0)],
+        coreProgram.programId
+      )
+      const [pairState] = await PublicKey.findProgramAddress(
+        [PAIR_SEED, tokenA.publicKey.toBuffer(), tokenB.publicKey.toBuffer()],
+        coreProgram.programId
+      )
+      const [bitmapState] = await PublicKey.findProgramAddress(
+        [BITMAP_SEED, ...seeds, u16ToSeed(0)],
+        coreProgram.programId
+      )
+
+      await coreProgram.rpc.createAndInitPool(new BN(1).shln(32), PublicKey.default, 0, {
+        accounts: {
+          poolCreator: owner,
+          token0: tokenA.publicKey,
+          token1: tokenB.publicKey,
+          feeState,
+          poolState,
+          initialObservationState: observationState,
+          pairState,
+          systemProgram: SystemProgram.programId,
+          rent: web3.SYSVAR_RENT_PUBKEY,
         }
+      })
+
+      const vault0 = await Token.getAssociatedTokenAddress(
+        ASSOCIATED_TOKEN_PROGRAM_ID,
+        TOKEN_PROGRAM_ID,
+        tokenA.publicKey,
+        poolState,
+        true
       )
-      vaultBalanceA0 = await token0.getAccountInfo(vaultA0)
-      vaultBalanceA1 = await token1.getAccountInfo(vaultA1)
-      vaultBalanceB1 = await token1.getAccountInfo(vaultB1)
-      vaultBalanceB2 = await token2.getAccountInfo(vaultB2)
-      console.log(
-        'vault balances after',
-        vaultBalanceA0.amount.toNumber(),
-        vaultBalanceA1.amount.toNumber(),
-        vaultBalanceB1.amount.toNumber(),
-        vaultBalanceB2.amount.toNumber()
+      const vault1 = await Token.getAssociatedTokenAddress(
+        ASSOCIATED_TOKEN_PROGRAM_ID,
+        TOKEN_PROGRAM_ID,
+        tokenB.publicKey,
+        poolState,
+        true
       )
+      const createVaultsTx = new Transaction()
+      createVaultsTx.instructions = [
+        Token.createAssociatedTokenAccountInstruction(
+          ASSOCIATED_TOKEN_PROGRAM_ID,
+          TOKEN_PROGRAM_ID,
+          tokenA.publicKey,
+          vault0,
+          poolState,
+          owner
+        ),
+        Token.createAssociatedTokenAccountInstruction(
+          ASSOCIATED_TOKEN_PROGRAM_ID,
+          TOKEN_PROGRAM_ID,
+          tokenB.publicKey,
+          vault1,
+          poolState,
+          owner
+        ),
+      ]
+      await anchor.getProvider().send(createVaultsTx)
 
-      const poolStateDataAfter = await coreProgram.account.poolState.fetch(poolAState)
-      console.log('pool A price after', poolStateDataAfter.sqrtPriceX32.toNumber())
-      console.log('pool A tick after', poolStateDataAfter.tick)
+      const wallet0 = await tokenA.createAssociatedTokenAccount(owner)
+      const wallet1 = await tokenB.createAssociatedTokenAccount(owner)
+      await tokenA.mintTo(wallet0, mintAuthority, [], 100_000_000)
+      await tokenB.mintTo(wallet1, mintAuthority, [], 100_000_000)
 
-      token2AccountInfo = await token2.getAccountInfo(minterWallet2)
-      console.log('token 2 balance after', token2AccountInfo.amount.toNumber())
+      return { poolState, vault0, vault1, wallet0, wallet1, observationState, bitmapState }
+    }
+
+    // swaps token_1 for token_0 in a pool without liquidity. The price moves to the end of
+    // bitmap word 0 and stops there, since the bitmap of word 1 is not supplied
+    const swapToWordBoundary = (pool: EmptyPool) => coreProgram.simulate.exactInputSingle(
+      deadline,
+      new BN(1_000),
+      new BN(0),
+      new BN(0),
+      true,
+      {
+        accounts: {
+          signer: owner,
+          factoryState,
+          poolState: pool.poolState,
+          inputTokenAccount: pool.wallet1,
+          outputTokenAccount: pool.wallet0,
+          inputVault: pool.vault1,
+          outputVault: pool.vault0,
+          lastObservationState: pool.observationState,
+          coreProgram: coreProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        remainingAccounts: [
+          { pubkey: pool.bitmapState, isSigner: false, isWritable: true },
+          { pubkey: pool.observationState, isSigner: false, isWritable: true },
+        ],
+      }
+    )
+
+    it('uses fewer compute units with the stored bump than with find_program_address', async () => {
+      const initializedPool = await createEmptyPool()
+      const uninitializedPool = await createEmptyPool()
+      await coreProgram.rpc.initBitmapAccount(0, {
+        accounts: {
+          signer: owner,
+          poolState: initializedPool.poolState,
+          bitmapState: initializedPool.bitmapState,
+          systemProgram: SystemProgram.programId,
+        }
+      })
+
+      // an initialized bitmap is validated with its stored bump, while the address of an
+      // uninitialized bitmap is found with find_program_address as before
+      const { raw: storedBumpLogs, events: storedBumpEvents } = await swapToWordBoundary(initializedPool)
+      const { raw: findAddressLogs, events: findAddressEvents } = await swapToWordBoundary(uninitializedPool)
+
+      // both swaps take the same path
+      const storedBumpSwap = storedBumpEvents.find(event => event.name == 'SwapEvent')
+      const findAddressSwap = findAddressEvents.find(event => event.name == 'SwapEvent')
+      assert.equal(storedBumpSwap.data.tick, findAddressSwap.data.tick)
+      assert((storedBumpSwap.data.sqrtPriceX32 as BN).eq(findAddressSwap.data.sqrtPriceX32))
+
+      const storedBumpUnits = computeUnitsConsumed(storedBumpLogs as string[], coreProgram.programId.toString())
+      const findAddressUnits = computeUnitsConsumed(findAddressLogs as string[], coreProgram.programId.toString())
+      assert.isBelow(storedBumpUnits, findAddressUnits)
     })
   })
 
-  describe('Completely close position and deallocate ticks', () => {
-    it('update observation accounts', async () => {
-      const {
-        observationIndex,
-        observationCardinalityNext
-      } = await coreProgram.account.poolState.fetch(poolAState)
+  describe('#vote_gauge', () => {
+    const epochDuration = 10
+    const emissionsPerEpoch = new BN(1_000_000)
+
+    let lockerKey: web3.PublicKey
+    let escrowKey: web3.PublicKey
+    let gaugeController: web3.PublicKey
+    let gaugeA: web3.PublicKey
+    let gaugeVoter: web3.PublicKey
+    let voteEpoch: number
+    let epochState: web3.PublicKey
+    let epochGaugeA: web3.PublicKey
+
+    const currentEpoch = async () => {
+      const { startTime } = await coreProgram.account.gaugeControllerState.fetch(gaugeController)
+      return Math.floor((Date.now() / 1000 - (startTime as BN).toNumber()) / epochDuration)
+    }
 
-      const { blockTimestamp: lastBlockTime } = await coreProgram.account.observationState.fetch(lastObservationAState)
+    before(async () => {
+      const solanaProvider = SolanaProvider.init({
+        connection,
+        wallet,
+        opts: {},
+      })
+      const base = new Keypair()
+      const [governorKey] = await findGovernorAddress(base.publicKey);
+      [lockerKey] = await findLockerAddress(base.publicKey);
+      [escrowKey] = await findEscrowAddress(lockerKey, owner)
+
+      // smart wallet and governor owning the locker
+      const gokiSdk = GokiSDK.load({ provider: solanaProvider })
+      const { smartWalletWrapper, tx: createSmartWalletTx } = await gokiSdk.newSmartWallet({
+        numOwners: 1,
+        owners: [governorKey],
+        threshold: new BN(1),
+        base,
+      })
+      await anchor.getProvider().send(createSmartWalletTx.build(), createSmartWalletTx.signers)
 
-      const slot = await connection.getSlot()
-      const blockTimestamp = await connection.getBlockTime(slot)
+      const govToken = await Token.createMint(
+        connection,
+        mintAuthority,
+        mintAuthority.publicKey,
+        null,
+        6,
+        TOKEN_PROGRAM_ID
+      )
+      const govTokenAccount = await govToken.createAssociatedTokenAccount(owner)
+      await govToken.mintTo(govTokenAccount, mintAuthority, [], 1_000_000)
+
+      const tribecaSdk = TribecaSDK.load({ provider: solanaProvider })
+      const { tx: createGovernorTx } = await tribecaSdk.govern.createGovernor({
+        electorate: lockerKey,
+        smartWallet: smartWalletWrapper.key,
+        baseKP: base,
+      })
+      await anchor.getProvider().send(createGovernorTx.build(), createGovernorTx.signers)
 
-      // If current observation account will expire in 3 seconds, we sleep for this time
-      // before recalculating the observation states
-      if (Math.floor(lastBlockTime / 14) == Math.floor(blockTimestamp / 14) && lastBlockTime % 14 >= 11) {
-        await new Promise(r => setTimeout(r, 3000))
+      const { tx: createLockerTx } = await tribecaSdk.createLocker({
+        baseKP: base,
+        governor: governorKey,
+        govTokenMint: govToken.publicKey,
+        minStakeDuration: new BN(1),
+      })
+      await anchor.getProvider().send(createLockerTx.build(), createLockerTx.signers)
+
+      // lock governance tokens for voting power
+      const lockerWrapper = await LockerWrapper.load(tribecaSdk, lockerKey, governorKey)
+      const lockTx = await lockerWrapper.lockTokens({
+        amount: new BN(1_000_000),
+        duration: new BN(365 * 24 * 3600),
+      })
+      await anchor.getProvider().send(lockTx.build(), lockTx.signers);
+
+      [gaugeController] = await PublicKey.findProgramAddress(
+        [GAUGE_CONTROLLER_SEED],
+        coreProgram.programId
+      );
+      [gaugeA] = await PublicKey.findProgramAddress(
+        [GAUGE_SEED, poolAState.toBuffer()],
+        coreProgram.programId
+      );
+      [gaugeVoter] = await PublicKey.findProgramAddress(
+        [GAUGE_VOTER_SEED, escrowKey.toBuffer()],
+        coreProgram.programId
+      )
+    })
+
+    it('fails if not called by the owner', async () => {
+      await expect(coreProgram.rpc.createGaugeController(epochDuration, emissionsPerEpoch, {
+        accounts: {
+          owner: notOwner.publicKey,
+          factoryState,
+          locker: lockerKey,
+          gaugeControllerState: gaugeController,
+          systemProgram: SystemProgram.programId,
+        },
+        signers: [notOwner],
+      })).to.be.rejectedWith(Error)
+    })
+
+    it('creates the gauge controller and a gauge for a pool', async () => {
+      await coreProgram.rpc.createGaugeController(epochDuration, emissionsPerEpoch, {
+        accounts: {
+          owner,
+          factoryState,
+          locker: lockerKey,
+          gaugeControllerState: gaugeController,
+          systemProgram: SystemProgram.programId,
+        }
+      })
+      const controllerData = await coreProgram.account.gaugeControllerState.fetch(gaugeController)
+      assert(controllerData.locker.equals(lockerKey))
+      assert.equal(controllerData.epochDuration, epochDuration)
+      assert((controllerData.emissionsPerEpoch as BN).eq(emissionsPerEpoch))
+
+      await coreProgram.rpc.createGauge({
+        accounts: {
+          owner,
+          factoryState,
+          poolState: poolAState,
+          gaugeState: gaugeA,
+          systemProgram: SystemProgram.programId,
+        }
+      })
+      const gaugeData = await coreProgram.account.gaugeState.fetch(gaugeA)
+      assert(gaugeData.pool.equals(poolAState))
+
+      await coreProgram.rpc.initGaugeVoterAccount({
+        accounts: {
+          payer: owner,
+          gaugeControllerState: gaugeController,
+          escrow: escrowKey,
+          gaugeVoterState: gaugeVoter,
+          systemProgram: SystemProgram.programId,
+        }
+      })
+    })
+
+    it('votes for the gauge in the next epoch', async () => {
+      voteEpoch = await currentEpoch() + 1;
+      [epochState] = await PublicKey.findProgramAddress(
+        [EPOCH_SEED, u32ToSeed(voteEpoch)],
+        coreProgram.programId
+      );
+      [epochGaugeA] = await PublicKey.findProgramAddress(
+        [EPOCH_GAUGE_SEED, gaugeA.toBuffer(), u32ToSeed(voteEpoch)],
+        coreProgram.programId
+      )
+      await coreProgram.rpc.initEpochAccount(voteEpoch, {
+        accounts: {
+          payer: owner,
+          epochState,
+          systemProgram: SystemProgram.programId,
+        }
+      })
+      await coreProgram.rpc.initEpochGaugeAccount(voteEpoch, {
+        accounts: {
+          payer: owner,
+          gaugeState: gaugeA,
+          epochGaugeState: epochGaugeA,
+          systemProgram: SystemProgram.programId,
+        }
+      })
+
+      const [gaugeVote] = await PublicKey.findProgramAddress(
+        [GAUGE_VOTE_SEED, escrowKey.toBuffer(), gaugeA.toBuffer(), u32ToSeed(voteEpoch)],
+        coreProgram.programId
+      )
+      const voteAccounts = {
+        voter: owner,
+        gaugeControllerState: gaugeController,
+        locker: lockerKey,
+        escrow: escrowKey,
+        gaugeVoterState: gaugeVoter,
+        gaugeState: gaugeA,
+        epochState,
+        epochGaugeState: epochGaugeA,
+        gaugeVoteState: gaugeVote,
+        systemProgram: SystemProgram.programId,
       }
-      if (Math.floor(lastBlockTime / 14) > Math.floor(blockTimestamp / 14)) {
-        lastObservationAState = (await PublicKey.findProgramAddress(
-          [
-            OBSERVATION_SEED,
-            token0.publicKey.toBuffer(),
-            token1.publicKey.toBuffer(),
-            u32ToSeed(fee),
-            u16ToSeed(observationIndex)
-          ],
-          coreProgram.programId
-        ))[0]
+      await coreProgram.rpc.voteGauge(voteEpoch, 10_000, { accounts: voteAccounts })
+
+      const { weight } = await coreProgram.account.epochGaugeState.fetch(epochGaugeA)
+      const { totalWeight } = await coreProgram.account.epochState.fetch(epochState)
+      assert((weight as BN).gtn(0))
+      assert((totalWeight as BN).eq(weight as BN))
+      const voteData = await coreProgram.account.gaugeVoteState.fetch(gaugeVote)
+      assert.equal(voteData.weightBps, 10_000)
+      assert((voteData.weight as BN).eq(weight as BN))
+    })
 
-        nextObservationAState = (await PublicKey.findProgramAddress(
-          [
-            OBSERVATION_SEED,
-            token0.publicKey.toBuffer(),
-            token1.publicKey.toBuffer(),
-            u32ToSeed(fee),
-            u16ToSeed((observationIndex + 1) % observationCardinalityNext)
-          ],
-          coreProgram.programId
-        ))[0]
+    it('revokes and recasts the vote before the epoch starts', async () => {
+      const [gaugeVote] = await PublicKey.findProgramAddress(
+        [GAUGE_VOTE_SEED, escrowKey.toBuffer(), gaugeA.toBuffer(), u32ToSeed(voteEpoch)],
+        coreProgram.programId
+      )
+      const voteAccounts = {
+        voter: owner,
+        gaugeControllerState: gaugeController,
+        locker: lockerKey,
+        escrow: escrowKey,
+        gaugeVoterState: gaugeVoter,
+        gaugeState: gaugeA,
+        epochState,
+        epochGaugeState: epochGaugeA,
+        gaugeVoteState: gaugeVote,
+        systemProgram: SystemProgram.programId,
       }
+
+      await coreProgram.rpc.voteGauge(voteEpoch, 0, { accounts: voteAccounts })
+      const { weight: revokedWeight } = await coreProgram.account.epochGaugeState.fetch(epochGaugeA)
+      const { totalWeight: revokedTotalWeight } = await coreProgram.account.epochState.fetch(epochState)
+      assert((revokedWeight as BN).eqn(0))
+      assert((revokedTotalWeight as BN).eqn(0))
+
+      // the revoked weight can be cast again
+      await coreProgram.rpc.voteGauge(voteEpoch, 10_000, { accounts: voteAccounts })
+      const { weight } = await coreProgram.account.epochGaugeState.fetch(epochGaugeA)
+      assert((weight as BN).gtn(0))
+      const { weightBps } = await coreProgram.account.gaugeVoterState.fetch(gaugeVoter)
+      assert.equal(weightBps, 10_000)
     })
 
-    it('burn entire of the position liquidity as owner', async () => {
-      const { liquidity } = await coreProgram.account.tokenizedPositionState.fetch(tokenizedPositionAState)
-      console.log('liquidity in position', liquidity)
-      const deadline = new BN(Date.now() / 1000 + 10_000)
+    it('fails to sync the gauge before the epoch starts', async () => {
+      await expect(coreProgram.rpc.syncGauge(voteEpoch, {
+        accounts: {
+          gaugeControllerState: gaugeController,
+          gaugeState: gaugeA,
+          epochState,
+          epochGaugeState: epochGaugeA,
+        }
+      })).to.be.rejectedWith(Error)
+    })
 
-      const tx = new Transaction()
-      tx.instructions = [
-        coreProgram.instruction.decreaseLiquidity(
-          liquidity,
-          new BN(0),
-          new BN(0),
-          deadline, {
-          accounts: {
-            ownerOrDelegate: owner,
-            nftAccount: positionANftAccount,
-            tokenizedPositionState: tokenizedPositionAState,
-            factoryState,
-            poolState: poolAState,
-            corePositionState: corePositionAState,
-            tickLowerState: tickLowerAState,
-            tickUpperState: tickUpperAState,
-            bitmapLowerState: bitmapLowerAState,
-            bitmapUpperState: bitmapUpperAState,
-            lastObservationState: lastObservationAState,
-            coreProgram: coreProgram.programId
-          },
-          remainingAccounts: [{
-            pubkey: nextObservationAState,
-            isSigner: false,
-            isWritable: true
-          }],
+    it('sets gauge emissions from votes once the epoch starts', async () => {
+      while (await currentEpoch() < voteEpoch) {
+        await new Promise(r => setTimeout(r, 1000))
+      }
+      await coreProgram.rpc.syncGauge(voteEpoch, {
+        accounts: {
+          gaugeControllerState: gaugeController,
+          gaugeState: gaugeA,
+          epochState,
+          epochGaugeState: epochGaugeA,
         }
-        ),
-        coreProgram.instruction.closeTickAccount({
-          accounts: {
-            recipient: owner,
-            tickState: tickLowerAState,
-          }
-        }),
-        coreProgram.instruction.closeTickAccount({
-          accounts: {
-            recipient: owner,
-            tickState: tickUpperAState,
-          }
-        })
-      ]
-      tx.recentBlockhash = (await connection.getRecentBlockhash()).blockhash
-      await anchor.getProvider().send(tx)
+      })
+      const gaugeData = await coreProgram.account.gaugeState.fetch(gaugeA)
+      assert.equal(gaugeData.epoch, voteEpoch)
+      // the only gauge voted for receives all emissions
+      assert((gaugeData.emissions as BN).eq(emissionsPerEpoch))
     })
   })
 })
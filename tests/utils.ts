@@ -1,4 +1,16 @@
 import BN from "bn.js"
+import { Connection } from "@solana/web3.js"
+
+export const PAIR_SEED = Buffer.from("pr")
+export const OPERATOR_APPROVAL_SEED = Buffer.from("oa")
+export const COLLECTION_SEED = Buffer.from("c")
+export const POSITION_LOCK_SEED = Buffer.from("lk")
+export const GAUGE_CONTROLLER_SEED = Buffer.from("gc")
+export const GAUGE_SEED = Buffer.from("g")
+export const EPOCH_SEED = Buffer.from("e")
+export const EPOCH_GAUGE_SEED = Buffer.from("eg")
+export const GAUGE_VOTER_SEED = Buffer.from("gv")
+export const GAUGE_VOTE_SEED = Buffer.from("gvt")
 
 export const MIN_SQRT_RATIO = new BN(65536)
 export const MAX_SQRT_RATIO = new BN(281474976710656)
@@ -7,3 +19,45 @@ export const MIN_TICK = -221818
 export const MAX_TICK = 221818
 
 export const MaxU64 = new BN(2).pow(new BN(64)).subn(1)
+
+/**
+ * Returns the compute units consumed by a program, as reported in transaction logs
+ *
+ * @param logs Transaction logs
+ * @param programId The program whose consumption is read. The outermost invocation is used,
+ * which includes compute units used by CPIs
+ */
+export function computeUnitsConsumed(logs: string[], programId: string): number {
+  const pattern = new RegExp(`^Program ${programId} consumed (\\d+) of \\d+ compute units$`)
+  const consumed = logs
+    .map(log => log.match(pattern))
+    .filter(match => match !== null)
+    .map(match => Number(match[1]))
+  return consumed.length > 0 ? Math.max(...consumed) : 0
+}
+
+/**
+ * Returns the data set by a program with `set_return_data`, as reported in transaction logs
+ *
+ * @param logs Transaction logs
+ * @param programId The program that set the return data
+ */
+export function returnData(logs: string[], programId: string): Buffer {
+  const prefix = `Program return: ${programId} `
+  const log = logs.find(log => log.startsWith(prefix))
+  return log === undefined ? Buffer.alloc(0) : Buffer.from(log.slice(prefix.length), 'base64')
+}
+
+/**
+ * Returns the data set by the outermost instruction of a confirmed transaction, read from
+ * the transaction metadata
+ *
+ * @param connection Connection to the cluster
+ * @param signature Signature of a transaction sent with at least 'confirmed' commitment
+ */
+export async function transactionReturnData(connection: Connection, signature: string): Promise<Buffer> {
+  const { meta } = await connection.getTransaction(signature, { commitment: 'confirmed' })
+  // returnData is missing from the metadata type of this web3.js version
+  const data = (meta as any).returnData?.data
+  return data === undefined ? Buffer.alloc(0) : Buffer.from(data[0], 'base64')
+}